@@ -1,10 +1,11 @@
 //! boot.json的主要數據結構
 
+use std::collections::BTreeMap;
 use glob::glob;
 use log::trace;
 use serde::{ Deserialize, Serialize };
 use nest_struct::nest_struct;
-use rust_i18n::t; // 添加本地化支持
+use crate::error::Error;
 
 /// boot.json的主要數據結構
 /// 包含mod的所有元數據和資源文件列表
@@ -16,6 +17,9 @@ pub struct BootJson {
   pub name: String,
   /// mod版本號
   pub version: Option<String>,
+  /// mod的顯示名稱
+  #[serde(skip_serializing_if = "Option::is_none")]
+  nickName: Option<NickName>,
   /// 額外文件列表(如README, License等)
   additionFile: Option<Vec<String>>,
   /// 圖片資源文件列表
@@ -26,6 +30,15 @@ pub struct BootJson {
   tweeFileList: Option<Vec<String>>,
   /// CSS樣式文件列表
   styleFileList: Option<Vec<String>>,
+  /// 在 ModLoader 加載其他mod之前執行的腳本
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  scriptFileList_preload: Option<Vec<String>>,
+  /// 在遊戲初始化之前執行的腳本
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  scriptFileList_earlyload: Option<Vec<String>>,
+  /// 作為 `<script>` 注入頁面的腳本
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  scriptFileList_inject_early: Option<Vec<String>>,
   /// 插件配置列表
   addonPlugin: Option<Vec<nest! {
     /// 目標mod名稱
@@ -50,6 +63,16 @@ pub struct BootJson {
   }>>,
 }
 
+/// mod的顯示名稱, 可以是字符串或按語言區分的對象
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(untagged)]
+pub enum NickName {
+  /// 單一名稱
+  Text(String),
+  /// 語言 -> 名稱
+  Locales(BTreeMap<String, String>),
+}
+
 /// BootJson結構體的方法實現
 impl BootJson {
   /// 從文件路徑創建BootJson實例
@@ -60,14 +83,29 @@ impl BootJson {
   /// ```
   /// # 錯誤處理
   /// - 返回錯誤如果文件不存在或格式錯誤
-  pub fn new(path: &str) -> Result<BootJson, Box<dyn std::error::Error>> {
+  pub fn new(path: &str) -> Result<BootJson, Error> {
     let file_content = std::fs
       ::read(path)
-      .map_err(|e| t!("filesystem.read_file_failed", path = path, e = e.to_string()))?;
+      .map_err(|source| Error::Io { path: path.into(), source })?;
+
+    BootJson::from_slice(&file_content).map_err(|e| {
+      match e {
+        Error::BootJson { msg, pos, .. } => Error::BootJson { path: path.into(), msg, pos },
+        e => e,
+      }
+    })
+  }
 
+  /// 從 boot.json 的內容創建BootJson實例(如 zip 中的 boot.json)
+  /// * `content` - boot.json的內容
+  pub fn from_slice(content: &[u8]) -> Result<BootJson, Error> {
     let mut json: BootJson = serde_json
-      ::from_slice(&file_content)
-      .map_err(|e| t!("json.parse_error", msg = e.to_string()))?;
+      ::from_slice(content)
+      .map_err(|e| Error::BootJson {
+        path: "boot.json".into(),
+        msg: e.to_string(),
+        pos: Some((e.line(), e.column())),
+      })?;
 
     // 初始化所有Option字段
     // json.name = Some(json.name.unwrap_or_else(|| "unknown".to_string()));
@@ -81,15 +119,36 @@ impl BootJson {
     Ok(json)
   }
 
+  /// 創建只有名稱和版本的 BootJson, 所有列表為空
+  pub fn empty(name: String, version: String) -> BootJson {
+    BootJson {
+      name,
+      version: Some(version),
+      nickName: None,
+      additionFile: Some(Vec::new()),
+      imgFileList: Some(Vec::new()),
+      scriptFileList: Some(Vec::new()),
+      tweeFileList: Some(Vec::new()),
+      styleFileList: Some(Vec::new()),
+      scriptFileList_preload: None,
+      scriptFileList_earlyload: None,
+      scriptFileList_inject_early: None,
+      addonPlugin: Some(Vec::new()),
+      dependenceInfo: Some(Vec::new()),
+    }
+  }
+
   /// 更新文件列表
   /// * `cwd` - 當前工作目錄路徑
+  /// * `source_map` - 是否把 `*.js.map` 加入 additionFile
   /// * 返回 Result
   ///
   /// 該函數會掃描工作目錄下的所有相關文件並更新到對應的文件列表中
   pub fn update_file_lists(
     &mut self,
-    cwd: &std::path::Path
-  ) -> Result<(), Box<dyn std::error::Error>> {
+    cwd: &std::path::Path,
+    source_map: bool
+  ) -> Result<(), Error> {
     let show_cwd = cwd.display();
 
     // 確保所有列表已初始化
@@ -111,14 +170,93 @@ impl BootJson {
 
     // 處理各類型文件
     scan_and_add_files(&format!("{}/**/*.png", show_cwd), img_files, cwd)?;
+    // 已經在 early/preload 列表中的腳本不再加入 scriptFileList
+    let listed_scripts = script_files.len();
     scan_and_add_files(&format!("{}/**/*.js", show_cwd), script_files, cwd)?;
+    let early: Vec<&String> = [&self.scriptFileList_preload, &self.scriptFileList_earlyload, &self.scriptFileList_inject_early]
+      .into_iter()
+      .flatten()
+      .flatten()
+      .collect();
+    let mut found = script_files.split_off(listed_scripts);
+    found.retain(|f| !early.contains(&f));
+    script_files.append(&mut found);
     scan_and_add_files(&format!("{}/**/*.css", show_cwd), style_files, cwd)?;
     scan_and_add_files(&format!("{}/**/*.twee", show_cwd), twee_files, cwd)?;
-    scan_and_add_files(&format!("{}/**/*.js.map", show_cwd), addition_files, cwd)?;
+    if source_map {
+      scan_and_add_files(&format!("{}/**/*.js.map", show_cwd), addition_files, cwd)?;
+    } else {
+      addition_files.retain(|f| !f.ends_with(".js.map"));
+    }
 
     Ok(())
   }
 
+  /// 按順序執行或加載的列表: (列表名稱, 文件)
+  pub fn ordered_lists_mut(&mut self) -> [(&'static str, &mut Vec<String>); 2] {
+    [
+      ("scriptFileList", self.scriptFileList.get_or_insert_with(Vec::new)),
+      ("styleFileList", self.styleFileList.get_or_insert_with(Vec::new)),
+    ]
+  }
+
+  /// 用 bundle 替換 scriptFileList 中已經打包的模塊
+  /// * `modules` - 打包進 bundle 的文件, 連同它們的 `.map` 從列表中去掉
+  /// * `bundles` - bundle 文件, 按依賴順序加在 scriptFileList 末尾
+  pub fn replace_scripts(&mut self, modules: &[String], bundles: &[String]) {
+    let script_files = self.scriptFileList.get_or_insert_with(Vec::new);
+    script_files.retain(|f| !modules.contains(f) && !bundles.contains(f));
+    script_files.extend(bundles.iter().cloned());
+    if let Some(addition_files) = &mut self.additionFile {
+      addition_files.retain(|f| !f.strip_suffix(".map").is_some_and(|js| modules.iter().any(|m| m == js)));
+    }
+  }
+
+  /// 取得顯示名稱
+  /// * 多語言時優先使用 `en`, 否則使用第一個
+  pub fn nick_name(&self) -> Option<&str> {
+    match self.nickName.as_ref()? {
+      NickName::Text(s) => Some(s),
+      NickName::Locales(map) =>
+        map
+          .get("en")
+          .or_else(|| map.values().next())
+          .map(|s| s.as_str()),
+    }
+  }
+
+  /// 取得 dependenceInfo 中 GameVersion 的版本
+  /// * 去掉版本要求前面的比較符號, 如 `>=0.5.2.8` -> `0.5.2.8`
+  pub fn game_version(&self) -> Option<&str> {
+    self.dependenceInfo
+      .as_ref()?
+      .iter()
+      .find(|d| d.modName == "GameVersion")
+      .map(|d| d.version.trim_start_matches(['>', '<', '=', '^', '~', ' ']).trim())
+  }
+
+  /// 所有文件列表: (列表名稱, 文件)
+  /// * early/preload 列表只在 boot.json 中有時返回
+  pub fn file_lists(&self) -> Vec<(&'static str, &[String])> {
+    let mut lists = vec![
+      ("additionFile", self.additionFile.as_deref().unwrap_or_default()),
+      ("imgFileList", self.imgFileList.as_deref().unwrap_or_default()),
+      ("scriptFileList", self.scriptFileList.as_deref().unwrap_or_default()),
+      ("tweeFileList", self.tweeFileList.as_deref().unwrap_or_default()),
+      ("styleFileList", self.styleFileList.as_deref().unwrap_or_default()),
+    ];
+    for (list, files) in [
+      ("scriptFileList_preload", &self.scriptFileList_preload),
+      ("scriptFileList_earlyload", &self.scriptFileList_earlyload),
+      ("scriptFileList_inject_early", &self.scriptFileList_inject_early),
+    ] {
+      if let Some(files) = files {
+        lists.push((list, files));
+      }
+    }
+    lists
+  }
+
   /// 檢查文件是否在任何列表中
   /// # 參數
   /// * `value` - 要檢查的文件路徑
@@ -137,11 +275,14 @@ impl BootJson {
       &self.tweeFileList,
       &self.styleFileList,
       &self.additionFile,
+      &self.scriptFileList_preload,
+      &self.scriptFileList_earlyload,
+      &self.scriptFileList_inject_early,
     ];
 
     trace!("檢查路徑: {}", normalized_path);
     lists.iter().any(|list| {
-      let r = list.as_ref().unwrap().contains(&normalized_path);
+      let r = list.as_ref().is_some_and(|list| list.contains(&normalized_path));
       trace!("    {r}");
       r
     })
@@ -156,7 +297,7 @@ pub fn scan_and_add_files(
   pattern: &str,
   file_list: &mut Vec<String>,
   cwd: &std::path::Path
-) -> Result<(), Box<dyn std::error::Error>> {
+) -> Result<(), Error> {
   for path in glob(pattern)?.flatten() {
     match process_file_path(&path, cwd) {
       Ok(rel_path) => {
@@ -179,11 +320,9 @@ pub fn scan_and_add_files(
 pub fn process_file_path(
   path: &std::path::Path,
   cwd: &std::path::Path
-) -> Result<String, Box<dyn std::error::Error>> {
+) -> Result<String, Error> {
   path
     .strip_prefix(cwd)
     .map(|p| p.to_string_lossy().to_string())
-    .map_err(|_|
-      format!("Failed to strip prefix: {} from path: {}", cwd.display(), path.display()).into()
-    )
+    .map_err(|_| Error::StripPrefix { prefix: cwd.to_path_buf(), path: path.to_path_buf() })
 }
@@ -0,0 +1,165 @@
+//! ts 編譯使用的持久構建目錄
+//!
+//! 每次運行都會清空 `tmp_path`, 在臨時目錄中編譯時 `.tsbuildinfo` 和之前的輸出都會丟失,
+//! tsc 只能全量編譯。所以在 `build_path/<profile>/<mod源文件夾>` 中編譯, 只同步有變化的源文件,
+//! 編譯後只把輸出的 JS 複製回臨時目錄。
+
+use std::collections::BTreeSet;
+use std::fs::{ self, File };
+use std::path::{ Component, Path, PathBuf };
+use log::debug;
+use rust_i18n::t;
+use walkdir::WalkDir;
+use crate::error::{ Error, WithPath };
+
+/// 同步到構建目錄的源文件擴展名
+const SOURCE_EXTENSIONS: [&str; 9] = ["ts", "tsx", "mts", "cts", "js", "jsx", "mjs", "cjs", "json"];
+/// 編譯輸出的文件
+const OUTPUT_SUFFIXES: [&str; 6] = [".js", ".mjs", ".cjs", ".js.map", ".mjs.map", ".cjs.map"];
+/// 記錄上次的編譯參數和 tsconfig.json, 變化時清空構建目錄
+const MANIFEST: &str = ".mod-dev-sources";
+/// 記錄上次同步的源文件列表, 用於刪除已經不存在的源文件
+const SYNCED: &str = ".mod-dev-synced";
+
+/// mod的構建目錄
+/// * `src` - mod源文件夾; 構建目錄中保留它的路徑, 不同 `mods_path` 下的同名mod不會共用構建目錄,
+///   `..` 換成 `__`, 根目錄和盤符去掉
+pub(crate) fn build_dir(build_path: &str, profile: Option<&str>, src: &Path) -> PathBuf {
+  let mut dir = Path::new(build_path).join(profile.unwrap_or("default"));
+  for component in src.components() {
+    match component {
+      Component::Normal(part) => dir.push(part),
+      Component::ParentDir => dir.push("__"),
+      Component::CurDir | Component::RootDir | Component::Prefix(_) => (),
+    }
+  }
+  dir
+}
+
+/// 記錄mod上次構建時 scriptFileList 和 styleFileList 順序的文件
+/// * 在構建目錄旁邊, 清空構建目錄時不會刪除
+pub(crate) fn order_state(build_path: &str, profile: Option<&str>, src: &Path) -> PathBuf {
+  PathBuf::from(format!("{}.order.json", build_dir(build_path, profile, src).display()))
+}
+
+/// 路徑轉換為 `/` 分隔的相對路徑
+fn rel_path(path: &Path, base: &Path) -> Option<String> {
+  path
+    .strip_prefix(base)
+    .ok()
+    .map(|rel| rel.to_string_lossy().replace('\\', "/"))
+}
+
+/// 是否是 ts 文件的輸出, 如 `a.js` 旁邊有 `a.ts`
+fn is_output_of_ts(path: &Path) -> bool {
+  let name = path.to_string_lossy();
+  OUTPUT_SUFFIXES.iter().any(|suffix| {
+    name
+      .strip_suffix(suffix)
+      .is_some_and(|stem| {
+        ["ts", "mts", "cts", "tsx"].iter().any(|ext| Path::new(&format!("{}.{}", stem, ext)).is_file())
+      })
+  })
+}
+
+/// mod中需要同步到構建目錄的源文件(相對路徑)
+/// * 跳過 `.git`, `node_modules` 和已經編譯過的 ts 輸出
+fn collect_sources(src: &Path) -> BTreeSet<String> {
+  WalkDir::new(src)
+    .into_iter()
+    .filter_entry(|e| e.file_name() != ".git" && e.file_name() != "node_modules")
+    .flatten()
+    .filter(|e| e.file_type().is_file())
+    .filter(|e| {
+      let ext = e.path().extension().map(|ext| ext.to_string_lossy().to_lowercase());
+      ext.is_some_and(|ext| SOURCE_EXTENSIONS.contains(&ext.as_str())) && !is_output_of_ts(e.path())
+    })
+    .filter_map(|e| rel_path(e.path(), src))
+    .collect()
+}
+
+/// 文件的 (大小, 修改時間) 是否相同
+fn same_file(a: &Path, b: &Path) -> bool {
+  match (fs::metadata(a), fs::metadata(b)) {
+    (Ok(a), Ok(b)) => a.len() == b.len() && a.modified().ok() == b.modified().ok(),
+    _ => false,
+  }
+}
+
+/// 把源文件同步到構建目錄
+/// * 只複製大小或修改時間不同的文件, 並保留修改時間, 讓 tsc 的增量編譯生效
+/// * 刪除上次同步後源文件夾中已經不存在的源文件和它旁邊的輸出, 增刪的文件由 tsc 的增量編譯處理
+/// * 編譯參數或 tsconfig.json 有變化時先清空構建目錄, 避免留下過期的輸出
+/// * `src` - mod源文件夾
+/// * `build` - 構建目錄
+/// * `args` - 編譯參數
+pub(crate) fn sync_sources(src: &Path, build: &Path, args: &[String]) -> Result<(), Error> {
+  let sources = collect_sources(src);
+  let tsconfig = fs::read_to_string(src.join("tsconfig.json")).unwrap_or_default();
+  let manifest = format!("{}\n--\n{}", args.join("\n"), tsconfig);
+  let manifest_path = build.join(MANIFEST);
+  if build.exists() && fs::read_to_string(&manifest_path).ok().as_deref() != Some(&manifest) {
+    debug!("    {}", t!("ts.build_dir_clean", path = build.display()));
+    fs::remove_dir_all(build).with_path(build)?;
+  }
+
+  let synced_path = build.join(SYNCED);
+  let synced = fs::read_to_string(&synced_path).unwrap_or_default();
+  for rel in synced.lines().filter(|rel| !rel.is_empty() && !sources.contains(*rel)) {
+    let stem = rel.rsplit_once('.').map_or(rel, |(stem, _)| stem);
+    let outputs = OUTPUT_SUFFIXES.iter().map(|suffix| format!("{}{}", stem, suffix));
+    for rel in std::iter::once(rel.to_string()).chain(outputs).filter(|rel| !sources.contains(rel)) {
+      let path = build.join(&rel);
+      if path.is_file() {
+        fs::remove_file(&path).with_path(&path)?;
+      }
+    }
+  }
+
+  for rel in &sources {
+    let from = src.join(rel);
+    let to = build.join(rel);
+    if same_file(&from, &to) {
+      continue;
+    }
+    if let Some(parent) = to.parent() {
+      fs::create_dir_all(parent).with_path(parent)?;
+    }
+    fs::copy(&from, &to).with_path(&from)?;
+    if let Ok(modified) = fs::metadata(&from).and_then(|m| m.modified()) {
+      File::options().write(true).open(&to).and_then(|f| f.set_modified(modified)).with_path(&to)?;
+    }
+  }
+  fs::create_dir_all(build).with_path(build)?;
+  let synced = sources.iter().cloned().collect::<Vec<_>>().join("\n");
+  fs::write(&synced_path, synced).with_path(&synced_path)?;
+  fs::write(&manifest_path, manifest).with_path(&manifest_path)
+}
+
+/// 把構建目錄中編譯輸出的 JS 和 source map 複製到臨時目錄
+/// * `src` - mod源文件夾, 其中的文件不是輸出
+/// # 返回
+/// * 複製的文件(相對路徑)
+pub(crate) fn sync_outputs(src: &Path, build: &Path, tmp: &Path) -> Result<Vec<String>, Error> {
+  let sources = collect_sources(src);
+  let mut synced = Vec::new();
+  for entry in WalkDir::new(build)
+    .into_iter()
+    .filter_entry(|e| e.file_name() != "node_modules")
+    .flatten()
+    .filter(|e| e.file_type().is_file()) {
+    let Some(rel) = rel_path(entry.path(), build) else {
+      continue;
+    };
+    if sources.contains(&rel) || !OUTPUT_SUFFIXES.iter().any(|suffix| rel.ends_with(suffix)) {
+      continue;
+    }
+    let to = tmp.join(&rel);
+    if let Some(parent) = to.parent() {
+      fs::create_dir_all(parent).with_path(parent)?;
+    }
+    fs::copy(entry.path(), &to).with_path(entry.path())?;
+    synced.push(rel);
+  }
+  Ok(synced)
+}
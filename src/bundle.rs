@@ -0,0 +1,694 @@
+//! 把 ES 模塊打包成按順序執行的腳本 (`mods.<name>.bundle`)
+//!
+//! ModLoader 按 `scriptFileList` 的順序把每個文件當作普通腳本執行, 不支持 `import`。
+//! 從每個入口開始跟隨相對路徑的 `import`, 把每個模塊包裝成一個函數, 輸出一個 IIFE。
+//! 所有 bundle 共用一個按 mod 名稱區分的模塊表, 被多個入口導入的模塊只執行一次。
+//!
+//! 改寫只替換導入, 導出語句和對導入綁定的引用, 不改變行數,
+//! 所以 bundle 中每一行都能對應回模塊(或模塊的 source map)中的同一行。
+
+use std::collections::{ BTreeMap, BTreeSet, HashMap };
+use std::fs;
+use std::path::{ Path, PathBuf };
+use log::{ debug, info };
+use oxc_allocator::Allocator;
+use oxc_ast::ast::*;
+use oxc_ast_visit::{ Visit, walk };
+use oxc_parser::Parser;
+use oxc_semantic::{ ScopeFlags, Scoping, SemanticBuilder, SymbolId };
+use oxc_span::{ GetSpan, SourceType, Span };
+use rust_i18n::t;
+use crate::cofg::BundleCofg;
+use crate::error::{ Error, WithPath };
+use crate::sourcemap::{ self, Segment, SourceMap };
+
+/// 生成的變量名前綴, 避免與模塊中的名稱衝突
+const PREFIX: &str = "__bundle_";
+
+/// bundle 開頭的運行時, `{name}` 為 mod 名稱
+const RUNTIME: &str = r#"(function () {
+"use strict";
+var __bundle_registry = globalThis.__mod_dev_bundles || (globalThis.__mod_dev_bundles = {});
+var __bundle_mod = __bundle_registry[{name}] || (__bundle_registry[{name}] = { modules: {}, cache: {} });
+var __bundle_modules = __bundle_mod.modules;
+function __bundle_require(id) {
+  if (Object.prototype.hasOwnProperty.call(__bundle_mod.cache, id)) return __bundle_mod.cache[id];
+  if (!__bundle_modules[id]) throw new Error("[" + {name} + "] module not found: " + id);
+  var exports = __bundle_mod.cache[id] = {};
+  __bundle_modules[id].call(undefined, exports, __bundle_require);
+  return exports;
+}
+function __bundle_export(exports, getters) {
+  for (var key in getters) Object.defineProperty(exports, key, { enumerable: true, get: getters[key] });
+}
+function __bundle_export_star(exports, from) {
+  Object.keys(from).forEach(function (key) {
+    if (key === "default" || Object.prototype.hasOwnProperty.call(exports, key)) return;
+    Object.defineProperty(exports, key, { enumerable: true, get: function () { return from[key]; } });
+  });
+}
+"#;
+
+/// 打包結果
+#[derive(Debug)]
+pub(crate) struct Bundled {
+  /// bundle 文件(相對mod文件夾), 按依賴順序
+  pub bundles: Vec<String>,
+  /// 打包進 bundle 並已刪除的模塊(相對mod文件夾)
+  pub modules: Vec<String>,
+}
+
+/// 導入的綁定如何訪問
+#[derive(Clone)]
+enum Access {
+  /// `import { name } from`, `import name from` (`default`)
+  Member(usize, String),
+  /// `import * as ns from`
+  Namespace(usize),
+}
+
+impl Access {
+  /// 訪問綁定的表達式
+  fn expr(&self) -> String {
+    match self {
+      Access::Member(m, name) if is_ident(name) => format!("{PREFIX}m{m}.{name}"),
+      Access::Member(m, name) => format!("{PREFIX}m{m}[{}]", serde_json::to_string(name).unwrap()),
+      Access::Namespace(m) => format!("{PREFIX}m{m}"),
+    }
+  }
+}
+
+/// 是否可以作為屬性名直接寫在 `.` 後面
+fn is_ident(name: &str) -> bool {
+  !name.is_empty()
+    && !name.starts_with(|c: char| c.is_ascii_digit())
+    && name.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '$')
+}
+
+/// 改寫後的模塊
+struct Module {
+  /// 相對mod文件夾的路徑, 也是模塊的 id
+  id: String,
+  /// 包裝函數第一行: 導入和導出
+  header: String,
+  /// 改寫後的代碼, 行與源文件相同
+  body: String,
+  /// 依賴的模塊, 按導入順序
+  deps: Vec<String>,
+  /// 模塊的 source map 和其所在的目錄
+  map: Option<(PathBuf, SourceMap)>,
+  /// 模塊的 source map 文件
+  map_file: Option<PathBuf>,
+}
+
+/// 模塊中的一個錯誤
+fn module_error(path: &Path, code: &str, offset: u32, msg: String) -> Error {
+  Error::Bundle { path: path.to_path_buf(), msg, pos: Some(sourcemap::position(code, offset as usize)) }
+}
+
+/// 解析模塊中的導入路徑, 返回模塊 id
+/// * 只支持相對路徑, 依次嘗試原路徑, `.ts` 對應的 `.js`, 加上 `.js`, `.mjs`, `/index.js`
+fn resolve(root: &Path, importer: &str, specifier: &str) -> Result<String, String> {
+  if !specifier.starts_with("./") && !specifier.starts_with("../") {
+    return Err(t!("bundle.bare_import", spec = specifier).to_string());
+  }
+  let base = sourcemap::normalize(
+    &Path::new(importer).parent().unwrap_or(Path::new("")).join(specifier)
+  );
+  let mut candidates = vec![base.clone()];
+  if let Some(ext) = base.extension().and_then(|ext| ext.to_str()) {
+    match ext {
+      "ts" | "tsx" => candidates.push(base.with_extension("js")),
+      "mts" => candidates.push(base.with_extension("mjs")),
+      _ => {}
+    }
+  }
+  candidates.extend([
+    PathBuf::from(format!("{}.js", base.display())),
+    PathBuf::from(format!("{}.mjs", base.display())),
+    base.join("index.js"),
+  ]);
+  let found = candidates
+    .into_iter()
+    .find(|path| !path.starts_with("..") && root.join(path).is_file())
+    .ok_or_else(|| t!("bundle.unresolved", spec = specifier).to_string())?;
+  if !found.extension().is_some_and(|ext| ext == "js" || ext == "mjs") {
+    return Err(t!("bundle.unsupported", spec = specifier).to_string());
+  }
+  Ok(found.to_string_lossy().replace('\\', "/"))
+}
+
+/// 收集改寫和模塊信息
+struct Rewriter<'s> {
+  scoping: &'s Scoping,
+  /// 導入的綁定
+  bindings: HashMap<SymbolId, Access>,
+  /// (開始, 結束, 替換成的文本)
+  edits: Vec<(u32, u32, String)>,
+  /// 頂層 `await` 的位置
+  top_level_await: Option<u32>,
+  /// 動態 `import()` 的位置
+  dynamic_import: Option<u32>,
+  /// `import.meta` 的位置
+  import_meta: Option<u32>,
+  /// 在函數中的深度
+  depth: usize,
+}
+
+impl Rewriter<'_> {
+  /// 引用的導入綁定
+  fn binding(&self, ident: &IdentifierReference) -> Option<&Access> {
+    let symbol = self.scoping.get_reference(ident.reference_id.get()?).symbol_id()?;
+    self.bindings.get(&symbol)
+  }
+
+  /// 被調用的導入綁定用 `(0, m.f)` 調用, 與 ES 模塊一樣 `this` 為 `undefined`
+  fn callee(&mut self, callee: &Expression) -> bool {
+    if let Expression::Identifier(ident) = callee && let Some(access) = self.binding(ident) {
+      let expr = format!("(0, {})", access.expr());
+      self.edits.push((ident.span.start, ident.span.end, expr));
+      return true;
+    }
+    false
+  }
+}
+
+impl<'a> Visit<'a> for Rewriter<'_> {
+  fn visit_identifier_reference(&mut self, it: &IdentifierReference<'a>) {
+    if let Some(access) = self.binding(it) {
+      let expr = access.expr();
+      self.edits.push((it.span.start, it.span.end, expr));
+    }
+  }
+
+  fn visit_call_expression(&mut self, it: &CallExpression<'a>) {
+    if !self.callee(&it.callee) {
+      self.visit_expression(&it.callee);
+    }
+    self.visit_arguments(&it.arguments);
+  }
+
+  fn visit_tagged_template_expression(&mut self, it: &TaggedTemplateExpression<'a>) {
+    if !self.callee(&it.tag) {
+      self.visit_expression(&it.tag);
+    }
+    self.visit_template_literal(&it.quasi);
+  }
+
+  fn visit_object_property(&mut self, it: &ObjectProperty<'a>) {
+    if it.shorthand && let Expression::Identifier(ident) = &it.value && let Some(access) = self.binding(ident) {
+      let expr = format!("{}: {}", ident.name, access.expr());
+      self.edits.push((ident.span.start, ident.span.end, expr));
+      return;
+    }
+    walk::walk_object_property(self, it);
+  }
+
+  fn visit_function(&mut self, it: &Function<'a>, flags: ScopeFlags) {
+    self.depth += 1;
+    walk::walk_function(self, it, flags);
+    self.depth -= 1;
+  }
+
+  fn visit_arrow_function_expression(&mut self, it: &ArrowFunctionExpression<'a>) {
+    self.depth += 1;
+    walk::walk_arrow_function_expression(self, it);
+    self.depth -= 1;
+  }
+
+  fn visit_await_expression(&mut self, it: &AwaitExpression<'a>) {
+    if self.depth == 0 {
+      self.top_level_await.get_or_insert(it.span.start);
+    }
+    walk::walk_await_expression(self, it);
+  }
+
+  fn visit_for_of_statement(&mut self, it: &ForOfStatement<'a>) {
+    if it.r#await && self.depth == 0 {
+      self.top_level_await.get_or_insert(it.span.start);
+    }
+    walk::walk_for_of_statement(self, it);
+  }
+
+  fn visit_import_expression(&mut self, it: &ImportExpression<'a>) {
+    self.dynamic_import.get_or_insert(it.span.start);
+    walk::walk_import_expression(self, it);
+  }
+
+  fn visit_meta_property(&mut self, it: &MetaProperty<'a>) {
+    if it.meta.name == "import" {
+      self.import_meta.get_or_insert(it.span.start);
+    }
+  }
+
+  // 導入和導出列表整個去掉, 不需要改寫其中的引用
+  fn visit_import_declaration(&mut self, _it: &ImportDeclaration<'a>) {}
+
+  fn visit_export_all_declaration(&mut self, _it: &ExportAllDeclaration<'a>) {}
+
+  fn visit_export_named_declaration(&mut self, it: &ExportNamedDeclaration<'a>) {
+    if let Some(declaration) = &it.declaration {
+      self.visit_declaration(declaration);
+    }
+  }
+}
+
+/// 替換成同樣寬度的空白, 保留換行, 讓後面的代碼位置不變
+fn blank(code: &str, span: Span) -> (u32, u32, String) {
+  let text = code[span.start as usize..span.end as usize]
+    .chars()
+    .flat_map(|c| match c {
+      '\n' | '\r' => std::iter::repeat_n(c, 1),
+      _ => std::iter::repeat_n(' ', c.len_utf16()),
+    })
+    .collect();
+  (span.start, span.end, text)
+}
+
+/// 解析並改寫一個模塊
+/// * `root` - mod文件夾
+/// * `id` - 模塊路徑(相對mod文件夾)
+fn transform(root: &Path, id: &str) -> Result<Module, Error> {
+  let path = root.join(id);
+  let code = fs::read_to_string(&path).with_path(&path)?;
+  let allocator = Allocator::default();
+  let ret = Parser::new(&allocator, &code, SourceType::mjs()).parse();
+  if let Some(e) = ret.errors.first() {
+    let offset = e.labels
+      .as_ref()
+      .and_then(|labels| labels.first())
+      .map_or(0, |label| label.offset());
+    return Err(module_error(&path, &code, offset as u32, e.message.to_string()));
+  }
+  let program = ret.program;
+  let semantic = SemanticBuilder::new().build(&program).semantic;
+  let error = |span: Span, msg: String| module_error(&path, &code, span.start, msg);
+
+  let mut deps: Vec<String> = Vec::new();
+  let mut dep = |source: &StringLiteral| -> Result<usize, Error> {
+    let resolved = resolve(root, id, &source.value).map_err(|msg| error(source.span, msg))?;
+    Ok(
+      deps
+        .iter()
+        .position(|d| *d == resolved)
+        .unwrap_or_else(|| {
+          deps.push(resolved);
+          deps.len() - 1
+        })
+    )
+  };
+  let mut rewriter = Rewriter {
+    scoping: semantic.scoping(),
+    bindings: HashMap::new(),
+    edits: Vec::new(),
+    top_level_await: None,
+    dynamic_import: None,
+    import_meta: None,
+    depth: 0,
+  };
+  // (導出名稱, 本地綁定, 本地名稱)
+  let mut local_exports: Vec<(String, Option<SymbolId>, String)> = Vec::new();
+  let mut reexports: Vec<(String, Access)> = Vec::new();
+  let mut stars: Vec<usize> = Vec::new();
+  let default_name = format!("{PREFIX}default");
+
+  for stmt in &program.body {
+    match stmt {
+      Statement::ImportDeclaration(decl) => {
+        let m = dep(&decl.source)?;
+        for specifier in decl.specifiers.iter().flatten() {
+          let (local, access) = match specifier {
+            ImportDeclarationSpecifier::ImportSpecifier(s) =>
+              (&s.local, Access::Member(m, s.imported.name().to_string())),
+            ImportDeclarationSpecifier::ImportDefaultSpecifier(s) =>
+              (&s.local, Access::Member(m, "default".to_string())),
+            ImportDeclarationSpecifier::ImportNamespaceSpecifier(s) => (&s.local, Access::Namespace(m)),
+          };
+          if let Some(symbol) = local.symbol_id.get() {
+            rewriter.bindings.insert(symbol, access);
+          }
+        }
+        rewriter.edits.push(blank(&code, decl.span));
+      }
+      Statement::ExportNamedDeclaration(decl) => {
+        if let Some(declaration) = &decl.declaration {
+          let names: Vec<&BindingIdentifier> = match declaration {
+            Declaration::VariableDeclaration(var) =>
+              var.declarations
+                .iter()
+                .flat_map(|d| d.id.get_binding_identifiers())
+                .collect(),
+            declaration => declaration.id().into_iter().collect(),
+          };
+          for name in names {
+            local_exports.push((name.name.to_string(), name.symbol_id.get(), name.name.to_string()));
+          }
+          rewriter.edits.push(blank(&code, Span::new(decl.span.start, declaration.span().start)));
+          continue;
+        }
+        match &decl.source {
+          Some(source) => {
+            let m = dep(source)?;
+            for s in &decl.specifiers {
+              reexports.push((s.exported.name().to_string(), Access::Member(m, s.local.name().to_string())));
+            }
+          }
+          None => {
+            for s in &decl.specifiers {
+              let symbol = match &s.local {
+                ModuleExportName::IdentifierReference(ident) =>
+                  ident.reference_id
+                    .get()
+                    .and_then(|r| semantic.scoping().get_reference(r).symbol_id()),
+                _ => None,
+              };
+              local_exports.push((s.exported.name().to_string(), symbol, s.local.name().to_string()));
+            }
+          }
+        }
+        rewriter.edits.push(blank(&code, decl.span));
+      }
+      Statement::ExportDefaultDeclaration(decl) => {
+        let prefix = Span::new(decl.span.start, decl.declaration.span().start);
+        let named = match &decl.declaration {
+          ExportDefaultDeclarationKind::FunctionDeclaration(f) => f.id.as_ref(),
+          ExportDefaultDeclarationKind::ClassDeclaration(c) => c.id.as_ref(),
+          _ => None,
+        };
+        match named {
+          Some(id) => {
+            local_exports.push(("default".to_string(), id.symbol_id.get(), id.name.to_string()));
+            rewriter.edits.push(blank(&code, prefix));
+          }
+          None => {
+            local_exports.push(("default".to_string(), None, default_name.clone()));
+            rewriter.edits.push((prefix.start, prefix.end, format!("var {default_name} = ")));
+            if
+              matches!(
+                decl.declaration,
+                ExportDefaultDeclarationKind::FunctionDeclaration(_) | ExportDefaultDeclarationKind::ClassDeclaration(_)
+              )
+            {
+              let end = decl.declaration.span().end;
+              rewriter.edits.push((end, end, ";".to_string()));
+            }
+          }
+        }
+      }
+      Statement::ExportAllDeclaration(decl) => {
+        let m = dep(&decl.source)?;
+        match &decl.exported {
+          Some(exported) => reexports.push((exported.name().to_string(), Access::Namespace(m))),
+          None => stars.push(m),
+        }
+        rewriter.edits.push(blank(&code, decl.span));
+      }
+      _ => {}
+    }
+  }
+  rewriter.visit_program(&program);
+  if let Some(offset) = rewriter.top_level_await {
+    return Err(module_error(&path, &code, offset, t!("bundle.top_level_await").to_string()));
+  }
+  // 模塊在 bundle 中是普通函數, 沒有 `import()` 和 `import.meta`
+  if let Some(offset) = rewriter.dynamic_import {
+    return Err(module_error(&path, &code, offset, t!("bundle.dynamic_import").to_string()));
+  }
+  if let Some(offset) = rewriter.import_meta {
+    return Err(module_error(&path, &code, offset, t!("bundle.import_meta").to_string()));
+  }
+
+  // 導出的 getter 在模塊執行前定義, 讓循環導入也能取得綁定
+  let getter = |(name, expr): (&String, String)| {
+    format!("{}: function () {{ return {}; }}", serde_json::to_string(name).unwrap(), expr)
+  };
+  let mut header = Vec::new();
+  if !local_exports.is_empty() {
+    let getters: Vec<String> = local_exports
+      .iter()
+      .map(|(name, symbol, local)| {
+        let expr = symbol
+          .and_then(|s| rewriter.bindings.get(&s))
+          .map_or_else(|| local.clone(), Access::expr);
+        getter((name, expr))
+      })
+      .collect();
+    header.push(format!("{PREFIX}export({PREFIX}exports, {{ {} }});", getters.join(", ")));
+  }
+  for (i, d) in deps.iter().enumerate() {
+    header.push(format!("var {PREFIX}m{i} = {PREFIX}require({});", serde_json::to_string(d).unwrap()));
+  }
+  if !reexports.is_empty() {
+    let getters: Vec<String> = reexports
+      .iter()
+      .map(|(name, access)| getter((name, access.expr())))
+      .collect();
+    header.push(format!("{PREFIX}export({PREFIX}exports, {{ {} }});", getters.join(", ")));
+  }
+  for m in stars {
+    header.push(format!("{PREFIX}export_star({PREFIX}exports, {PREFIX}m{m});"));
+  }
+
+  // source map 在打包後重新生成, 去掉原來的註釋
+  let map = sourcemap::read_source_map(&path, &code);
+  if let Some((range, _)) = sourcemap::mapping_url(&code) {
+    rewriter.edits.push((range.start as u32, range.end as u32, String::new()));
+  }
+  rewriter.edits.sort_by_key(|(start, end, _)| (*start, *end));
+  let mut body = String::with_capacity(code.len());
+  let mut pos = 0;
+  for (start, end, text) in rewriter.edits {
+    // 在去掉的語句中的改寫
+    if (start as usize) < pos {
+      continue;
+    }
+    body.push_str(&code[pos..start as usize]);
+    body.push_str(&text);
+    pos = end as usize;
+  }
+  body.push_str(&code[pos..]);
+
+  let (map_file, map) = match map {
+    Some((file, map)) => {
+      let dir = file.as_deref().and_then(Path::parent).unwrap_or(path.parent().unwrap_or(root));
+      (file.clone(), Some((dir.to_path_buf(), map)))
+    }
+    None => (None, None),
+  };
+  Ok(Module { id: id.to_string(), header: header.join(" "), body, deps, map, map_file })
+}
+
+/// 從入口開始按依賴順序(被依賴的在前)收集模塊
+fn collect(
+  root: &Path,
+  id: &str,
+  modules: &mut BTreeMap<String, Module>,
+  visiting: &mut BTreeSet<String>,
+  order: &mut Vec<String>
+) -> Result<(), Error> {
+  // 循環導入時已經在處理中, 運行時由模塊緩存處理
+  if order.iter().any(|o| o == id) || !visiting.insert(id.to_string()) {
+    return Ok(());
+  }
+  if !modules.contains_key(id) {
+    let module = transform(root, id)?;
+    modules.insert(id.to_string(), module);
+  }
+  for dep in modules[id].deps.clone() {
+    collect(root, &dep, modules, visiting, order)?;
+  }
+  visiting.remove(id);
+  order.push(id.to_string());
+  Ok(())
+}
+
+/// 入口文件對應的模塊 id
+/// * `.ts` 文件使用同一位置編譯輸出的 `.js`
+fn entry_id(root: &Path, entry: &str) -> Result<String, Error> {
+  let entry = entry.trim_start_matches("./").replace('\\', "/");
+  let mut path = PathBuf::from(&entry);
+  if path.extension().is_some_and(|ext| ext == "ts" || ext == "tsx") {
+    path.set_extension("js");
+  }
+  if !root.join(&path).is_file() {
+    return Err(Error::Bundle { path: root.join(&entry), msg: t!("bundle.no_entry").to_string(), pos: None });
+  }
+  Ok(path.to_string_lossy().replace('\\', "/"))
+}
+
+/// 生成一個 bundle 和它的 source map
+/// * `root` - mod文件夾
+/// * `ids` - 包含的模塊, 按依賴順序
+/// * `out` - bundle 文件
+fn render(
+  root: &Path,
+  name: &str,
+  entry: &str,
+  ids: &[String],
+  modules: &BTreeMap<String, Module>,
+  out: &Path,
+  source_map: bool
+) -> (String, Option<String>) {
+  let mut code = RUNTIME.replace("{name}", &serde_json::to_string(name).unwrap());
+  let mut lines: Vec<Vec<Segment>> = vec![Vec::new(); code.matches('\n').count()];
+  let mut map = SourceMap { version: 3, ..Default::default() };
+  let out_dir = sourcemap::normalize(out.parent().unwrap_or(Path::new("")));
+  let add_source = |map: &mut SourceMap, path: &Path, content: Option<String>| -> i64 {
+    let source = sourcemap::relative(&out_dir, &sourcemap::normalize(path));
+    match map.sources.iter().position(|s| *s == source) {
+      Some(i) => i as i64,
+      None => {
+        map.sources.push(source);
+        map.sources_content.push(content);
+        (map.sources.len() - 1) as i64
+      }
+    }
+  };
+
+  for id in ids {
+    let module = &modules[id];
+    code.push_str(
+      &format!(
+        "{PREFIX}modules[{}] = function ({PREFIX}exports, {PREFIX}require) {{ {}\n",
+        serde_json::to_string(id).unwrap(),
+        module.header
+      )
+    );
+    lines.push(Vec::new());
+    code.push_str(&module.body);
+    code.push_str("\n};\n");
+
+    let body_lines = module.body.split('\n').count();
+    if !source_map {
+      continue;
+    }
+    match &module.map {
+      // 保留模塊 source map 中同一行的所有映射
+      Some((dir, module_map)) => {
+        let paths = module_map.source_paths(dir);
+        let indices: Vec<i64> = paths
+          .iter()
+          .enumerate()
+          .map(|(i, path)| add_source(&mut map, path, module_map.sources_content.get(i).cloned().flatten()))
+          .collect();
+        let mut decoded = sourcemap::decode_mappings(&module_map.mappings).into_iter();
+        for _ in 0..body_lines {
+          let segments = decoded.next().unwrap_or_default();
+          lines.push(
+            segments
+              .into_iter()
+              .filter_map(|s| Some(Segment { source: *indices.get(s.source as usize)?, ..s }))
+              .collect()
+          );
+        }
+      }
+      // 沒有 source map 時映射到模塊本身的每一行
+      None => {
+        let path = root.join(id);
+        let source = add_source(&mut map, &path, fs::read_to_string(&path).ok());
+        for line in 0..body_lines {
+          lines.push(vec![Segment { column: 0, source, line: line as i64, original_column: 0 }]);
+        }
+      }
+    }
+    lines.push(Vec::new());
+  }
+  code.push_str(&format!("{PREFIX}require({});\n}})();\n", serde_json::to_string(entry).unwrap()));
+  if !source_map {
+    return (code, None);
+  }
+
+  let file = out.file_name().unwrap_or_default().to_string_lossy().to_string();
+  code.push_str(&format!("//# sourceMappingURL={}.map\n", file));
+  map.file = Some(file);
+  map.mappings = sourcemap::encode_mappings(&lines);
+  (code, Some(serde_json::to_string(&map).unwrap()))
+}
+
+/// 按入口之間的依賴排序: 入口被另一個入口導入時排在前面
+/// * `reach` - 每個入口能到達的模塊
+fn sort_entries(entries: &[String], reach: &[Vec<String>]) -> Vec<usize> {
+  let mut order = Vec::new();
+  let mut left: Vec<usize> = (0..entries.len()).collect();
+  while !left.is_empty() {
+    // 沒有依賴其他剩下的入口的第一個, 有循環時按配置順序
+    let next = left
+      .iter()
+      .position(|&i| !left.iter().any(|&j| j != i && reach[i].contains(&entries[j])))
+      .unwrap_or(0);
+    order.push(left.remove(next));
+  }
+  order
+}
+
+/// 打包一個mod
+/// * `root` - 臨時目錄中的mod文件夾
+/// * `name` - mod名稱, 用於區分不同mod的模塊表
+/// * `source_map` - 是否生成 source map
+pub(crate) fn bundle_mod(
+  root: &Path,
+  name: &str,
+  cofg: &BundleCofg,
+  source_map: bool
+) -> Result<Bundled, Error> {
+  let entries = cofg.entries
+    .iter()
+    .map(|entry| entry_id(root, entry))
+    .collect::<Result<Vec<_>, _>>()?;
+
+  let mut modules = BTreeMap::new();
+  let mut reach = Vec::new();
+  for entry in &entries {
+    let mut order = Vec::new();
+    collect(root, entry, &mut modules, &mut BTreeSet::new(), &mut order)?;
+    reach.push(order);
+  }
+
+  let out_dir = cofg.out_dir.trim_start_matches("./").trim_end_matches('/');
+  let mut bundles: Vec<String> = Vec::new();
+  let mut outputs = Vec::new();
+  let mut included: BTreeSet<String> = BTreeSet::new();
+  for i in sort_entries(&entries, &reach) {
+    let stem = Path::new(&entries[i]).file_stem().unwrap_or_default().to_string_lossy().to_string();
+    let bundle = if out_dir.is_empty() || out_dir == "." {
+      format!("{stem}.js")
+    } else {
+      format!("{out_dir}/{stem}.js")
+    };
+    if bundles.contains(&bundle) {
+      return Err(Error::Config(t!("bundle.duplicate", path = bundle).to_string()));
+    }
+    // 之前的 bundle 已經定義的模塊不再重複
+    let ids: Vec<String> = reach[i]
+      .iter()
+      .filter(|id| included.insert(id.to_string()))
+      .cloned()
+      .collect();
+    let out = root.join(&bundle);
+    let (code, map) = render(root, name, &entries[i], &ids, &modules, &out, source_map);
+    info!("    {}", t!("bundle.written", path = bundle, count = ids.len()));
+    outputs.push((out, code, map));
+    bundles.push(bundle);
+  }
+
+  // 刪除已打包的模塊, 再寫入 bundle (bundle 可能與模塊同名)
+  for module in modules.values() {
+    let path = root.join(&module.id);
+    debug!("    {}", t!("bundle.removed", path = module.id));
+    fs::remove_file(&path).with_path(&path)?;
+    if let Some(map_file) = &module.map_file && map_file.starts_with(root) && map_file.is_file() {
+      fs::remove_file(map_file).with_path(map_file)?;
+    }
+  }
+  for (out, code, map) in outputs {
+    if let Some(parent) = out.parent() {
+      fs::create_dir_all(parent).with_path(parent)?;
+    }
+    fs::write(&out, code).with_path(&out)?;
+    if let Some(map) = map {
+      let map_path = PathBuf::from(format!("{}.map", out.display()));
+      fs::write(&map_path, map).with_path(&map_path)?;
+    }
+  }
+  Ok(Bundled { bundles, modules: modules.into_keys().collect() })
+}
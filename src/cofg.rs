@@ -1,13 +1,21 @@
 //! 配置相關結構體和實現
 
-use clap::{ Parser, ArgAction };
-use log::warn;
+use std::collections::BTreeMap;
+use std::path::{ Path, PathBuf };
+use clap::{ Parser, Subcommand, ArgAction };
+use log::{ info, warn };
 use nest_struct::nest_struct;
 use config::Config;
 use serde::{ Deserialize, Serialize };
 use rust_i18n::t;
 use super::fs;
 use super::r#const::VERSION;
+use super::template;
+use super::defines;
+use super::diagnostics;
+use super::hooks;
+use super::stage;
+use super::error::{ Error, WithPath };
 
 #[nest_struct]
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -22,8 +30,14 @@ pub(crate) struct Cofg {
     pub tmp_path: String,
     /// 輸出結果存放路徑
     pub results_path: String,
-    /// mod源文件路徑
-    pub mods_path: String,
+    /// mod源文件路徑, 可以是一個或多個目錄, 支持 glob
+    pub mods_path: ModsPath,
+    /// 本地 mod 模板路徑(`mod-dev new`)
+    #[serde(default = "default_templates_path")]
+    pub templates_path: String,
+    /// ts 編譯使用的構建目錄, 保存增量編譯的緩存, 不會被清空
+    #[serde(default = "default_build_path")]
+    pub build_path: String,
   },
   /// 最後暫停?
   pub pause: bool,
@@ -31,22 +45,318 @@ pub(crate) struct Cofg {
   pub ts_process: bool,
   /// file name
   pub file_name: String,
+  /// 打包 source map(*.js.map)?
+  #[serde(default = "default_true")]
+  pub source_map: bool,
+  /// 壓縮 js/css?
+  #[serde(default)]
+  pub minify: bool,
+  /// zip 壓縮等級(Deflated: 0-9, null 為默認)
+  #[serde(default)]
+  pub compression_level: Option<i64>,
+  /// 額外傳給 tsc 的參數, 加在 `compiler.args` 後面
+  #[serde(default)]
+  pub tsc_args: Vec<String>,
+  /// 編譯 ts 文件的命令
+  #[serde(default)]
+  pub compiler: CompilerCofg! {
+    /// 命令, 如 `tsc`, `./node_modules/.bin/tsc`, `esbuild`
+    pub command: String,
+    /// 參數, 支持 `{build_dir}`, `{mod_dir}`, `{src_dir}`, `{name}` 佔位符
+    #[serde(default = "default_compiler_args")]
+    pub args: Vec<String>,
+    /// 工作目錄, 支持佔位符, 為空時為當前目錄
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<String>,
+    /// 是否把 `tsc_args` 加在參數後面, 為空時按命令判斷(見 [`CompilerCofg::accepts_tsc_args`])
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub accepts_tsc_args: Option<bool>,
+  },
+  /// scriptFileList 和 styleFileList 的排序規則
+  #[serde(default = "default_order")]
+  pub order: OrderCofg! {
+    /// 排在最前面的文件(glob), 按列出的順序
+    #[serde(default)]
+    pub first: Vec<String>,
+    /// 排在最後面的文件(glob), 按列出的順序
+    #[serde(default)]
+    pub last: Vec<String>,
+    /// 按優先級排序, 優先級高的在前, 沒有匹配的文件為 0
+    #[serde(default)]
+    pub priority: Vec<PriorityRule! {
+      /// 匹配的文件(glob)
+      pub glob: String,
+      /// 優先級
+      pub priority: i64,
+    }>,
+    /// 新加入列表的文件按自然排序(`2.js` 在 `10.js` 前面), 否則按 glob 順序
+    #[serde(default)]
+    pub natural: bool,
+  },
+  /// 構建時替換的常量: 名稱 -> 值(字符串, 數字, 布爾值或 null)
+  #[serde(default)]
+  pub defines: BTreeMap<String, serde_json::Value>,
+  /// 用腳本編寫的自定義步驟, 按順序插入到 `after` 指定的步驟之後
+  #[serde(default)]
+  pub stages: Vec<StageCofg! {
+    /// 步驟名稱, 用於日誌, hooks 和構建報告中的用時
+    pub name: String,
+    /// Rhai 腳本的路徑
+    pub script: String,
+    /// 在這個步驟之後運行
+    pub after: String,
+    /// 只處理這些mod文件夾, 為空時處理所有mod
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub mods: Vec<String>,
+  }>,
+  /// 每個步驟前後運行的命令: 步驟名稱 -> 命令
+  #[serde(default)]
+  pub hooks: BTreeMap<String, HookCofg! {
+    /// 步驟之前運行的命令
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub pre: Vec<String>,
+    /// 步驟之後運行的命令
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub post: Vec<String>,
+  }>,
+  /// 按mod文件夾名稱覆蓋的配置
+  #[serde(default)]
+  pub mods: BTreeMap<String, ModCofg! {
+    /// 覆蓋 compiler
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compiler: Option<CompilerCofg>,
+    /// 從入口開始打包 ES 模塊, 每個入口輸出一個腳本
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bundle: Option<BundleCofg! {
+      /// 入口文件(相對mod文件夾), `.ts` 使用同一位置編譯輸出的 `.js`
+      pub entries: Vec<String>,
+      /// bundle 的輸出目錄(相對mod文件夾)
+      #[serde(default = "default_bundle_out_dir")]
+      pub out_dir: String,
+    }>,
+    /// 覆蓋 order
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub order: Option<OrderCofg>,
+    /// 加到 defines 中, 同名的覆蓋
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub defines: BTreeMap<String, serde_json::Value>,
+    /// 在 hooks 之後運行的命令
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub hooks: BTreeMap<String, HookCofg>,
+  }>,
+  /// 構建配置, 用 `--profile` 選擇
+  #[serde(default = "default_profiles")]
+  pub profiles: BTreeMap<String, ProfileCofg! {
+    /// 覆蓋 file_name
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file_name: Option<String>,
+    /// 覆蓋 source_map
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source_map: Option<bool>,
+    /// 覆蓋 minify
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub minify: Option<bool>,
+    /// 覆蓋 compression_level
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub compression_level: Option<i64>,
+    /// 覆蓋 tsc_args
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tsc_args: Option<Vec<String>>,
+    /// 加到 defines 中, 同名的覆蓋
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub defines: BTreeMap<String, serde_json::Value>,
+  }>,
+  /// 允許 mod 名稱或輸出路徑衝突?
+  #[serde(default)]
+  pub allow_collisions: bool,
+  /// 有警告時視為構建失敗?
+  #[serde(default)]
+  pub deny_warnings: bool,
+  /// 當前使用的 profile(僅命令行)
+  #[serde(skip)]
+  pub profile: Option<String>,
+  /// 構建報告的輸出路徑(僅命令行)
+  #[serde(skip)]
+  pub report: Option<PathBuf>,
+  /// 診斷信息的輸出格式(僅命令行)
+  #[serde(skip)]
+  pub diagnostic_format: diagnostics::Format,
+  /// 診斷信息的輸出文件, 為空時輸出到 stdout(僅命令行)
+  #[serde(skip)]
+  pub diagnostic_output: Option<PathBuf>,
+  /// 日誌顏色: auto, always, never(僅命令行)
+  #[serde(skip)]
+  pub color: String,
+  /// 只輸出錯誤日誌(僅命令行)
+  #[serde(skip)]
+  pub quiet: bool,
+  /// 構建前清空構建目錄(僅命令行)
+  #[serde(skip)]
+  pub clean: bool,
+  /// 子命令(僅命令行)
+  #[serde(skip)]
+  pub command: Option<Command>,
+}
+
+/// mod源文件路徑
+/// * 可以寫成單個字符串或字符串列表
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(untagged)]
+pub(crate) enum ModsPath {
+  /// 單個目錄
+  One(String),
+  /// 多個目錄
+  Many(Vec<String>),
+}
+
+impl ModsPath {
+  /// 配置中寫的所有路徑(未展開 glob)
+  pub(crate) fn patterns(&self) -> Vec<&str> {
+    match self {
+      ModsPath::One(s) => vec![s.as_str()],
+      ModsPath::Many(v) => v.iter().map(|s| s.as_str()).collect(),
+    }
+  }
+
+  /// 展開 glob 後的所有 mod 根目錄
+  /// * 重複的目錄只保留一次
+  pub(crate) fn roots(&self) -> Vec<PathBuf> {
+    let mut roots: Vec<PathBuf> = Vec::new();
+    for pattern in self.patterns() {
+      let matched: Vec<PathBuf> = if is_glob(pattern) {
+        match glob::glob(pattern) {
+          Ok(paths) => paths.flatten().filter(|p| p.is_dir()).collect(),
+          Err(e) => {
+            warn!("{}", t!("config.invalid_mods_path", path = pattern, e = e));
+            continue;
+          }
+        }
+      } else {
+        vec![PathBuf::from(pattern)]
+      };
+      if matched.is_empty() {
+        warn!("{}", t!("config.empty_mods_path", path = pattern));
+      }
+      for path in matched {
+        let key = fs::canonicalize(&path).unwrap_or(path.clone());
+        if !roots.iter().any(|r| fs::canonicalize(r).unwrap_or(r.clone()) == key) {
+          roots.push(path);
+        }
+      }
+    }
+    roots
+  }
+}
+
+/// 路徑中是否有 glob 語法
+fn is_glob(pattern: &str) -> bool {
+  pattern.contains(['*', '?', '['])
+}
+
+fn default_templates_path() -> String {
+  "./templates".to_string()
+}
+
+fn default_build_path() -> String {
+  "./build".to_string()
+}
+
+fn default_true() -> bool {
+  true
+}
+
+fn default_compiler_args() -> Vec<String> {
+  vec!["--project".to_string(), "{build_dir}".to_string()]
+}
+
+fn default_order() -> OrderCofg {
+  OrderCofg { first: Vec::new(), last: Vec::new(), priority: Vec::new(), natural: false }
+}
+
+fn default_bundle_out_dir() -> String {
+  "dist".to_string()
+}
+
+impl Default for CompilerCofg {
+  fn default() -> Self {
+    CompilerCofg { command: "tsc".to_string(), args: default_compiler_args(), cwd: None, accepts_tsc_args: None }
+  }
+}
+
+/// 運行本地命令的包管理器, 如 `npx tsc`
+const PACKAGE_RUNNERS: [&str; 6] = ["npx", "pnpx", "bunx", "pnpm", "yarn", "bun"];
+
+impl CompilerCofg {
+  /// 是否把 `tsc_args` 加在參數後面
+  /// * 未指定 `accepts_tsc_args` 時, 命令為 `builtin` 或文件名為 `tsc` (如 `./node_modules/.bin/tsc`, `tsc.cmd`),
+  ///   或通過包管理器運行 `tsc` (如 `npx tsc`, `pnpm exec tsc`) 時才加
+  pub(crate) fn accepts_tsc_args(&self) -> bool {
+    if let Some(accepts) = self.accepts_tsc_args {
+      return accepts;
+    }
+    let stem = |command: &str| {
+      Path::new(command).file_stem().map(|stem| stem.to_string_lossy().to_ascii_lowercase()).unwrap_or_default()
+    };
+    let command = stem(&self.command);
+    if self.command == "builtin" || command == "tsc" {
+      return true;
+    }
+    // 包管理器運行的是第一個不是選項或子命令的參數
+    PACKAGE_RUNNERS.contains(&command.as_str()) &&
+      self.args
+        .iter()
+        .find(|arg| !arg.starts_with('-') && !["exec", "dlx", "x"].contains(&arg.as_str()))
+        .is_some_and(|arg| stem(arg) == "tsc")
+  }
+}
+
+/// 默認的 dev / release 構建配置
+fn default_profiles() -> BTreeMap<String, ProfileCofg> {
+  BTreeMap::from([
+    (
+      "dev".to_string(),
+      ProfileCofg {
+        file_name: None,
+        source_map: Some(true),
+        minify: Some(false),
+        compression_level: Some(1),
+        tsc_args: Some(vec!["--sourceMap".to_string()]),
+        defines: BTreeMap::new(),
+      },
+    ),
+    (
+      "release".to_string(),
+      ProfileCofg {
+        file_name: None,
+        source_map: Some(false),
+        minify: Some(true),
+        compression_level: Some(9),
+        tsc_args: Some(vec!["--removeComments".to_string()]),
+        defines: BTreeMap::new(),
+      },
+    ),
+  ])
 }
 
 impl Cofg {
   /// 配置初始化函數
   /// * 讀取並解析cofg.json文件
-  pub(crate) fn new() -> Cofg {
-    let settings = Config::builder()
-      .add_source(config::File::with_name("./cofg.json"))
-      .build()
-      .unwrap();
-    let mut cofg: Cofg = settings.try_deserialize().unwrap_or_default();
+  /// * cofg.json 不存在時使用默認配置
+  pub(crate) fn new() -> Result<Cofg, Error> {
+    let mut cofg: Cofg = if std::path::Path::new("./cofg.json").exists() {
+      Config::builder()
+        .add_source(config::File::with_name("./cofg.json"))
+        .build()
+        .and_then(|settings| settings.try_deserialize())
+        .map_err(|e| Error::Config(t!("config.load_failed", e = e).to_string()))?
+    } else {
+      Cofg::default()
+    };
 
     cofg.locale = cofg.normalize_locale();
     cofg.loglv = cofg.validate_log_level().unwrap_or(cofg.loglv);
     cofg.write_file();
-    cofg
+    Ok(cofg)
   }
 
   /// 正規化語言環境
@@ -74,15 +384,121 @@ impl Cofg {
   }
 
   /// form cli load args
-  fn load_cli(mut self, cli: Cli) {
+  fn load_cli(&mut self, cli: Cli) {
     if let Some(v) = cli.locale {
       self.locale = v;
+      self.locale = self.normalize_locale();
     }
     if let Some(v) = cli.loglv {
       self.loglv = v;
+      self.loglv = self.validate_log_level().unwrap_or(self.loglv.clone());
+    }
+    if cli.pause {
+      self.pause = true;
+    }
+    if cli.ts_process {
+      self.ts_process = true;
     }
-    self.pause = cli.pause;
-    self.ts_process = cli.ts_process;
+    if cli.allow_collisions {
+      self.allow_collisions = true;
+    }
+    if cli.deny_warnings {
+      self.deny_warnings = true;
+    }
+    self.profile = cli.profile;
+    self.report = cli.report;
+    self.diagnostic_format = cli.diagnostic_format;
+    self.diagnostic_output = cli.diagnostic_output;
+    self.color = cli.color;
+    self.quiet = cli.quiet;
+    self.clean = cli.clean;
+    self.command = cli.command;
+  }
+
+  /// 取得mod使用的編譯命令
+  /// * `name` - mod文件夾名稱
+  pub(crate) fn compiler_for(&self, name: &str) -> &CompilerCofg {
+    self.mods
+      .get(name)
+      .and_then(|m| m.compiler.as_ref())
+      .unwrap_or(&self.compiler)
+  }
+
+  /// 取得mod使用的排序規則
+  /// * `name` - mod文件夾名稱
+  pub(crate) fn order_for(&self, name: &str) -> &OrderCofg {
+    self.mods
+      .get(name)
+      .and_then(|m| m.order.as_ref())
+      .unwrap_or(&self.order)
+  }
+
+  /// 取得mod使用的常量, mod的配置覆蓋同名的常量
+  /// * `name` - mod文件夾名稱
+  pub(crate) fn defines_for(&self, name: &str) -> BTreeMap<String, serde_json::Value> {
+    let mut defines = self.defines.clone();
+    if let Some(m) = self.mods.get(name) {
+      defines.extend(m.defines.clone());
+    }
+    defines
+  }
+
+  /// 取得mod在步驟前(`pre`)或後(`post`)運行的命令, 先是 hooks 中的, 然後是mod的
+  /// * `name` - mod文件夾名稱
+  pub(crate) fn hooks_for(&self, name: &str, stage: &str, post: bool) -> Vec<&str> {
+    [self.hooks.get(stage), self.mods.get(name).and_then(|m| m.hooks.get(stage))]
+      .into_iter()
+      .flatten()
+      .flat_map(|hook| if post { &hook.post } else { &hook.pre })
+      .map(String::as_str)
+      .collect()
+  }
+
+  /// 取得mod的打包配置, 沒有入口時為 `None`
+  /// * `name` - mod文件夾名稱
+  pub(crate) fn bundle_for(&self, name: &str) -> Option<&BundleCofg> {
+    self.mods
+      .get(name)
+      .and_then(|m| m.bundle.as_ref())
+      .filter(|b| !b.entries.is_empty())
+  }
+
+  /// 應用 `--profile` 選擇的構建配置
+  /// * 只修改內存中的配置, 不會寫回 cofg.json
+  pub(crate) fn apply_profile(&mut self) -> Result<(), Error> {
+    let Some(name) = &self.profile else {
+      return Ok(());
+    };
+    let Some(profile) = self.profiles.get(name).cloned() else {
+      return Err(
+        Error::Config(
+          t!(
+            "config.unknown_profile",
+            name = name,
+            list = self.profiles.keys().cloned().collect::<Vec<_>>().join(", ")
+          ).to_string()
+        )
+      );
+    };
+
+    if let Some(v) = profile.file_name {
+      self.file_name = v;
+    }
+    if let Some(v) = profile.source_map {
+      self.source_map = v;
+    }
+    if let Some(v) = profile.minify {
+      self.minify = v;
+    }
+    if let Some(v) = profile.compression_level {
+      self.compression_level = Some(v);
+    }
+    if let Some(v) = profile.tsc_args {
+      self.tsc_args = v;
+    }
+    self.defines.extend(profile.defines);
+    info!("{}", t!("config.profile", name = name));
+    Ok(())
   }
 
   /// Returns the write file of this [`Cofg`].
@@ -102,19 +518,9 @@ impl Cofg {
   /// 初始化路徑和日誌系統
   /// * 設置程序語言環境
   /// * 初始化日誌系統
-  pub(crate) fn init(&self) {
-    self.clone().load_cli(Cli::parse());
-
-    for path in [&self.path.tmp_path, &self.path.results_path].iter() {
-      let path_obj = std::path::Path::new(path);
-      if path_obj.exists() {
-        fs::remove_dir_all(path_obj).unwrap();
-      }
-      fs::create_dir(path_obj).unwrap();
-    }
-    if !std::path::Path::new(&self.path.mods_path).exists() {
-      fs::create_dir(&self.path.mods_path).unwrap();
-    }
+  /// * 應用構建配置
+  pub(crate) fn init(&mut self) -> Result<(), Error> {
+    self.load_cli(Cli::parse());
 
     rust_i18n::set_locale(&self.locale);
     let mut colog_cofg = colog::default_builder();
@@ -133,7 +539,51 @@ impl Cofg {
       }
       o => warn!("{}", t!("config.invalid_log_level", msg = o)),
     }
+    if self.quiet {
+      colog_cofg.filter_level(log::LevelFilter::Error);
+    }
+    match self.color.as_str() {
+      "always" => colored::control::set_override(true),
+      "never" => colored::control::set_override(false),
+      _ => (),
+    }
+    colog_cofg.parse_write_style(&self.color);
     colog_cofg.init();
+
+    self.apply_profile()?;
+    for name in self.defines.keys().chain(self.mods.values().flat_map(|m| m.defines.keys())) {
+      defines::validate_name(name)?;
+    }
+    stage::validate(&self.stages)?;
+    let custom: Vec<&str> = self.stages.iter().map(|s| s.name.as_str()).collect();
+    for stage in self.hooks.keys().chain(self.mods.values().flat_map(|m| m.hooks.keys())) {
+      hooks::validate_stage(stage, &custom)?;
+    }
+    template::validate(&self.file_name)
+  }
+
+  /// 準備打包用的目錄
+  /// * 清空臨時目錄和輸出目錄
+  /// * `--clean` 時清空構建目錄
+  /// * 創建不存在的 mods 目錄
+  pub(crate) fn prepare_dirs(&self) -> Result<(), Error> {
+    let build_path = std::path::Path::new(&self.path.build_path);
+    if self.clean && build_path.exists() {
+      fs::remove_dir_all(build_path).with_path(build_path)?;
+    }
+    for path in [&self.path.tmp_path, &self.path.results_path].iter() {
+      let path_obj = std::path::Path::new(path);
+      if path_obj.exists() {
+        fs::remove_dir_all(path_obj).with_path(path_obj)?;
+      }
+      fs::create_dir(path_obj).with_path(path_obj)?;
+    }
+    for path in self.path.mods_path.patterns() {
+      if !is_glob(path) && !std::path::Path::new(path).exists() {
+        fs::create_dir_all(path).with_path(path)?;
+      }
+    }
+    Ok(())
   }
 }
 
@@ -145,11 +595,34 @@ impl Default for Cofg {
       path: PathCofg {
         tmp_path: "./tmp".to_string(),
         results_path: "./results".to_string(),
-        mods_path: "./mods".to_string(),
+        mods_path: ModsPath::One("./mods".to_string()),
+        templates_path: default_templates_path(),
+        build_path: default_build_path(),
       },
       pause: true,
       ts_process: true,
       file_name: "{name}.mod.zip".to_string(),
+      source_map: true,
+      minify: false,
+      compression_level: None,
+      tsc_args: Vec::new(),
+      compiler: CompilerCofg::default(),
+      order: default_order(),
+      defines: BTreeMap::new(),
+      stages: Vec::new(),
+      hooks: BTreeMap::new(),
+      mods: BTreeMap::new(),
+      profiles: default_profiles(),
+      allow_collisions: false,
+      deny_warnings: false,
+      profile: None,
+      report: None,
+      diagnostic_format: diagnostics::Format::Text,
+      diagnostic_output: None,
+      color: "auto".to_string(),
+      quiet: false,
+      clean: false,
+      command: None,
     }
   }
 }
@@ -191,10 +664,10 @@ impl std::fmt::Display for Cofg {
 /// 命令行參數結構體
 struct Cli {
   /// 語言環境
-  #[clap(long, short = 'i')]
+  #[clap(long, short = 'i', global = true)]
   locale: Option<String>,
   /// 日誌級別
-  #[clap(long, short)]
+  #[clap(long, short, global = true)]
   loglv: Option<String>,
   /// 是否處理ts文件
   #[clap(long = "tsp", action = ArgAction::SetTrue)]
@@ -202,6 +675,102 @@ struct Cli {
   /// 是否暫停
   #[clap(short, long, action = ArgAction::SetTrue)]
   pause: bool,
+  /// 使用的構建配置(如 dev, release)
+  #[clap(long)]
+  profile: Option<String>,
+  /// 允許 mod 名稱或輸出路徑衝突(後打包的會覆蓋先打包的)
+  #[clap(long, action = ArgAction::SetTrue)]
+  allow_collisions: bool,
+  /// 有警告時視為構建失敗
+  #[clap(long, action = ArgAction::SetTrue)]
+  deny_warnings: bool,
+  /// 把構建報告以 JSON 格式寫入指定文件
+  #[clap(long, value_name = "PATH")]
+  report: Option<PathBuf>,
+  /// 診斷信息的輸出格式
+  #[clap(long, value_enum, default_value_t = diagnostics::Format::Text)]
+  diagnostic_format: diagnostics::Format,
+  /// 診斷信息的輸出文件, 默認輸出到 stdout
+  #[clap(long, value_name = "PATH")]
+  diagnostic_output: Option<PathBuf>,
+  /// 日誌顏色
+  #[clap(long, global = true, default_value = "auto", value_parser = ["auto", "always", "never"])]
+  color: String,
+  /// 只輸出錯誤日誌
+  #[clap(long, short, global = true, action = ArgAction::SetTrue)]
+  quiet: bool,
+  /// 清空構建目錄, 重新編譯所有 ts 文件
+  #[clap(long, action = ArgAction::SetTrue)]
+  clean: bool,
+  /// 子命令, 不指定時打包所有mod
+  #[clap(subcommand)]
+  command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug, Serialize, Clone)]
+/// 子命令
+pub(crate) enum Command {
+  /// 用模板創建新的mod
+  New {
+    /// mod名稱
+    #[clap(required_unless_present = "list")]
+    name: Option<String>,
+    /// 模板名稱(twee/img/ts/tweereplacer 或 templates_path 下的文件夾)
+    #[clap(long, short, default_value = "twee")]
+    template: String,
+    /// mod版本
+    #[clap(long = "mod-version", default_value = "1.0.0")]
+    version: String,
+    /// 創建在哪個目錄下, 默認為 mods_path 的第一個目錄
+    #[clap(long)]
+    root: Option<PathBuf>,
+    /// 列出所有模板
+    #[clap(long, action = ArgAction::SetTrue)]
+    list: bool,
+  },
+  /// 為沒有 boot.json 的文件夾生成 boot.json
+  Init {
+    /// mod文件夾
+    #[clap(long = "from-dir")]
+    from_dir: PathBuf,
+    /// mod名稱, 不指定時自動選擇(有 TTY 時會詢問)
+    #[clap(long)]
+    name: Option<String>,
+    /// mod版本, 不指定時自動選擇(有 TTY 時會詢問)
+    #[clap(long = "mod-version")]
+    version: Option<String>,
+    /// 覆蓋已有的 boot.json
+    #[clap(long, action = ArgAction::SetTrue)]
+    force: bool,
+  },
+  /// 把打包好的mod zip解壓回可編輯的源文件夾
+  Import {
+    /// mod zip 文件
+    zip: PathBuf,
+    /// 解壓到哪個目錄下, 默認為 mods_path 的第一個目錄
+    #[clap(long)]
+    root: Option<PathBuf>,
+    /// 從 *.js.map 的 sourcesContent 還原源文件(如 TypeScript)
+    #[clap(long, action = ArgAction::SetTrue)]
+    sources: bool,
+  },
+  /// 查看mod zip 的 boot.json 和文件
+  Inspect {
+    /// mod zip 文件
+    zip: PathBuf,
+  },
+  /// 比較同一個mod的兩個 zip
+  Diff {
+    /// 舊的 zip
+    old: PathBuf,
+    /// 新的 zip
+    new: PathBuf,
+  },
+  /// 為 ts mod 生成 ModLoader API 的類型聲明 (types/modloader.d.ts)
+  Types {
+    /// mod文件夾, 不指定時處理 mods_path 中所有有 tsconfig.json 的mod
+    dirs: Vec<PathBuf>,
+  },
 }
 /*
 impl std::fmt::Display for Cli {
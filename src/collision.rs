@@ -0,0 +1,103 @@
+//! 檢查 mod 之間的名稱和輸出路徑衝突
+
+use std::collections::HashMap;
+use std::path::{ Path, PathBuf };
+use rust_i18n::t;
+use crate::diagnostics::Warning;
+
+/// 兩個 mod 之間的衝突
+#[derive(Debug, PartialEq)]
+pub(crate) enum Collision {
+  /// boot.json 的 `name` 相同
+  Name {
+    name: String,
+    first: PathBuf,
+    second: PathBuf,
+  },
+  /// `file_name` 渲染出相同的 zip 路徑
+  Output {
+    path: PathBuf,
+    first: PathBuf,
+    second: PathBuf,
+  },
+}
+
+impl std::fmt::Display for Collision {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Collision::Name { name, first, second } =>
+        write!(
+          f,
+          "{}",
+          t!(
+            "collision.name",
+            name = name,
+            first = first.display(),
+            second = second.display()
+          )
+        ),
+      Collision::Output { path, first, second } =>
+        write!(
+          f,
+          "{}",
+          t!(
+            "collision.output",
+            path = path.display(),
+            first = first.display(),
+            second = second.display()
+          )
+        ),
+    }
+  }
+}
+
+impl Collision {
+  /// 後出現的mod的源文件夾
+  pub(crate) fn second(&self) -> &Path {
+    match self {
+      Collision::Name { second, .. } | Collision::Output { second, .. } => second,
+    }
+  }
+
+  /// 允許衝突(`allow_collisions`)時作為警告記錄
+  pub(crate) fn to_warning(&self) -> Warning {
+    let key = match self {
+      Collision::Name { .. } => "collision.name",
+      Collision::Output { .. } => "collision.output",
+    };
+    Warning { key, message: self.to_string(), file: None }
+  }
+}
+
+/// 找出所有衝突
+/// * `mods` - (mod名稱, 輸出zip路徑, 源文件夾)
+///
+/// 輸出路徑不區分大小寫比較, 因為 Windows 和 macOS 上它們是同一個文件
+pub(crate) fn find_collisions<'a>(
+  mods: impl IntoIterator<Item = (&'a str, &'a Path, &'a Path)>
+) -> Vec<Collision> {
+  let mut names: HashMap<&str, &Path> = HashMap::new();
+  let mut outputs: HashMap<String, &Path> = HashMap::new();
+  let mut collisions = Vec::new();
+
+  for (name, zip_path, src) in mods {
+    if let Some(first) = names.insert(name, src) {
+      collisions.push(Collision::Name {
+        name: name.to_string(),
+        first: first.to_path_buf(),
+        second: src.to_path_buf(),
+      });
+      names.insert(name, first);
+    }
+    let key = zip_path.to_string_lossy().replace('\\', "/").to_lowercase();
+    if let Some(first) = outputs.insert(key.clone(), src) {
+      collisions.push(Collision::Output {
+        path: zip_path.to_path_buf(),
+        first: first.to_path_buf(),
+        second: src.to_path_buf(),
+      });
+      outputs.insert(key, first);
+    }
+  }
+  collisions
+}
@@ -0,0 +1,314 @@
+//! 構建時替換的常量 (`defines`)
+//!
+//! 內置的 `__MOD_NAME__`, `__MOD_VERSION__`, `__BUILD_HASH__` 和 cofg.json 中 `defines` 定義的常量,
+//! 在 ts 編譯之後替換到 boot.json 列出的 JS, twee, CSS 文件和 boot.json 的元數據中。
+//! JS 用 oxc 解析, 只替換沒有本地綁定的讀取, 替換為 JSON 字面量(字符串帶引號);
+//! 字符串, 模板字符串和註釋中的以及其他文件中的替換為原文。
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+#[cfg(feature = "defines")]
+use oxc_allocator::Allocator;
+#[cfg(feature = "defines")]
+use oxc_ast::ast::*;
+#[cfg(feature = "defines")]
+use oxc_ast_visit::{ Visit, walk };
+#[cfg(feature = "defines")]
+use oxc_parser::Parser;
+#[cfg(feature = "defines")]
+use oxc_semantic::{ Scoping, SemanticBuilder };
+#[cfg(feature = "defines")]
+use oxc_span::{ SourceType, Span };
+use rust_i18n::t;
+use serde_json::Value;
+use crate::boot_json::BootJson;
+use crate::error::{ Error, WithPath };
+use crate::fs_utils;
+#[cfg(feature = "defines")]
+use crate::sourcemap;
+
+/// mod名稱(boot.json 的 `name`)
+pub(crate) const MOD_NAME: &str = "__MOD_NAME__";
+/// mod版本(boot.json 的 `version`)
+pub(crate) const MOD_VERSION: &str = "__MOD_VERSION__";
+/// mod源文件夾的 git 短哈希
+pub(crate) const BUILD_HASH: &str = "__BUILD_HASH__";
+
+/// 常量名稱必須是 JS 標識符
+pub(crate) fn validate_name(name: &str) -> Result<(), Error> {
+  let mut chars = name.chars();
+  let valid =
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || c == '$') &&
+    chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$');
+  if valid {
+    Ok(())
+  } else {
+    Err(Error::Config(t!("defines.invalid_name", name = name).to_string()))
+  }
+}
+
+/// 常量在文本中的值: 字符串為原文, 其他為 JSON
+fn raw(value: &Value) -> String {
+  match value {
+    Value::String(s) => s.clone(),
+    value => value.to_string(),
+  }
+}
+
+/// 替換文本中的常量, 常量前後不能是標識符中的字符
+/// * `word` - 標識符中的字符
+/// * `replace` - 返回常量替換成的文本
+fn replace_words(text: &str, word: fn(char) -> bool, mut replace: impl FnMut(&str) -> Option<String>) -> String {
+  let mut out = String::with_capacity(text.len());
+  let mut rest = text;
+  while let Some(start) = rest.find(word) {
+    let end = rest[start..].find(|c| !word(c)).map_or(rest.len(), |i| start + i);
+    out.push_str(&rest[..start]);
+    let name = &rest[start..end];
+    match replace(name) {
+      Some(value) => out.push_str(&value),
+      None => out.push_str(name),
+    }
+    rest = &rest[end..];
+  }
+  out.push_str(rest);
+  out
+}
+
+/// twee 中的變量名(如 SugarCube 的 `$money`)包括 `$`
+fn is_text_word(c: char) -> bool {
+  c.is_alphanumeric() || c == '_' || c == '$'
+}
+
+#[cfg(feature = "defines")]
+fn is_js_word(c: char) -> bool {
+  c.is_alphanumeric() || c == '_' || c == '$'
+}
+
+/// 替換 twee, CSS 等文本中的常量
+pub(crate) fn replace_text(text: &str, defines: &BTreeMap<String, Value>) -> String {
+  replace_words(text, is_text_word, |name| defines.get(name).map(raw))
+}
+
+/// 轉義放進字符串或模板字符串中的文本
+#[cfg(feature = "defines")]
+fn escape(text: &str, quote: char) -> String {
+  let mut out = String::with_capacity(text.len());
+  let mut chars = text.chars().peekable();
+  while let Some(c) = chars.next() {
+    match c {
+      '\\' => out.push_str("\\\\"),
+      '\n' => out.push_str("\\n"),
+      '\r' => out.push_str("\\r"),
+      '$' if quote == '`' && chars.peek() == Some(&'{') => out.push_str("\\$"),
+      c if c == quote => {
+        out.push('\\');
+        out.push(c);
+      }
+      c => out.push(c),
+    }
+  }
+  out
+}
+
+/// 收集 JS 中要替換的範圍
+#[cfg(feature = "defines")]
+struct Replacer<'s> {
+  source: &'s str,
+  scoping: &'s Scoping,
+  defines: &'s BTreeMap<String, Value>,
+  /// (開始, 結束, 替換成的文本)
+  edits: Vec<(u32, u32, String)>,
+}
+
+#[cfg(feature = "defines")]
+impl Replacer<'_> {
+  /// 讀取全局變量(沒有本地綁定)的常量
+  fn define(&self, ident: &IdentifierReference) -> Option<&Value> {
+    let reference = self.scoping.get_reference(ident.reference_id.get()?);
+    if reference.symbol_id().is_some() || reference.is_write() {
+      return None;
+    }
+    self.defines.get(ident.name.as_str())
+  }
+
+  /// 替換字符串或模板字符串中的常量
+  /// * `span` - 不含引號的範圍
+  fn text(&mut self, span: Span, quote: char) {
+    let text = span.source_text(self.source);
+    let replaced = replace_words(text, is_js_word, |name| self.defines.get(name).map(|v| escape(&raw(v), quote)));
+    if replaced != text {
+      self.edits.push((span.start, span.end, replaced));
+    }
+  }
+}
+
+#[cfg(feature = "defines")]
+impl<'a> Visit<'a> for Replacer<'_> {
+  fn visit_identifier_reference(&mut self, it: &IdentifierReference<'a>) {
+    if let Some(value) = self.define(it) {
+      self.edits.push((it.span.start, it.span.end, value.to_string()));
+    }
+  }
+
+  fn visit_object_property(&mut self, it: &ObjectProperty<'a>) {
+    if it.shorthand && let Expression::Identifier(ident) = &it.value && let Some(value) = self.define(ident) {
+      let text = format!("{}: {}", ident.name, value);
+      self.edits.push((ident.span.start, ident.span.end, text));
+      return;
+    }
+    walk::walk_object_property(self, it);
+  }
+
+  fn visit_string_literal(&mut self, it: &StringLiteral<'a>) {
+    let quote = it.span.source_text(self.source).chars().next().unwrap_or('"');
+    self.text(Span::new(it.span.start + 1, it.span.end - 1), quote);
+  }
+
+  fn visit_template_element(&mut self, it: &TemplateElement<'a>) {
+    self.text(it.span, '`');
+  }
+
+  // `export { N }` 導出的是本地綁定, 不是常量
+  fn visit_export_specifier(&mut self, _it: &ExportSpecifier<'a>) {}
+}
+
+/// 替換 JS 中的常量
+/// * 只替換沒有本地綁定的讀取(`IdentifierReference`), 替換為 JSON 字面量; 簡寫屬性 `{ N }` 替換為 `{ N: 值 }`
+/// * 屬性名, 聲明, 參數, 解構和賦值的目標等不替換
+/// * 字符串, 模板字符串和註釋中替換為(轉義後的)原文, 正則表達式中不替換
+/// # 錯誤
+/// * 語法錯誤: (字節偏移, 消息)
+#[cfg(feature = "defines")]
+pub(crate) fn replace_js(code: &str, defines: &BTreeMap<String, Value>) -> Result<String, (usize, String)> {
+  let allocator = Allocator::default();
+  let ret = Parser::new(&allocator, code, SourceType::unambiguous()).parse();
+  if let Some(e) = ret.errors.first() {
+    let offset = e.labels
+      .as_ref()
+      .and_then(|labels| labels.first())
+      .map_or(0, |label| label.offset());
+    return Err((offset, e.message.to_string()));
+  }
+  let semantic = SemanticBuilder::new().build(&ret.program).semantic;
+  let mut replacer = Replacer { source: code, scoping: semantic.scoping(), defines, edits: Vec::new() };
+  replacer.visit_program(&ret.program);
+  for comment in &ret.program.comments {
+    let text = comment.span.source_text(code);
+    let replaced = replace_words(text, is_js_word, |name| defines.get(name).map(raw));
+    if replaced != text {
+      replacer.edits.push((comment.span.start, comment.span.end, replaced));
+    }
+  }
+
+  let mut edits = replacer.edits;
+  edits.sort_by_key(|(start, ..)| *start);
+  let mut out = String::with_capacity(code.len());
+  let mut last = 0;
+  for (start, end, text) in edits {
+    out.push_str(&code[last..start as usize]);
+    out.push_str(&text);
+    last = end as usize;
+  }
+  out.push_str(&code[last..]);
+  Ok(out)
+}
+
+/// 替換 boot.json 頂層的字符串字段(如 `name`, `version`)中的常量
+/// * 文件列表, `addonPlugin`, `dependenceInfo` 等不替換
+/// # 返回
+/// * 是否有修改
+fn replace_metadata(boot_json: &mut Value, defines: &BTreeMap<String, Value>) -> bool {
+  let Value::Object(map) = boot_json else {
+    return false;
+  };
+  let mut changed = false;
+  for value in map.values_mut() {
+    if let Value::String(s) = value {
+      let replaced = replace_text(s, defines);
+      changed |= replaced != *s;
+      *s = replaced;
+    }
+  }
+  changed
+}
+
+/// 替換mod文件夾中的常量
+/// * 先替換 boot.json 的元數據, 再用替換後的 `name` 和 `version` 作為 `__MOD_NAME__` 和 `__MOD_VERSION__`
+/// * 只替換 boot.json 的 scriptFileList(包括 early/preload 列表), styleFileList 和 tweeFileList 中的文件,
+///   列表與 boot_json 步驟掃描後的相同
+/// * `defines` - cofg.json 中的常量, 內置常量優先
+/// * `hash` - `__BUILD_HASH__` 的值
+/// # 返回
+/// * 修改了的文件(相對mod文件夾)
+pub(crate) fn apply(root: &Path, defines: &BTreeMap<String, Value>, hash: &str) -> Result<Vec<String>, Error> {
+  let mut defines = defines.clone();
+  defines.insert(BUILD_HASH.to_string(), Value::from(hash));
+  let mut changed = Vec::new();
+
+  let boot_json_path = root.join("boot.json");
+  let text = fs::read_to_string(&boot_json_path).with_path(&boot_json_path)?;
+  let mut boot_json: Value = serde_json::from_str(&text).map_err(|e| Error::BootJson {
+    path: boot_json_path.clone(),
+    msg: e.to_string(),
+    pos: Some((e.line(), e.column())),
+  })?;
+  if replace_metadata(&mut boot_json, &defines) {
+    fs::write(&boot_json_path, serde_json::to_string_pretty(&boot_json)?).with_path(&boot_json_path)?;
+    changed.push("boot.json".to_string());
+  }
+  let name = boot_json.get("name").cloned().unwrap_or(Value::Null);
+  let version = boot_json.get("version").cloned().unwrap_or(Value::Null);
+  defines.insert(MOD_NAME.to_string(), name);
+  defines.insert(MOD_VERSION.to_string(), version);
+
+  let mut lists = BootJson::new(&boot_json_path.to_string_lossy())?;
+  lists.update_file_lists(root, false)?;
+  let mut files: Vec<&String> = lists
+    .file_lists()
+    .into_iter()
+    .filter(|(list, _)| list.starts_with("scriptFileList") || matches!(*list, "styleFileList" | "tweeFileList"))
+    .flat_map(|(_, files)| files)
+    .collect();
+  files.sort();
+  files.dedup();
+  for file in files {
+    let Some(path) = fs_utils::safe_join(root, file) else {
+      continue;
+    };
+    let ext = path.extension().and_then(|ext| ext.to_str()).unwrap_or_default();
+    if !path.is_file() || !matches!(ext, "js" | "twee" | "css") {
+      continue;
+    }
+    let text = fs::read_to_string(&path).with_path(&path)?;
+    if !text.contains("__") && !defines.keys().any(|name| text.contains(name.as_str())) {
+      continue;
+    }
+    let replaced = if ext == "js" { js(&path, &text, &defines)? } else { replace_text(&text, &defines) };
+    if replaced != text {
+      fs::write(&path, replaced).with_path(&path)?;
+      changed.push(file.clone());
+    }
+  }
+  Ok(changed)
+}
+
+/// 替換 JS 文件中的常量
+#[cfg(feature = "defines")]
+fn js(path: &Path, text: &str, defines: &BTreeMap<String, Value>) -> Result<String, Error> {
+  replace_js(text, defines).map_err(|(offset, msg)| Error::Defines {
+    path: path.to_path_buf(),
+    msg,
+    pos: Some(sourcemap::position(text, offset)),
+  })
+}
+
+/// 沒有啟用 `defines` 功能時不能替換 JS 中的常量
+#[cfg(not(feature = "defines"))]
+fn js(_path: &Path, text: &str, defines: &BTreeMap<String, Value>) -> Result<String, Error> {
+  if replace_text(text, defines) != text {
+    return Err(Error::Config(t!("defines.disabled").to_string()));
+  }
+  Ok(text.to_string())
+}
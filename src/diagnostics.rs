@@ -0,0 +1,273 @@
+//! 統計每個mod的警告和錯誤, 並以 CI 使用的格式輸出
+
+use std::path::{ Path, PathBuf };
+use serde::Serialize;
+use serde_json::json;
+
+/// 帶消息鍵的警告, 消息鍵用於構建報告
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct Warning {
+  /// 本地化消息的鍵, 如 `lint.reserved`
+  pub key: &'static str,
+  /// 本地化後的消息
+  pub message: String,
+  /// 相關的文件
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub file: Option<PathBuf>,
+}
+
+impl Warning {
+  /// 設置相關的文件
+  pub(crate) fn with_file(mut self, file: impl Into<PathBuf>) -> Warning {
+    self.file = Some(file.into());
+    self
+  }
+}
+
+impl std::fmt::Display for Warning {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{}", self.message)
+  }
+}
+
+/// 創建 [`Warning`], 參數與 `t!` 相同
+/// ```rust
+/// let w = warning!("lint.not_nfc", path = path);
+/// ```
+macro_rules! warning {
+  ($key:literal $(, $($args:tt)*)?) => {
+    $crate::diagnostics::Warning {
+      key: $key,
+      message: rust_i18n::t!($key $(, $($args)*)?).to_string(),
+      file: None,
+    }
+  };
+}
+pub(crate) use warning;
+
+/// 診斷信息的輸出格式
+#[derive(clap::ValueEnum, Serialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Format {
+  /// 只輸出日誌
+  #[default]
+  Text,
+  /// GitHub Actions 的 workflow commands (`::error file=...::`)
+  Github,
+  /// SARIF 2.1.0, 用於 code scanning
+  Sarif,
+  /// JUnit XML, 每個mod一個 test case
+  Junit,
+}
+
+/// 診斷信息的級別
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Severity {
+  Warning,
+  Error,
+}
+
+impl Severity {
+  fn as_str(self) -> &'static str {
+    match self {
+      Severity::Warning => "warning",
+      Severity::Error => "error",
+    }
+  }
+}
+
+/// 一條帶位置的診斷信息
+#[derive(Debug, Clone)]
+pub(crate) struct Diagnostic {
+  pub severity: Severity,
+  /// 本地化消息的鍵, 用作 SARIF 的 ruleId
+  pub key: String,
+  pub message: String,
+  /// 相關的文件(mod源文件夾中的路徑)
+  pub file: Option<PathBuf>,
+  /// 行, 從 1 開始
+  pub line: Option<usize>,
+  /// 列, 從 1 開始
+  pub column: Option<usize>,
+}
+
+/// 一個mod的所有診斷信息, 對應 JUnit 中的一個 test case
+pub(crate) struct ModDiagnostics {
+  /// mod名稱
+  pub name: String,
+  /// 用時(毫秒)
+  pub time_ms: u64,
+  pub diagnostics: Vec<Diagnostic>,
+}
+
+/// 統計所有mod的 (警告數量, 錯誤數量)
+pub(crate) fn counts(all: &[ModDiagnostics]) -> (usize, usize) {
+  let diagnostics = all.iter().flat_map(|m| &m.diagnostics);
+  let errors = diagnostics.clone().filter(|d| d.severity == Severity::Error).count();
+  (diagnostics.count() - errors, errors)
+}
+
+/// 文件路徑轉換為相對的 URI, 如 `./mods/a\\b.twee` -> `mods/a/b.twee`
+fn uri(path: &Path) -> String {
+  let path = path.to_string_lossy().replace('\\', "/");
+  path.trim_start_matches("./").to_string()
+}
+
+/// 按指定格式輸出所有診斷信息
+/// * `Text` 格式返回 `None`
+pub(crate) fn render(format: Format, mods: &[ModDiagnostics]) -> Option<String> {
+  match format {
+    Format::Text => None,
+    Format::Github => Some(github(mods)),
+    Format::Sarif => Some(sarif(mods)),
+    Format::Junit => Some(junit(mods)),
+  }
+}
+
+/// GitHub Actions 的 workflow commands
+/// * 參考 <https://docs.github.com/actions/reference/workflow-commands-for-github-actions>
+fn github(mods: &[ModDiagnostics]) -> String {
+  let escape = |s: &str| s.replace('%', "%25").replace('\r', "%0D").replace('\n', "%0A");
+  let escape_prop = |s: &str| escape(s).replace(':', "%3A").replace(',', "%2C");
+
+  let mut out = String::new();
+  for d in mods.iter().flat_map(|m| &m.diagnostics) {
+    let mut props = Vec::new();
+    if let Some(file) = &d.file {
+      props.push(format!("file={}", escape_prop(&uri(file))));
+    }
+    if let Some(line) = d.line {
+      props.push(format!("line={}", line));
+    }
+    if let Some(column) = d.column {
+      props.push(format!("col={}", column));
+    }
+    props.push(format!("title={}", escape_prop(&d.key)));
+    out.push_str(&format!("::{} {}::{}\n", d.severity.as_str(), props.join(","), escape(&d.message)));
+  }
+  out
+}
+
+/// SARIF 2.1.0
+fn sarif(mods: &[ModDiagnostics]) -> String {
+  let mut rules: Vec<&str> = mods
+    .iter()
+    .flat_map(|m| &m.diagnostics)
+    .map(|d| d.key.as_str())
+    .collect();
+  rules.sort();
+  rules.dedup();
+
+  let results: Vec<serde_json::Value> = mods
+    .iter()
+    .flat_map(|m| &m.diagnostics)
+    .map(|d| {
+      let mut result =
+        json!({
+        "ruleId": d.key,
+        "level": d.severity.as_str(),
+        "message": { "text": d.message },
+      });
+      if let Some(file) = &d.file {
+        let mut location = json!({ "artifactLocation": { "uri": uri(file) } });
+        if let Some(line) = d.line {
+          location["region"] = json!({ "startLine": line, "startColumn": d.column.unwrap_or(1) });
+        }
+        result["locations"] = json!([{ "physicalLocation": location }]);
+      }
+      result
+    })
+    .collect();
+
+  let doc =
+    json!({
+    "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+    "version": "2.1.0",
+    "runs": [{
+      "tool": {
+        "driver": {
+          "name": "mod-dev",
+          "version": crate::r#const::VERSION,
+          "rules": rules.iter().map(|id| json!({ "id": id })).collect::<Vec<_>>(),
+        },
+      },
+      "results": results,
+    }],
+  });
+  serde_json::to_string_pretty(&doc).unwrap_or_default()
+}
+
+/// 轉義 XML 文本和屬性
+fn xml_escape(s: &str) -> String {
+  s.replace('&', "&amp;")
+    .replace('<', "&lt;")
+    .replace('>', "&gt;")
+    .replace('"', "&quot;")
+    .replace('\'', "&apos;")
+}
+
+/// JUnit XML, 每個mod一個 test case
+/// * 有錯誤的mod為 failure, 警告放在 system-out 中
+fn junit(mods: &[ModDiagnostics]) -> String {
+  let failures = mods
+    .iter()
+    .filter(|m| m.diagnostics.iter().any(|d| d.severity == Severity::Error))
+    .count();
+  let time: u64 = mods.iter().map(|m| m.time_ms).sum();
+
+  let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+  out.push_str(
+    &format!(
+      "<testsuites name=\"mod-dev\" tests=\"{}\" failures=\"{}\" time=\"{:.3}\">\n",
+      mods.len(),
+      failures,
+      (time as f64) / 1000.0
+    )
+  );
+  out.push_str(
+    &format!(
+      "  <testsuite name=\"mods\" tests=\"{}\" failures=\"{}\" time=\"{:.3}\">\n",
+      mods.len(),
+      failures,
+      (time as f64) / 1000.0
+    )
+  );
+  for m in mods {
+    out.push_str(
+      &format!(
+        "    <testcase name=\"{}\" classname=\"mods\" time=\"{:.3}\">\n",
+        xml_escape(&m.name),
+        (m.time_ms as f64) / 1000.0
+      )
+    );
+    let line = |d: &Diagnostic| {
+      match &d.file {
+        Some(file) => format!("{}: {}", uri(file), d.message),
+        None => d.message.clone(),
+      }
+    };
+    for d in m.diagnostics.iter().filter(|d| d.severity == Severity::Error) {
+      out.push_str(
+        &format!(
+          "      <failure type=\"{}\" message=\"{}\">{}</failure>\n",
+          xml_escape(&d.key),
+          xml_escape(d.message.lines().next().unwrap_or_default()),
+          xml_escape(&line(d))
+        )
+      );
+    }
+    let warnings: Vec<String> = m.diagnostics
+      .iter()
+      .filter(|d| d.severity == Severity::Warning)
+      .map(|d| format!("warning: {}", line(d)))
+      .collect();
+    if !warnings.is_empty() {
+      out.push_str(
+        &format!("      <system-out>{}</system-out>\n", xml_escape(&warnings.join("\n")))
+      );
+    }
+    out.push_str("    </testcase>\n");
+  }
+  out.push_str("  </testsuite>\n</testsuites>\n");
+  out
+}
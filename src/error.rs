@@ -0,0 +1,236 @@
+//! 錯誤類型
+
+use std::path::{ Path, PathBuf };
+use rust_i18n::t;
+
+/// 程序內部錯誤(panic)時的退出碼
+pub const PANIC_EXIT_CODE: i32 = 12;
+
+/// 程序中所有可能出現的錯誤
+#[derive(Debug)]
+pub enum Error {
+  /// 配置錯誤(cofg.json, 命令行參數, file_name 模板等)
+  Config(String),
+  /// 文件系統錯誤
+  Io {
+    path: PathBuf,
+    source: std::io::Error,
+  },
+  /// glob 模式錯誤
+  Glob(glob::PatternError),
+  /// JSON 序列化/反序列化錯誤
+  Json(serde_json::Error),
+  /// boot.json 解析錯誤
+  BootJson {
+    path: PathBuf,
+    msg: String,
+    /// 出錯的 (行, 列), 從 1 開始
+    pos: Option<(usize, usize)>,
+  },
+  /// 路徑不在指定目錄下
+  StripPrefix {
+    prefix: PathBuf,
+    path: PathBuf,
+  },
+  /// 無法啟動 tsc
+  TscSpawn {
+    cmd: String,
+    source: std::io::Error,
+  },
+  /// tsc 編譯失敗, 具體的錯誤見構建報告中的 tsc 診斷信息
+  Tsc {
+    path: PathBuf,
+    /// 錯誤數量
+    errors: usize,
+  },
+  /// 打包 ES 模塊失敗
+  Bundle {
+    path: PathBuf,
+    msg: String,
+    /// 出錯的 (行, 列), 從 1 開始
+    pos: Option<(usize, usize)>,
+  },
+  /// 替換 JS 中的常量時解析失敗
+  Defines {
+    path: PathBuf,
+    msg: String,
+    /// 出錯的 (行, 列), 從 1 開始
+    pos: Option<(usize, usize)>,
+  },
+  /// zip 讀寫錯誤
+  Zip {
+    path: PathBuf,
+    source: zip::result::ZipError,
+  },
+  /// 打包後檢查 zip 失敗
+  Verify {
+    path: PathBuf,
+    problems: Vec<String>,
+  },
+  /// mod 名稱或輸出路徑衝突
+  Collision(usize),
+  /// 子命令的使用錯誤(如目標已存在, 未知模板)
+  Usage(String),
+  /// `deny_warnings` 時出現了警告
+  DeniedWarnings(usize),
+  /// hooks 中的命令失敗
+  Hook {
+    stage: String,
+    cmd: String,
+    msg: String,
+  },
+  /// 自定義步驟的腳本出錯
+  Script {
+    path: PathBuf,
+    msg: String,
+    /// 出錯的 (行, 列), 從 1 開始
+    pos: Option<(usize, usize)>,
+  },
+}
+
+impl Error {
+  /// 進程退出碼, 每類錯誤不同
+  pub fn exit_code(&self) -> i32 {
+    match self {
+      Error::Config(_) | Error::Glob(_) => 2,
+      Error::Io { .. } | Error::StripPrefix { .. } => 3,
+      Error::BootJson { .. } | Error::Json(_) => 4,
+      Error::TscSpawn { .. } | Error::Tsc { .. } | Error::Bundle { .. } | Error::Defines { .. } => 5,
+      Error::Zip { .. } | Error::Verify { .. } => 6,
+      Error::Collision(_) => 7,
+      Error::Usage(_) => 8,
+      Error::DeniedWarnings(_) => 9,
+      Error::Hook { .. } => 10,
+      Error::Script { .. } => 11,
+    }
+  }
+
+  /// 出錯的文件
+  pub fn path(&self) -> Option<&Path> {
+    match self {
+      Error::Io { path, .. } | Error::BootJson { path, .. } | Error::StripPrefix { path, .. } =>
+        Some(path),
+      Error::Tsc { path, .. } | Error::Bundle { path, .. } | Error::Defines { path, .. } => Some(path),
+      Error::Script { path, .. } => Some(path),
+      Error::Zip { path, .. } | Error::Verify { path, .. } => Some(path),
+      _ => None,
+    }
+  }
+
+  /// 出錯的 (行, 列)
+  pub fn position(&self) -> Option<(usize, usize)> {
+    match self {
+      Error::BootJson { pos, .. } | Error::Bundle { pos, .. } | Error::Defines { pos, .. } => *pos,
+      Error::Script { pos, .. } => *pos,
+      Error::Json(e) => Some((e.line(), e.column())),
+      _ => None,
+    }
+  }
+
+  /// 錯誤類型對應的消息鍵, 用於構建報告
+  /// * `Config` 和 `Usage` 的消息來自多個鍵, 統一用 `errors.config` 和 `errors.usage`
+  pub fn key(&self) -> &'static str {
+    match self {
+      Error::Config(_) => "errors.config",
+      Error::Io { .. } => "errors.io",
+      Error::Glob(_) => "errors.glob",
+      Error::Json(_) => "json.parse_error",
+      Error::BootJson { .. } => "errors.boot_json",
+      Error::StripPrefix { .. } => "errors.strip_prefix",
+      Error::TscSpawn { source, .. } if source.kind() == std::io::ErrorKind::NotFound =>
+        "errors.tsc_not_found",
+      Error::TscSpawn { .. } => "errors.tsc_spawn",
+      Error::Tsc { .. } => "ts.tsc_error",
+      Error::Bundle { .. } => "errors.bundle",
+      Error::Defines { .. } => "errors.defines",
+      Error::Zip { .. } => "errors.zip",
+      Error::Verify { .. } => "verify.failed",
+      Error::Collision(_) => "collision.abort",
+      Error::Usage(_) => "errors.usage",
+      Error::DeniedWarnings(_) => "build.denied_warnings",
+      Error::Hook { .. } => "errors.hook",
+      Error::Script { .. } => "errors.script",
+    }
+  }
+}
+
+impl std::fmt::Display for Error {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    let msg = match self {
+      Error::Config(msg) | Error::Usage(msg) => msg.clone().into(),
+      Error::Io { path, source } =>
+        t!("errors.io", path = path.display(), e = source),
+      Error::Glob(e) => t!("errors.glob", e = e),
+      Error::Json(e) => t!("json.parse_error", msg = e),
+      Error::BootJson { path, msg, .. } =>
+        t!("errors.boot_json", path = path.display(), msg = msg),
+      Error::StripPrefix { prefix, path } =>
+        t!("errors.strip_prefix", prefix = prefix.display(), path = path.display()),
+      Error::TscSpawn { cmd, source } if source.kind() == std::io::ErrorKind::NotFound =>
+        t!("errors.tsc_not_found", cmd = cmd),
+      Error::TscSpawn { cmd, source } => t!("errors.tsc_spawn", cmd = cmd, e = source),
+      Error::Tsc { path, errors } => t!("ts.tsc_error", path = path.display(), count = errors),
+      Error::Bundle { path, msg, pos } => t!("errors.bundle", path = location(path, pos), msg = msg),
+      Error::Defines { path, msg, pos } => t!("errors.defines", path = location(path, pos), msg = msg),
+      Error::Zip { path, source } => t!("errors.zip", path = path.display(), e = source),
+      Error::Verify { path, problems } =>
+        format!(
+          "{}\n        {}",
+          t!("verify.failed", path = path.display(), count = problems.len()),
+          problems.join("\n        ")
+        ).into(),
+      Error::Collision(count) => t!("collision.abort", count = count),
+      Error::DeniedWarnings(count) => t!("build.denied_warnings", count = count),
+      Error::Hook { stage, cmd, msg } => t!("errors.hook", stage = stage, cmd = cmd, msg = msg),
+      Error::Script { path, msg, pos } => t!("errors.script", path = location(path, pos), msg = msg),
+    };
+    write!(f, "{}", msg)
+  }
+}
+
+/// 帶位置的路徑 `path:line:column`
+fn location(path: &Path, pos: &Option<(usize, usize)>) -> String {
+  match pos {
+    Some((line, column)) => format!("{}:{}:{}", path.display(), line, column),
+    None => path.display().to_string(),
+  }
+}
+
+impl std::error::Error for Error {}
+
+impl From<glob::PatternError> for Error {
+  fn from(e: glob::PatternError) -> Self {
+    Error::Glob(e)
+  }
+}
+
+impl From<serde_json::Error> for Error {
+  fn from(e: serde_json::Error) -> Self {
+    Error::Json(e)
+  }
+}
+
+impl From<walkdir::Error> for Error {
+  fn from(e: walkdir::Error) -> Self {
+    let path = e.path().map(Path::to_path_buf).unwrap_or_default();
+    Error::Io { path, source: e.into() }
+  }
+}
+
+/// 給 IO 和 zip 錯誤加上路徑
+pub trait WithPath<T> {
+  /// 出錯時記錄相關的路徑
+  fn with_path(self, path: impl AsRef<Path>) -> Result<T, Error>;
+}
+
+impl<T> WithPath<T> for Result<T, std::io::Error> {
+  fn with_path(self, path: impl AsRef<Path>) -> Result<T, Error> {
+    self.map_err(|source| Error::Io { path: path.as_ref().to_path_buf(), source })
+  }
+}
+
+impl<T> WithPath<T> for Result<T, zip::result::ZipError> {
+  fn with_path(self, path: impl AsRef<Path>) -> Result<T, Error> {
+    self.map_err(|source| Error::Zip { path: path.as_ref().to_path_buf(), source })
+  }
+}
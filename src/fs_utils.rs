@@ -58,3 +58,27 @@ pub(crate) fn check_empty_dirs(path: &std::path::PathBuf) -> bool {
     }
   }
 }
+
+/// 把相對路徑按字面規範化後接到 `base` 上
+/// * 去掉 `.`, 處理 `..`
+/// * 絕對路徑(包括 `C:` 之類的盤符)或跳出 `base` 的路徑返回 `None`
+pub(crate) fn safe_join(base: &Path, rel: &str) -> Option<std::path::PathBuf> {
+  let rel = rel.replace('\\', "/");
+  if rel.starts_with('/') || rel.chars().nth(1) == Some(':') {
+    return None;
+  }
+  let mut parts: Vec<&str> = Vec::new();
+  for part in rel.split('/') {
+    match part {
+      "" | "." => (),
+      ".." => {
+        parts.pop()?;
+      }
+      part => parts.push(part),
+    }
+  }
+  if parts.is_empty() {
+    return None;
+  }
+  Some(parts.iter().fold(base.to_path_buf(), |path, part| path.join(part)))
+}
@@ -0,0 +1,60 @@
+//! 構建步驟前後運行的命令 (`hooks`)
+//!
+//! 命令用 shell 運行(Windows 上為 `cmd /C`), 工作目錄為mod源文件夾,
+//! 通過 `MOD_DEV_*` 環境變量取得mod的信息。命令失敗(退出碼不為 0)時該mod構建失敗, stderr 的輸出不算失敗。
+
+use std::path::Path;
+use std::process::Command;
+use log::info;
+use rust_i18n::t;
+use crate::error::Error;
+
+/// 內置的步驟, 按運行順序
+pub(crate) const STAGES: [&str; 7] = ["copy", "ts", "defines", "bundle", "boot_json", "minify", "compress"];
+
+/// 步驟名稱必須是 [`STAGES`] 之一或自定義步驟的名稱
+pub(crate) fn validate_stage(stage: &str, custom: &[&str]) -> Result<(), Error> {
+  if STAGES.contains(&stage) || custom.contains(&stage) {
+    Ok(())
+  } else {
+    let stages: Vec<&str> = STAGES.iter().chain(custom).copied().collect();
+    Err(Error::Config(t!("hooks.unknown_stage", stage = stage, stages = stages.join(", ")).to_string()))
+  }
+}
+
+/// 運行一個命令, 輸出逐行記錄到日誌
+/// * `stage` - 步驟名稱, 用於錯誤信息
+/// * `cwd` - 工作目錄
+/// * `env` - 額外的環境變量
+pub(crate) fn run(stage: &str, cmd: &str, cwd: &Path, env: &[(&str, String)]) -> Result<(), Error> {
+  info!("    {}", t!("hooks.run", stage = stage, cmd = cmd));
+  let mut command = if cfg!(windows) {
+    let mut command = Command::new("cmd");
+    command.arg("/C").arg(cmd);
+    command
+  } else {
+    let mut command = Command::new("sh");
+    command.arg("-c").arg(cmd);
+    command
+  };
+  let output = command
+    .current_dir(cwd)
+    .envs(env.iter().map(|(key, value)| (*key, value)))
+    .output()
+    .map_err(|e| Error::Hook { stage: stage.to_string(), cmd: cmd.to_string(), msg: e.to_string() })?;
+  for line in String::from_utf8_lossy(&output.stdout).lines() {
+    info!("        {}", line);
+  }
+  for line in String::from_utf8_lossy(&output.stderr).lines() {
+    info!("        {}", line);
+  }
+  if output.status.success() {
+    Ok(())
+  } else {
+    let msg = match output.status.code() {
+      Some(code) => t!("hooks.exit_code", code = code).to_string(),
+      None => t!("hooks.killed").to_string(),
+    };
+    Err(Error::Hook { stage: stage.to_string(), cmd: cmd.to_string(), msg })
+  }
+}
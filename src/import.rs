@@ -0,0 +1,177 @@
+//! `mod-dev import`: 把打包好的mod解壓回可編輯的源文件夾
+
+use std::fs::{ self, File };
+use std::io::Read;
+use std::path::{ Path, PathBuf };
+use log::{ info, warn };
+use rust_i18n::t;
+use serde::Deserialize;
+use walkdir::WalkDir;
+use crate::boot_json::{ BootJson, process_file_path };
+use crate::cofg::Cofg;
+use crate::error::{ Error, WithPath };
+use crate::fs_utils::safe_join;
+use crate::template::sanitize_file_name;
+
+/// source map 中需要的部分
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SourceMap {
+  #[serde(default)]
+  source_root: Option<String>,
+  #[serde(default)]
+  sources: Vec<String>,
+  #[serde(default)]
+  sources_content: Vec<Option<String>>,
+}
+
+/// 解壓mod zip到 `mods_path/<name>`
+/// * `zip_path` - mod zip 文件
+/// * `root` - 解壓到哪個目錄下, 默認為 `mods_path` 的第一個目錄
+/// * `sources` - 是否從 `*.js.map` 的 `sourcesContent` 還原源文件
+/// # 錯誤處理
+/// - zip 中有絕對路徑或 `../` 時整個導入失敗
+/// - boot.json 無效時刪除解壓的文件
+/// # 返回
+/// * 新mod文件夾的路徑
+pub(crate) fn import_mod(
+  cofg: &Cofg,
+  zip_path: &Path,
+  root: Option<&Path>,
+  sources: bool
+) -> Result<PathBuf, Error> {
+  let file = File::open(zip_path).with_path(zip_path)?;
+  let mut archive = zip::ZipArchive::new(file).with_path(zip_path)?;
+  let root = match root {
+    Some(root) => root.to_path_buf(),
+    None =>
+      cofg.path.mods_path
+        .roots()
+        .into_iter()
+        .next()
+        .ok_or_else(|| Error::Usage(t!("new.no_mods_path").to_string()))?,
+  };
+
+  // 先檢查所有路徑, 避免解壓到一半才失敗
+  let staging = root.join(
+    format!(".import-{}", zip_path.file_stem().unwrap_or_default().to_string_lossy())
+  );
+  let mut entries = Vec::new();
+  for i in 0..archive.len() {
+    let file = archive.by_index(i).with_path(zip_path)?;
+    // `./` 之類的條目就是根目錄本身, 不需要解壓
+    if file.name().replace('\\', "/").split('/').all(|part| part.is_empty() || part == ".") {
+      continue;
+    }
+    match safe_join(&staging, file.name()) {
+      Some(dest) => entries.push((i, dest, file.is_dir())),
+      None => {
+        return Err(Error::Usage(t!("import.unsafe_path", path = file.name()).to_string()));
+      }
+    }
+  }
+  if !entries.iter().any(|(_, dest, is_dir)| !is_dir && *dest == staging.join("boot.json")) {
+    return Err(
+      Error::BootJson {
+        path: zip_path.to_path_buf(),
+        msg: t!("import.no_boot_json", path = zip_path.display()).to_string(),
+        pos: None,
+      }
+    );
+  }
+
+  if staging.exists() {
+    fs::remove_dir_all(&staging).with_path(&staging)?;
+  }
+  let result = (|| -> Result<PathBuf, Error> {
+    for (i, dest, is_dir) in entries {
+      if is_dir {
+        fs::create_dir_all(&dest).with_path(&dest)?;
+        continue;
+      }
+      if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent).with_path(parent)?;
+      }
+      let mut content = Vec::new();
+      archive.by_index(i).with_path(zip_path)?.read_to_end(&mut content).with_path(zip_path)?;
+      fs::write(&dest, content).with_path(&dest)?;
+    }
+
+    let boot_json = BootJson::new(&staging.join("boot.json").to_string_lossy())?;
+    let dir = root.join(sanitize_file_name(&boot_json.name));
+    if dir.exists() {
+      return Err(Error::Usage(t!("new.exists", path = dir.display()).to_string()));
+    }
+    fs::rename(&staging, &dir).with_path(&dir)?;
+    Ok(dir)
+  })();
+  let dir = match result {
+    Ok(dir) => dir,
+    Err(e) => {
+      let _ = fs::remove_dir_all(&staging);
+      return Err(e);
+    }
+  };
+
+  if sources {
+    let count = restore_sources(&dir)?;
+    info!("    {}", t!("import.sources_restored", count = count));
+  }
+
+  info!("{}", t!("import.done", zip = zip_path.display(), path = dir.display()));
+  Ok(dir)
+}
+
+/// 從 `*.js.map` 的 `sourcesContent` 還原源文件
+/// * 已存在的文件不會被覆蓋
+/// * 跳出mod文件夾的路徑會被忽略
+/// # 返回
+/// * 還原的文件數量
+pub(crate) fn restore_sources(dir: &Path) -> Result<usize, Error> {
+  let mut count = 0;
+  for entry in WalkDir::new(dir).sort_by_file_name().into_iter().flatten() {
+    let map_path = entry.path();
+    if !entry.file_type().is_file() || !map_path.to_string_lossy().ends_with(".js.map") {
+      continue;
+    }
+    let map: SourceMap = match serde_json::from_slice(&fs::read(map_path).with_path(map_path)?) {
+      Ok(map) => map,
+      Err(e) => {
+        warn!("    {}", t!("json.parse_error", msg = format!("{}: {}", map_path.display(), e)));
+        continue;
+      }
+    };
+    let map_dir = process_file_path(map_path.parent().unwrap_or(dir), dir)?;
+    let source_root = map.source_root.unwrap_or_default();
+
+    for (source, content) in map.sources.iter().zip(&map.sources_content) {
+      let Some(content) = content else {
+        continue;
+      };
+      // 去掉 `webpack:///` 之類的前綴
+      let source = match source.split_once("://") {
+        Some((_, s)) => s.trim_start_matches('/'),
+        None => source,
+      };
+      let rel = [map_dir.as_str(), source_root.as_str(), source]
+        .iter()
+        .filter(|s| !s.is_empty())
+        .copied()
+        .collect::<Vec<_>>()
+        .join("/");
+      match safe_join(dir, &rel) {
+        Some(dest) if !dest.exists() => {
+          if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent).with_path(parent)?;
+          }
+          fs::write(&dest, content).with_path(&dest)?;
+          info!("    {}", t!("import.source_restored", path = dest.display()));
+          count += 1;
+        }
+        Some(_) => (),
+        None => warn!("    {}", t!("import.unsafe_source", path = source)),
+      }
+    }
+  }
+  Ok(count)
+}
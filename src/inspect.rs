@@ -0,0 +1,235 @@
+//! `mod-dev inspect` 和 `mod-dev diff`: 查看和比較打包好的mod zip
+
+use std::collections::{ BTreeMap, BTreeSet };
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use rust_i18n::t;
+use crate::boot_json::BootJson;
+use crate::error::{ Error, WithPath };
+
+/// 讀取到內存中的mod zip
+pub(crate) struct ModZip {
+  /// 文件路徑 -> 內容(不含目錄)
+  pub files: BTreeMap<String, Vec<u8>>,
+}
+
+impl ModZip {
+  /// 讀取mod zip
+  pub(crate) fn open(path: &Path) -> Result<ModZip, Error> {
+    let file = File::open(path).with_path(path)?;
+    let mut archive = zip::ZipArchive::new(file).with_path(path)?;
+    let mut files = BTreeMap::new();
+    for i in 0..archive.len() {
+      let mut file = archive.by_index(i).with_path(path)?;
+      if file.is_dir() {
+        continue;
+      }
+      let mut content = Vec::new();
+      file.read_to_end(&mut content).with_path(path)?;
+      files.insert(file.name().to_string(), content);
+    }
+    Ok(ModZip { files })
+  }
+
+  /// 解析 zip 中的 boot.json
+  pub(crate) fn boot_json(&self) -> Result<BootJson, Error> {
+    let content = self.files.get("boot.json").ok_or_else(|| {
+      Error::BootJson {
+        path: "boot.json".into(),
+        msg: t!("inspect.no_boot_json").to_string(),
+        pos: None,
+      }
+    })?;
+    BootJson::from_slice(content)
+  }
+
+  /// zip 中存在但不在任何列表中的文件(boot.json 除外)
+  pub(crate) fn unlisted<'a>(&'a self, boot_json: &BootJson) -> Vec<&'a str> {
+    self.files
+      .keys()
+      .filter(|name| !boot_json.in_list(name))
+      .map(|name| name.as_str())
+      .collect()
+  }
+
+  /// 列表中有但 zip 中不存在的文件: (列表名稱, 文件)
+  pub(crate) fn missing<'a>(&self, boot_json: &'a BootJson) -> Vec<(&'static str, &'a str)> {
+    boot_json
+      .file_lists()
+      .into_iter()
+      .flat_map(|(list, files)| files.iter().map(move |file| (list, file.as_str())))
+      .filter(|(_, file)| !self.files.contains_key(*file))
+      .collect()
+  }
+}
+
+/// 格式化文件大小
+pub(crate) fn format_size(size: u64) -> String {
+  const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+  let mut size = size as f64;
+  let mut unit = 0;
+  while size >= 1024.0 && unit < UNITS.len() - 1 {
+    size /= 1024.0;
+    unit += 1;
+  }
+  if unit == 0 { format!("{} {}", size, UNITS[0]) } else { format!("{:.1} {}", size, UNITS[unit]) }
+}
+
+/// 顯示mod zip的內容
+/// * boot.json
+/// * 按列表分類的文件和大小
+/// * 未列出的文件和缺失的文件
+pub(crate) fn inspect(zip_path: &Path) -> Result<(), Error> {
+  let zip = ModZip::open(zip_path)?;
+  let boot_json = zip.boot_json()?;
+
+  println!("# {}", zip_path.display());
+  println!("{}", serde_json::to_string_pretty(&boot_json)?);
+
+  for (list, files) in boot_json.file_lists() {
+    let present: Vec<(&String, u64)> = files
+      .iter()
+      .filter_map(|file| zip.files.get(file).map(|content| (file, content.len() as u64)))
+      .collect();
+    let total: u64 = present.iter().map(|(_, size)| size).sum();
+    println!("\n## {} ({}, {})", list, t!("inspect.files", count = present.len()), format_size(total));
+    for (file, size) in present {
+      println!("  {:>10}  {}", format_size(size), file);
+    }
+  }
+
+  let unlisted = zip.unlisted(&boot_json);
+  if !unlisted.is_empty() {
+    println!("\n## {}", t!("inspect.unlisted"));
+    for file in unlisted {
+      println!("  {:>10}  {}", format_size(zip.files[file].len() as u64), file);
+    }
+  }
+  let missing = zip.missing(&boot_json);
+  if !missing.is_empty() {
+    println!("\n## {}", t!("inspect.missing"));
+    for (list, file) in missing {
+      println!("  {}  ({})", file, list);
+    }
+  }
+  Ok(())
+}
+
+/// 解析 twee 文件中的所有段落: (段落名稱, 內容)
+/// * 段落以 `:: 名稱 [標籤] {元數據}` 開始
+pub(crate) fn parse_passages(twee: &str) -> Vec<(String, String)> {
+  let mut passages: Vec<(String, String)> = Vec::new();
+  for line in twee.lines() {
+    if let Some(header) = line.strip_prefix("::") {
+      let mut name = String::new();
+      let mut chars = header.chars();
+      while let Some(c) = chars.next() {
+        match c {
+          '\\' => name.extend(chars.next()),
+          '[' | '{' => break,
+          c => name.push(c),
+        }
+      }
+      passages.push((name.trim().to_string(), String::new()));
+    } else if let Some((_, body)) = passages.last_mut() {
+      body.push_str(line);
+      body.push('\n');
+    }
+  }
+  for (_, body) in passages.iter_mut() {
+    body.truncate(body.trim_end().len());
+  }
+  passages
+}
+
+/// zip 中所有 twee 段落: 段落名稱 -> (文件, 內容)
+fn collect_passages(zip: &ModZip) -> BTreeMap<String, (String, String)> {
+  let mut passages = BTreeMap::new();
+  for (file, content) in zip.files.iter().filter(|(file, _)| file.ends_with(".twee")) {
+    for (name, body) in parse_passages(&String::from_utf8_lossy(content)) {
+      passages.insert(name, (file.clone(), body));
+    }
+  }
+  passages
+}
+
+/// 比較兩個 JSON 值並輸出差異
+fn diff_json(key: &str, old: Option<&serde_json::Value>, new: Option<&serde_json::Value>) {
+  use serde_json::Value;
+  match (old, new) {
+    (Some(Value::Array(old)), Some(Value::Array(new))) => {
+      for item in old.iter().filter(|item| !new.contains(item)) {
+        println!("  {}: - {}", key, item);
+      }
+      for item in new.iter().filter(|item| !old.contains(item)) {
+        println!("  {}: + {}", key, item);
+      }
+    }
+    (old, new) => {
+      let show = |v: Option<&Value>| v.map_or("-".to_string(), |v| v.to_string());
+      println!("  {}: {} -> {}", key, show(old), show(new));
+    }
+  }
+}
+
+/// 比較同一個mod的兩個 zip
+/// * 新增, 刪除和修改的文件
+/// * boot.json 字段的變化
+/// * twee 段落的變化
+pub(crate) fn diff(old_path: &Path, new_path: &Path) -> Result<(), Error> {
+  let old = ModZip::open(old_path)?;
+  let new = ModZip::open(new_path)?;
+  println!("--- {}\n+++ {}", old_path.display(), new_path.display());
+
+  println!("\n## {}", t!("inspect.diff_files"));
+  let names: BTreeSet<&String> = old.files.keys().chain(new.files.keys()).collect();
+  for name in names {
+    match (old.files.get(name), new.files.get(name)) {
+      (None, Some(content)) => println!("  A {} ({})", name, format_size(content.len() as u64)),
+      (Some(content), None) => println!("  D {} ({})", name, format_size(content.len() as u64)),
+      (Some(a), Some(b)) if a != b => {
+        println!(
+          "  M {} ({} -> {})",
+          name,
+          format_size(a.len() as u64),
+          format_size(b.len() as u64)
+        );
+      }
+      _ => (),
+    }
+  }
+
+  println!("\n## boot.json");
+  let parse = |zip: &ModZip| -> serde_json::Map<String, serde_json::Value> {
+    zip.files
+      .get("boot.json")
+      .and_then(|content| serde_json::from_slice(content).ok())
+      .unwrap_or_default()
+  };
+  let (old_boot, new_boot) = (parse(&old), parse(&new));
+  let keys: BTreeSet<&String> = old_boot.keys().chain(new_boot.keys()).collect();
+  for key in keys {
+    if old_boot.get(key) != new_boot.get(key) {
+      diff_json(key, old_boot.get(key), new_boot.get(key));
+    }
+  }
+
+  println!("\n## {}", t!("inspect.diff_passages"));
+  let (old_passages, new_passages) = (collect_passages(&old), collect_passages(&new));
+  let names: BTreeSet<&String> = old_passages.keys().chain(new_passages.keys()).collect();
+  for name in names {
+    match (old_passages.get(name), new_passages.get(name)) {
+      (None, Some((file, _))) => println!("  A {} ({})", name, file),
+      (Some((file, _)), None) => println!("  D {} ({})", name, file),
+      (Some((_, a)), Some((file, b))) if a != b => {
+        let (a, b): (Vec<&str>, Vec<&str>) = (a.lines().collect(), b.lines().collect());
+        let removed = a.iter().filter(|line| !b.contains(line)).count();
+        let added = b.iter().filter(|line| !a.contains(line)).count();
+        println!("  M {} ({}, +{} -{})", name, file, added, removed);
+      }
+      _ => (),
+    }
+  }
+  Ok(())
+}
@@ -0,0 +1,75 @@
+//! 檢查文件路徑的大小寫和可移植性
+//!
+//! Windows 上 `img/Foo.png` 和 `img/foo.png` 是同一個文件,
+//! 但 ModLoader 讀取 zip 時區分大小寫, 所以在本地正常的mod在玩家那裡可能會出錯
+
+use std::collections::{ BTreeMap, HashMap };
+use std::path::Path;
+use unicode_normalization::is_nfc;
+use walkdir::WalkDir;
+use crate::boot_json::BootJson;
+use crate::diagnostics::{ Warning, warning };
+use crate::template::RESERVED_NAMES;
+
+/// 檢查一個路徑的每一段
+/// * Windows 保留名稱(如 `CON`, `NUL`)
+/// * 結尾的 `.` 或空格
+/// * 非 NFC 的 Unicode
+pub(crate) fn lint_path(path: &str) -> Vec<Warning> {
+  let mut problems = Vec::new();
+  for part in path.split('/') {
+    let stem = part.split('.').next().unwrap_or_default().trim_end();
+    if RESERVED_NAMES.iter().any(|r| r.eq_ignore_ascii_case(stem)) {
+      problems.push(warning!("lint.reserved", path = path, part = part).with_file(path));
+    }
+    if part.ends_with(['.', ' ']) {
+      problems.push(warning!("lint.trailing", path = path, part = part).with_file(path));
+    }
+  }
+  if !is_nfc(path) {
+    problems.push(warning!("lint.not_nfc", path = path).with_file(path));
+  }
+  problems
+}
+
+/// 檢查 boot.json 列表中的路徑
+/// * `dir` - mod文件夾
+/// * `boot_json` - 已更新列表的 boot.json
+/// # 返回
+/// * 所有問題
+pub(crate) fn lint_mod(dir: &Path, boot_json: &BootJson) -> Vec<Warning> {
+  let mut problems = Vec::new();
+
+  // 磁盤上的真實路徑: 小寫 -> 路徑(區分大小寫的文件系統上可能有多個)
+  let mut on_disk: HashMap<String, Vec<String>> = HashMap::new();
+  for entry in WalkDir::new(dir).sort_by_file_name().into_iter().flatten() {
+    if entry.file_type().is_file() && let Ok(rel) = entry.path().strip_prefix(dir) {
+      let rel = rel.to_string_lossy().replace('\\', "/");
+      on_disk.entry(rel.to_lowercase()).or_default().push(rel);
+    }
+  }
+
+  // 列表中的路徑: 小寫 -> 路徑
+  let mut listed: BTreeMap<String, Vec<&str>> = BTreeMap::new();
+  for (_, files) in boot_json.file_lists() {
+    for file in files {
+      let entries = listed.entry(file.to_lowercase()).or_default();
+      if !entries.contains(&file.as_str()) {
+        entries.push(file);
+      }
+    }
+  }
+
+  for (lower, files) in &listed {
+    if files.len() > 1 {
+      problems.push(warning!("lint.case_collision", paths = files.join(", ")).with_file(files[0]));
+    }
+    for file in files {
+      if let Some(disk) = on_disk.get(lower) && !disk.iter().any(|d| d == file) {
+        problems.push(warning!("lint.case_mismatch", path = file, disk = disk.join(", ")).with_file(*file));
+      }
+      problems.extend(lint_path(file));
+    }
+  }
+  problems
+}
@@ -4,17 +4,51 @@ mod tests;
 // 引入模塊和依賴
 pub mod boot_json;
 use boot_json::BootJson;
+pub mod build_dir;
+#[cfg(feature = "bundle")]
+pub mod bundle;
 pub mod cofg;
-use cofg::Cofg;
+use cofg::{ Cofg, Command };
+pub mod collision;
 pub mod r#const;
+pub mod defines;
+pub mod diagnostics;
+use diagnostics::{ Diagnostic, ModDiagnostics, Severity, Warning };
+pub mod error;
+use error::{ Error, WithPath };
 pub mod fs_utils;
+pub mod hooks;
+pub mod import;
+pub mod inspect;
+pub mod lint;
+#[cfg(feature = "minify")]
+pub mod minify;
+pub mod order;
+use order::OrderState;
+pub mod report;
+use report::{ ModReport, Report, TscReport };
+pub mod scaffold;
+#[cfg(feature = "script")]
+pub mod script;
+#[cfg(any(feature = "builtin-ts", feature = "bundle", feature = "defines", feature = "minify"))]
+pub mod sourcemap;
+pub mod stage;
+use stage::{ builtin, Stage };
+pub mod template;
+#[cfg(feature = "builtin-ts")]
+pub mod transpile;
+pub mod tsc;
+pub mod typings;
+pub mod verify;
 
 use glob::glob;
 use log::{ debug, error, info, trace, warn };
 use rust_i18n::t;
 use zip_extensions::ZipWriterExtensions;
 use std::fs::{ self, remove_dir_all, remove_file, File };
-use std::path::Path;
+use std::collections::BTreeMap;
+use std::path::{ Path, PathBuf };
+use std::time::Instant;
 use walkdir::WalkDir;
 use zip::write::FileOptions;
 use zip::ZipWriter;
@@ -22,132 +56,535 @@ use zip::ZipWriter;
 // 設定i18n
 rust_i18n::i18n!();
 
+/// 一個正在構建的mod
+pub(crate) struct ModCtx {
+  /// mod源文件夾
+  pub src: PathBuf,
+  /// 臨時目錄中的文件夾
+  pub tmp: PathBuf,
+  /// 構建失敗的原因, 失敗後跳過後面的步驟
+  pub error: Option<Error>,
+  /// 構建報告中的信息
+  pub report: ModReport,
+  /// 列表的順序, 構建成功後保存
+  pub order: Option<OrderState>,
+}
+
+impl ModCtx {
+  /// 是否還沒有失敗
+  fn ok(&self) -> bool {
+    self.error.is_none()
+  }
+
+  /// 記錄失敗原因, 只保留第一個錯誤
+  fn fail(&mut self, e: Error) {
+    error!("    {}", e);
+    self.error.get_or_insert(e);
+  }
+
+  /// 輸出警告並記錄到報告中
+  /// * 警告中的文件是相對mod文件夾的路徑
+  fn warn(&mut self, mut w: Warning) {
+    warn!("        {}", w);
+    w.file = w.file.map(|file| self.src.join(file));
+    self.report.warnings.push(w);
+  }
+
+  /// 把臨時目錄中的路徑轉換為mod源文件夾中的路徑
+  fn source_path(&self, path: &Path) -> PathBuf {
+    match path.strip_prefix(&self.tmp) {
+      Ok(rel) => self.src.join(rel),
+      Err(_) => path.to_path_buf(),
+    }
+  }
+
+  /// 所有警告和錯誤
+  fn diagnostics(&self) -> ModDiagnostics {
+    let mut diagnostics: Vec<Diagnostic> = self.report.warnings
+      .iter()
+      .map(|w| Diagnostic {
+        severity: Severity::Warning,
+        key: w.key.to_string(),
+        message: w.message.clone(),
+        file: w.file.clone(),
+        line: None,
+        column: None,
+      })
+      .collect();
+    if let Some(tsc) = &self.report.tsc {
+      diagnostics.extend(
+        tsc.diagnostics.iter().filter(|d| d.category != "message").map(|d| Diagnostic {
+          severity: if d.is_error() { Severity::Error } else { Severity::Warning },
+          key: d.code.clone().unwrap_or("tsc".to_string()),
+          message: d.message.clone(),
+          file: d.file.clone(),
+          line: d.line,
+          column: d.column,
+        })
+      );
+    }
+    // tsc 的每個錯誤已經單獨列出
+    if let Some(e) = &self.error && !matches!(e, Error::Tsc { errors: 1.., .. }) {
+      let pos = e.position();
+      diagnostics.push(Diagnostic {
+        severity: Severity::Error,
+        key: e.key().to_string(),
+        message: e.to_string(),
+        file: Some(e.path().map_or(self.src.clone(), |path| self.source_path(path))),
+        line: pos.map(|(line, _)| line),
+        column: pos.map(|(_, column)| column),
+      });
+    }
+    ModDiagnostics {
+      name: self.report.name.clone().unwrap_or(self.src.display().to_string()),
+      time_ms: self.report.timings.values().sum(),
+      diagnostics,
+    }
+  }
+}
+
+/// 運行mod的一個步驟, 前後運行 hooks 中的命令, 並記錄用時
+/// * 命令或步驟失敗時記錄到mod中
+fn run_stage(
+  cofg: &Cofg,
+  ctx: &mut ModCtx,
+  stage: &str,
+  work: impl FnOnce(&mut ModCtx) -> Result<(), Error>
+) {
+  let start = Instant::now();
+  let result = run_hooks(cofg, ctx, stage, false)
+    .and_then(|_| work(ctx))
+    .and_then(|_| run_hooks(cofg, ctx, stage, true));
+  if let Err(e) = result {
+    ctx.fail(e);
+  }
+  ctx.report.time(stage, start);
+}
+
+/// 運行mod在步驟前(`post` 為 `false`)或後的命令, 遇到失敗的命令時停止
+fn run_hooks(cofg: &Cofg, ctx: &ModCtx, stage: &str, post: bool) -> Result<(), Error> {
+  let name = ctx.src.file_name().unwrap_or_default().to_string_lossy().to_string();
+  let commands = cofg.hooks_for(&name, stage, post);
+  if commands.is_empty() {
+    return Ok(());
+  }
+  let env = hook_env(cofg, ctx, stage, post);
+  for cmd in commands {
+    hooks::run(stage, cmd, &ctx.src, &env)?;
+  }
+  Ok(())
+}
+
+/// hooks 中的命令的環境變量
+/// * mod名稱, 版本和 zip 路徑來自臨時目錄中的 boot.json, 複製之前來自源文件夾; 無法讀取時為空
+fn hook_env(cofg: &Cofg, ctx: &ModCtx, stage: &str, post: bool) -> Vec<(&'static str, String)> {
+  let absolute = |path: &Path| std::path::absolute(path).unwrap_or(path.to_path_buf()).display().to_string();
+  let boot_json_path = if ctx.tmp.join("boot.json").exists() { ctx.tmp.join("boot.json") } else { ctx.src.join("boot.json") };
+  let boot_json = BootJson::new(&boot_json_path.to_string_lossy()).ok();
+  let zip = match (&ctx.report.zip, &boot_json) {
+    (Some(zip), _) => absolute(zip),
+    (None, Some(boot_json)) => zip_path(cofg, ctx, boot_json).map(|zip| absolute(&zip)).unwrap_or_default(),
+    (None, None) => String::new(),
+  };
+  vec![
+    ("MOD_DEV_STAGE", stage.to_string()),
+    ("MOD_DEV_HOOK", (if post { "post" } else { "pre" }).to_string()),
+    ("MOD_DEV_MOD_NAME", boot_json.as_ref().map(|b| b.name.clone()).unwrap_or_default()),
+    ("MOD_DEV_MOD_VERSION", boot_json.as_ref().and_then(|b| b.version.clone()).unwrap_or_default()),
+    ("MOD_DEV_SRC_DIR", absolute(&ctx.src)),
+    ("MOD_DEV_TMP_DIR", absolute(&ctx.tmp)),
+    ("MOD_DEV_ZIP", zip),
+    ("MOD_DEV_PROFILE", cofg.profile.clone().unwrap_or_default()),
+  ]
+}
+
 /// 主要處理 TypeScript 文件的函數
-fn process_ts_files(cofg: &Cofg) {
+fn process_ts_files(cofg: &Cofg, mods: &mut [ModCtx]) {
   info!("### {} ###", t!("ts.start"));
-  for entry in glob(&format!("{}/*/", cofg.path.tmp_path)).expect(&t!("filesystem.glob_failed")) {
-    match entry {
-      Err(_) => (),
-      Ok(path) => {
-        let mut has_ts_file = false;
-        for entry in glob(&format!("{}/**/*.ts", path.display())).expect(
-          &t!("filesystem.glob_failed")
-        ) {
-          match entry {
-            Err(_) => (),
-            Ok(ts_path) => {
-              if ts_path.ends_with(".d.ts") {
-                continue;
-              }
-              has_ts_file = true;
-            }
-          }
-        }
-        if has_ts_file {
-          // 調用 tsc 編譯 TypeScript 文件
-          let mut tsc_command = if cfg!(windows) {
-            std::process::Command::new("tsc.cmd")
-          } else {
-            std::process::Command::new("tsc")
-          };
-          let output = tsc_command
-            .arg("--project")
-            .arg(path.to_str().unwrap())
-            .output()
-            .expect(&t!("ts.tsc_failed"));
-
-          if !output.status.success() {
-            error!(
-              "    {}",
-              t!(
-                "ts.tsc_error",
-                msg = String::from_utf8_lossy(&output.stdout),
-                path = path.display()
-              )
-            );
-          } else {
-            info!("    {}", t!("ts.tsc_success", path = path.display()));
-          }
-        }
+  for ctx in mods.iter_mut().filter(|ctx| ctx.ok()) {
+    run_stage(cofg, ctx, "ts", |ctx| compile_mod(cofg, ctx));
+  }
+  info!("=== {} ===", t!("ts.end"));
+}
+
+/// 用 `compiler` 配置的命令編譯一個mod
+/// * 沒有 `.ts` 文件(`.d.ts` 除外)時跳過
+/// * 在構建目錄(`build_path`)中編譯, 成功後只把輸出的 JS 複製到臨時目錄
+/// * 命令, 參數和工作目錄中的 `{build_dir}`(構建目錄), `{mod_dir}`(臨時目錄), `{src_dir}`, `{name}`
+///   會替換為絕對路徑和mod文件夾名稱
+fn compile_mod(cofg: &Cofg, ctx: &mut ModCtx) -> Result<(), Error> {
+  let path = ctx.tmp.clone();
+  let has_ts_file = glob(&format!("{}/**/*.ts", path.display()))?
+    .flatten()
+    .any(|ts_path| !ts_path.to_string_lossy().ends_with(".d.ts"));
+  if !has_ts_file {
+    return Ok(());
+  }
+
+  let name = ctx.src.file_name().unwrap_or_default().to_string_lossy().to_string();
+  let compiler = cofg.compiler_for(&name);
+  // 在持久的構建目錄中編譯, 保留增量編譯的緩存
+  let build = build_dir::build_dir(&cofg.path.build_path, cofg.profile.as_deref(), &ctx.src);
+  let build_abs = std::path::absolute(&build).with_path(&build)?;
+  let mod_dir = std::path::absolute(&path).with_path(&path)?;
+  let src_dir = std::path::absolute(&ctx.src).with_path(&ctx.src)?;
+  let vars = [
+    ("{build_dir}", build_abs.display().to_string()),
+    ("{mod_dir}", mod_dir.display().to_string()),
+    ("{src_dir}", src_dir.display().to_string()),
+    ("{name}", name.clone()),
+  ];
+  let fill = |s: &str| vars.iter().fold(s.to_string(), |s, (k, v)| s.replace(k, v));
+
+  // tsc_args 是 tsc 的參數, 其他編譯器(如 esbuild)不能使用
+  let tsc_args = if compiler.accepts_tsc_args() {
+    cofg.tsc_args.as_slice()
+  } else {
+    if !cofg.tsc_args.is_empty() {
+      ctx.warn(diagnostics::warning!("ts.tsc_args_ignored", command = compiler.command, args = cofg.tsc_args.join(" ")));
+    }
+    &[]
+  };
+  let args: Vec<String> = compiler.args
+    .iter()
+    .map(|arg| fill(arg))
+    .chain(tsc_args.iter().cloned())
+    .collect();
+  build_dir::sync_sources(&ctx.src, &build, &args)?;
+  let cwd = match compiler.cwd.as_deref().map(fill) {
+    Some(cwd) => {
+      let cwd = PathBuf::from(cwd);
+      if !cwd.is_dir() {
+        return Err(Error::Io { path: cwd, source: std::io::ErrorKind::NotFound.into() });
       }
+      std::path::absolute(&cwd).with_path(&cwd)?
+    }
+    None => std::env::current_dir().with_path(".")?,
+  };
+  let (success, mut diagnostics) = if compiler.command == "builtin" {
+    compile_builtin(&args, &cwd)?
+  } else {
+    // Windows 上 npm 安裝的 tsc 是 tsc.cmd
+    let cmd = match compiler.command.as_str() {
+      "tsc" if cfg!(windows) => "tsc.cmd".to_string(),
+      command => fill(command),
+    };
+    let output = std::process::Command::new(&cmd)
+      .args(&args)
+      .current_dir(&cwd)
+      .output()
+      .map_err(|source| Error::TscSpawn { cmd, source })?;
+    let mut diagnostics = tsc::parse_output(&String::from_utf8_lossy(&output.stdout));
+    diagnostics.extend(tsc::parse_output(&String::from_utf8_lossy(&output.stderr)));
+    (output.status.success(), diagnostics)
+  };
+  // 失敗但沒有可以解析的錯誤時, 其他輸出就是失敗原因
+  let promote = !success && !diagnostics.iter().any(|d| d.is_error());
+  for d in diagnostics.iter_mut() {
+    // 輸出的路徑相對於工作目錄
+    d.file = d.file.as_deref().map(|file| {
+      let file = cwd.join(file);
+      match file.strip_prefix(&build_abs).or_else(|_| file.strip_prefix(&mod_dir)) {
+        Ok(rel) => ctx.src.join(rel),
+        Err(_) => ctx.source_path(&file),
+      }
+    });
+    if promote {
+      d.category = "error".to_string();
+    }
+    match d.category.as_str() {
+      "error" => error!("        {}", d),
+      "warning" => warn!("        {}", d),
+      _ => info!("        {}", d),
     }
   }
-  info!("=== {} ===", t!("ts.end"));
+  let errors = diagnostics.iter().filter(|d| d.is_error()).count();
+  ctx.report.tsc = Some(TscReport { success, errors, diagnostics });
+  if !success {
+    return Err(Error::Tsc { path: ctx.src.clone(), errors });
+  }
+  let synced = build_dir::sync_outputs(&ctx.src, &build, &path)?;
+  debug!("    {}", t!("ts.synced", count = synced.len(), path = build.display()));
+  info!("    {}", t!("ts.tsc_success", path = path.display()));
+  Ok(())
+}
+
+/// 使用內置編譯器編譯, 返回 (是否成功, 診斷信息)
+#[cfg(feature = "builtin-ts")]
+fn compile_builtin(args: &[String], cwd: &Path) -> Result<(bool, Vec<tsc::TscDiagnostic>), Error> {
+  let diagnostics = transpile::compile(args, cwd)?;
+  Ok((!diagnostics.iter().any(|d| d.is_error()), diagnostics))
+}
+
+/// 沒有啟用 `builtin-ts` 功能時不能使用內置編譯器
+#[cfg(not(feature = "builtin-ts"))]
+fn compile_builtin(_args: &[String], _cwd: &Path) -> Result<(bool, Vec<tsc::TscDiagnostic>), Error> {
+  Err(Error::Config(t!("ts.builtin_disabled").to_string()))
+}
+
+/// 替換所有mod中的構建常量
+fn inject_defines(cofg: &Cofg, mods: &mut [ModCtx]) {
+  info!("### {} ###", t!("defines.start"));
+  for ctx in mods.iter_mut().filter(|ctx| ctx.ok()) {
+    run_stage(cofg, ctx, "defines", |ctx| {
+      let name = ctx.src.file_name().unwrap_or_default().to_string_lossy().to_string();
+      let changed = defines::apply(&ctx.tmp, &cofg.defines_for(&name), &git_short_hash(&ctx.src))?;
+      for file in &changed {
+        debug!("        {}", file);
+      }
+      if !changed.is_empty() {
+        info!("    {}", t!("defines.done", path = ctx.src.display(), count = changed.len()));
+      }
+      Ok(())
+    });
+  }
+  info!("=== {} ===", t!("defines.end"));
+}
+
+/// 打包配置了 `bundle` 的mod
+fn bundle_mods(cofg: &Cofg, mods: &mut [ModCtx]) {
+  info!("### {} ###", t!("bundle.start"));
+  for ctx in mods.iter_mut().filter(|ctx| ctx.ok()) {
+    let name = ctx.src.file_name().unwrap_or_default().to_string_lossy().to_string();
+    let Some(bundle) = cofg.bundle_for(&name) else {
+      continue;
+    };
+    run_stage(cofg, ctx, "bundle", |ctx| bundle_mod(cofg, ctx, bundle));
+  }
+  info!("=== {} ===", t!("bundle.end"));
+}
+
+/// 打包一個mod, 並在 boot.json 的 scriptFileList 中用 bundle 替換模塊
+#[cfg(feature = "bundle")]
+fn bundle_mod(cofg: &Cofg, ctx: &mut ModCtx, bundle: &cofg::BundleCofg) -> Result<(), Error> {
+  let path = ctx.tmp.join("boot.json");
+  let mut boot_json = BootJson::new(&path.to_string_lossy())?;
+  let bundled = bundle::bundle_mod(&ctx.tmp, &boot_json.name, bundle, cofg.source_map)?;
+  boot_json.replace_scripts(&bundled.modules, &bundled.bundles);
+  fs::write(&path, serde_json::to_string_pretty(&boot_json)?).with_path(&path)
+}
+
+/// 沒有啟用 `bundle` 功能時不能打包
+#[cfg(not(feature = "bundle"))]
+fn bundle_mod(_cofg: &Cofg, _ctx: &mut ModCtx, _bundle: &cofg::BundleCofg) -> Result<(), Error> {
+  Err(Error::Config(t!("bundle.disabled").to_string()))
 }
 
 /// 主要處理 boot.json 文件的函數
 /// 掃描、解析和更新所有mod文件夾中的boot.json文件
-fn process_boot_json_files(cofg: &Cofg) {
+fn process_boot_json_files(cofg: &Cofg, mods: &mut [ModCtx]) {
   info!("### {} ###", t!("boot_json.start"));
 
-  for entry in glob(&format!("{}/*/boot.json", cofg.path.tmp_path)).expect(
-    &t!("filesystem.glob_failed")
-  ) {
-    match entry {
-      Ok(path) => {
-        let cwd = path.parent().unwrap();
-        info!("    {}", t!("boot_json.processing", path = path.display()));
-
-        match BootJson::new(path.to_str().unwrap()) {
-          Ok(mut boot_json) => {
-            if let Err(e) = boot_json.update_file_lists(cwd) {
-              warn!("{}", t!("filesystem.update_failed", msg = format!("{:?}", e)));
-              continue;
-            }
-
-            // 保存更新後的boot.json
-            match serde_json::to_string_pretty(&boot_json) {
-              Ok(json_string) => {
-                if let Err(e) = std::fs::write(&path, json_string) {
-                  warn!("{}", t!("filesystem.write_file_failed", path = path.display(), e = e));
-                }
-              }
-              Err(e) => {
-                warn!("{}", t!("json.serialize_error", msg = format!("{}", e)));
-              }
-            }
-          }
-          Err(e) => warn!("{}", t!("filesystem.read_file_failed", path = path.display(), e = e)),
-        }
-      }
-      Err(e) => warn!("{}", t!("boot_json.read_error", e = format!("{:?}", e))),
-    }
+  for ctx in mods.iter_mut().filter(|ctx| ctx.ok()) {
+    run_stage(cofg, ctx, "boot_json", |ctx| update_boot_json(cofg, ctx));
   }
   info!("=== {} ===", t!("boot_json.end"));
 }
 
+/// 更新一個mod的 boot.json 文件列表並寫回
+fn update_boot_json(cofg: &Cofg, ctx: &mut ModCtx) -> Result<(), Error> {
+  let cwd = ctx.tmp.as_path();
+  let path = cwd.join("boot.json");
+  info!("    {}", t!("boot_json.processing", path = path.display()));
+
+  let mut boot_json = BootJson::new(&path.to_string_lossy())?;
+  let listed: Vec<String> = boot_json
+    .file_lists()
+    .iter()
+    .flat_map(|(_, files)| files.iter().cloned())
+    .collect();
+  boot_json.update_file_lists(cwd, cofg.source_map)?;
+
+  // 排序並與上次構建的順序比較
+  let name = ctx.src.file_name().unwrap_or_default().to_string_lossy().to_string();
+  let order = cofg.order_for(&name);
+  let state = build_dir::order_state(&cofg.path.build_path, cofg.profile.as_deref(), &ctx.src);
+  let mut lists = Vec::new();
+  for (list, files) in boot_json.ordered_lists_mut() {
+    order::sort_list(files, order, &listed)?;
+    lists.push((list, files.clone()));
+  }
+  let lists: Vec<(&str, &[String])> = lists.iter().map(|(list, files)| (*list, files.as_slice())).collect();
+  let (moved, order_state) = order::track(&state, &lists);
+  ctx.order = Some(order_state);
+
+  ctx.report.name = Some(boot_json.name.clone());
+  for (list, files) in boot_json.file_lists() {
+    ctx.report.lists.insert(list, files.len());
+    ctx.report.added.extend(files.iter().filter(|file| !listed.contains(file)).cloned());
+  }
+  for problem in moved.into_iter().chain(lint::lint_mod(cwd, &boot_json)) {
+    ctx.warn(problem);
+  }
+
+  // 保存更新後的boot.json
+  fs::write(&path, serde_json::to_string_pretty(&boot_json)?).with_path(&path)
+}
+
+/// 壓縮所有mod的腳本和樣式
+#[cfg(feature = "minify")]
+fn minify_mods(cofg: &Cofg, mods: &mut [ModCtx]) {
+  info!("### {} ###", t!("minify.start"));
+  for ctx in mods.iter_mut().filter(|ctx| ctx.ok()) {
+    run_stage(cofg, ctx, "minify", |ctx| minify_mod(cofg, ctx));
+  }
+  info!("=== {} ===", t!("minify.end"));
+}
+
+/// 沒有啟用 `minify` 功能時不壓縮
+#[cfg(not(feature = "minify"))]
+fn minify_mods(_cofg: &Cofg, mods: &mut [ModCtx]) {
+  for ctx in mods.iter_mut().filter(|ctx| ctx.ok()) {
+    ctx.warn(diagnostics::warning!("minify.disabled"));
+  }
+}
+
+/// 壓縮一個mod的 scriptFileList(包括 early/preload 列表) 中的 `.js` 和 styleFileList 中的 `.css`
+#[cfg(feature = "minify")]
+fn minify_mod(cofg: &Cofg, ctx: &mut ModCtx) -> Result<(), Error> {
+  let boot_json = BootJson::new(&ctx.tmp.join("boot.json").to_string_lossy())?;
+  let files: Vec<&str> = boot_json
+    .file_lists()
+    .into_iter()
+    .filter_map(|(list, files)| match list {
+      "styleFileList" => Some((".css", files)),
+      list if list.starts_with("scriptFileList") => Some((".js", files)),
+      _ => None,
+    })
+    .flat_map(|(ext, files)| files.iter().filter(move |file| file.ends_with(ext)).map(String::as_str))
+    .collect();
+  let result = minify::minify_mod(&ctx.tmp, &files, cofg.source_map)?;
+  for (file, line, column, msg) in &result.skipped {
+    ctx.warn(
+      diagnostics::warning!("minify.skipped", path = file, line = line, column = column, msg = msg).with_file(file)
+    );
+  }
+  if result.files == 0 {
+    return Ok(());
+  }
+  let saved = result.before.saturating_sub(result.after);
+  info!(
+    "    {}",
+    t!(
+      "minify.done",
+      path = ctx.src.display(),
+      files = result.files,
+      before = inspect::format_size(result.before),
+      after = inspect::format_size(result.after),
+      saved = inspect::format_size(saved),
+      percent = format!("{:.1}", (saved as f64) * 100.0 / (result.before.max(1) as f64))
+    )
+  );
+  ctx.report.minify = Some(report::MinifyReport { files: result.files, before: result.before, after: result.after });
+  Ok(())
+}
+
 /// 壓縮所有的mod文件夾成zip格式
 /// 將處理完的mod打包成最終發布格式
-fn compress_mod_folders(cofg: &Cofg) {
+/// # 錯誤處理
+/// - 有名稱或輸出路徑衝突且沒有 `allow_collisions` 時不打包任何mod
+fn compress_mod_folders(cofg: &Cofg, mods: &mut [ModCtx]) -> Result<(), Error> {
   info!("### {} ###", t!("compress.start"));
 
-  let results_dir = Path::new(&cofg.path.results_path);
-
-  for entry in glob(&format!("{}/*/", cofg.path.tmp_path))
-    .expect("Failed to read glob pattern")
-    .flatten() {
-    let src_dir = entry.as_path();
-    let boot_json_path = src_dir.join("boot.json");
-
-    match BootJson::new(boot_json_path.to_str().unwrap()) {
-      Ok(boot_json) => {
-        let zip_path = results_dir.join(
-          cofg.file_name
-            .replace("{name}", boot_json.name.as_str())
-            .replace("{ver}", boot_json.version.as_deref().unwrap_or("1.0.0"))
-        );
-
-        match create_mod_zip(src_dir, &zip_path, boot_json) {
-          Ok(_) => info!("    {}", t!("compress.done", path = src_dir.display())),
-          Err(e) =>
-            warn!("{}", t!("filesystem.compression_failed", path = src_dir.display(), e = e)),
+  // 先運行步驟前的命令, 算出所有 mod 的輸出路徑, 再檢查衝突
+  let mut packages = Vec::new();
+  for ctx in mods.iter_mut().filter(|ctx| ctx.ok()) {
+    let start = Instant::now();
+    let package = run_hooks(cofg, ctx, "compress", false)
+      .and_then(|_| BootJson::new(&ctx.tmp.join("boot.json").to_string_lossy()))
+      .and_then(|boot_json| Ok((zip_path(cofg, ctx, &boot_json)?, boot_json)));
+    ctx.report.time("compress", start);
+    match package {
+      Ok((zip_path, boot_json)) => packages.push((ctx, zip_path, boot_json)),
+      Err(e) => ctx.fail(e),
+    }
+  }
+
+  let collisions = collision::find_collisions(
+    packages
+      .iter()
+      .map(|(ctx, zip_path, boot_json)| {
+        (boot_json.name.as_str(), zip_path.as_path(), ctx.src.as_path())
+      })
+  );
+  if cofg.allow_collisions {
+    // 警告記錄到後出現的mod中
+    for collision in &collisions {
+      if let Some((ctx, _, _)) = packages.iter_mut().find(|(ctx, _, _)| ctx.src == collision.second()) {
+        ctx.warn(collision.to_warning());
+      }
+    }
+  } else if !collisions.is_empty() {
+    for collision in &collisions {
+      error!("    {}", collision);
+    }
+    return Err(Error::Collision(collisions.len()));
+  }
+
+  for (ctx, zip_path, boot_json) in packages {
+    let start = Instant::now();
+    let result = create_mod_zip(&ctx.tmp, &zip_path, boot_json, cofg.compression_level).and_then(
+      |pruned| {
+        ctx.report.pruned = pruned;
+        ctx.report.zip_size = Some(fs::metadata(&zip_path).with_path(&zip_path)?.len());
+        ctx.report.zip = Some(zip_path.clone());
+        verify::verify_mod_zip(&zip_path)
+      }
+    );
+    match result {
+      Ok(problems) if problems.is_empty() => {
+        info!("    {}", t!("compress.done", path = ctx.tmp.display()));
+        if let Err(e) = run_hooks(cofg, ctx, "compress", true) {
+          ctx.fail(e);
+          // 構建失敗的mod不保留壓縮包
+          match fs::remove_file(&zip_path).with_path(&zip_path) {
+            Ok(()) => {
+              ctx.report.zip = None;
+              ctx.report.zip_size = None;
+            }
+            Err(e) => ctx.fail(e),
+          }
         }
       }
-      Err(e) => warn!("{}", t!("boot_json.read_error", e = e)),
+      Ok(problems) => ctx.fail(Error::Verify { path: zip_path, problems }),
+      Err(e) => ctx.fail(e),
     }
+    ctx.report.time("compress", start);
   }
 
   info!("=== {} ===", t!("compress.end"));
+  Ok(())
+}
+
+/// 按 `file_name` 模板算出mod的 zip 路徑
+fn zip_path(cofg: &Cofg, ctx: &ModCtx, boot_json: &BootJson) -> Result<PathBuf, Error> {
+  let file_name = template::render(&cofg.file_name, |key| {
+    Ok(match key {
+      "name" => boot_json.name.clone(),
+      "ver" => boot_json.version.clone().unwrap_or("1.0.0".to_string()),
+      "hash" => git_short_hash(&ctx.src),
+      "profile" => cofg.profile.clone().unwrap_or("default".to_string()),
+      "nick" => boot_json.nick_name().unwrap_or(&boot_json.name).to_string(),
+      "game" => boot_json.game_version().unwrap_or("unknown").to_string(),
+      key => return Err(template::unknown(key)),
+    })
+  })?;
+  Ok(Path::new(&cofg.path.results_path).join(file_name))
+}
+
+/// 取得目錄所在 git 倉庫的短哈希
+/// * 不是 git 倉庫或沒有 git 時返回 `unknown`
+fn git_short_hash(dir: &Path) -> String {
+  std::process::Command
+    ::new("git")
+    .arg("-C")
+    .arg(dir)
+    .args(["rev-parse", "--short", "HEAD"])
+    .output()
+    .ok()
+    .filter(|output| output.status.success())
+    .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+    .unwrap_or("unknown".to_string())
 }
 
 /// 壓縮指定目錄到zip文件
@@ -156,90 +593,254 @@ fn compress_mod_folders(cofg: &Cofg) {
 /// * `src_dir` - 源目錄
 /// * `zip_path` - 目標zip文件路徑
 /// * `boot_json` - boot.json配置
+/// * `compression_level` - 壓縮等級, `None` 為默認
+/// # 返回
+/// * 不在列表中而被刪除的文件
 fn create_mod_zip(
   src_dir: &Path,
   zip_path: &Path,
-  boot_json: BootJson
-) -> Result<(), Box<dyn std::error::Error>> {
-  let file = File::create(zip_path)?;
+  boot_json: BootJson,
+  compression_level: Option<i64>
+) -> Result<Vec<String>, Error> {
+  let mut pruned = Vec::new();
+  let file = File::create(zip_path).with_path(zip_path)?;
   let zip = ZipWriter::new(file);
   let options: FileOptions<()> = FileOptions::default()
     .compression_method(zip::CompressionMethod::Deflated)
     .unix_permissions(0o755)
-    .compression_level(None);
+    .compression_level(compression_level);
 
   for entry in WalkDir::new(src_dir).sort_by_file_name() {
-    match entry {
-      Err(e) => warn!("{}", e),
-      Ok(entry) => {
-        let path = entry.path();
-        let name = path.strip_prefix(src_dir).unwrap();
-        if path.is_file() && !boot_json.in_list(name.to_str().unwrap()) {
-          remove_file(path)?;
-          trace!("    f:{}", name.display());
-        }
-        if path.is_dir() && fs_utils::check_empty_dirs(&path.to_path_buf()) {
-          remove_dir_all(path)?;
-          trace!("    f:{}", name.display());
-        }
-      }
+    let entry = entry?;
+    let path = entry.path();
+    let name = boot_json::process_file_path(path, src_dir)?;
+    if path.is_file() && !boot_json.in_list(&name) {
+      remove_file(path).with_path(path)?;
+      trace!("    f:{}", name);
+      pruned.push(name.replace('\\', "/"));
+    }
+    if path.is_dir() && fs_utils::check_empty_dirs(&path.to_path_buf()) {
+      remove_dir_all(path).with_path(path)?;
+      trace!("    f:{}", name);
     }
   }
 
-  zip.create_from_directory_with_options(&src_dir.to_path_buf(), |_| options)?;
+  zip.create_from_directory_with_options(&src_dir.to_path_buf(), |_| options).with_path(zip_path)?;
 
-  Ok(())
+  Ok(pruned)
 }
 
 /// 將mods目錄下的所有內容複製到臨時目錄
 /// 用於後續處理和打包
-fn copy_to_tmp(cofg: &Cofg) {
-  let mods_dir = Path::new(cofg.path.mods_path.as_str());
-  let tmp_dir = Path::new(cofg.path.tmp_path.as_str());
+/// * 返回 所有mod, 按臨時目錄中的文件夾名稱排序
+fn copy_to_tmp(cofg: &Cofg) -> Vec<ModCtx> {
+  // glob 返回的路徑不含 `.`, 這裡也去掉, 否則之後無法去掉前綴
+  let tmp_dir: PathBuf = Path::new(cofg.path.tmp_path.as_str())
+    .components()
+    .filter(|c| *c != std::path::Component::CurDir)
+    .collect();
+  let mut mods: BTreeMap<String, ModCtx> = BTreeMap::new();
 
   info!("### {} ###", t!("copy_to_tmp.start"));
 
-  for entry in fs::read_dir(mods_dir).expect("Failed to read mods directory") {
-    let entry = entry.expect("Failed to read entry");
-    let path = entry.path();
-
-    if path.is_dir() {
-      if Path::new(&format!("{}/.ig", path.display())).exists() {
-        info!(
-          "    {}",
-          t!("copy_to_tmp.skip", path = path.display().to_string().replace("/", "\\"))
-        );
+  for mods_dir in cofg.path.mods_path.roots() {
+    let entries = match fs::read_dir(&mods_dir) {
+      Ok(entries) => entries,
+      Err(e) => {
+        warn!("{}", t!("filesystem.read_dir_failed", path = mods_dir.display(), e = e));
         continue;
       }
-      if !Path::new(&format!("{}/boot.json", path.display())).exists() {
-        info!(
-          "    {}",
-          t!("copy_to_tmp.skip", path = path.display().to_string().replace("/", "\\"))
-        );
-        continue;
-      }
-      let dest = tmp_dir.join(path.file_name().unwrap());
-      if let Err(e) = fs_utils::copy_dir_all(&path, &dest) {
-        warn!(
-          "    {}",
-          t!("filesystem.copy_dir_failed", path = path.display(), msg = format!("{}", e))
-        );
+    };
+    for entry in entries.flatten() {
+      let path = entry.path();
+
+      if path.is_dir() {
+        if Path::new(&format!("{}/.ig", path.display())).exists() {
+          info!(
+            "    {}",
+            t!("copy_to_tmp.skip", path = path.display().to_string().replace("/", "\\"))
+          );
+          continue;
+        }
+        if !Path::new(&format!("{}/boot.json", path.display())).exists() {
+          info!(
+            "    {}",
+            t!("copy_to_tmp.skip", path = path.display().to_string().replace("/", "\\"))
+          );
+          continue;
+        }
+        // 不同根目錄下的同名文件夾, 用 `name~2` 之類的名稱區分
+        let dir_name = entry.file_name().to_string_lossy().to_string();
+        let mut tmp_name = dir_name.clone();
+        let mut n = 1;
+        let mut duplicate = None;
+        while let Some(other) = mods.get(&tmp_name) {
+          if n == 1 {
+            duplicate = Some(
+              diagnostics::warning!(
+                "copy_to_tmp.duplicate",
+                name = dir_name,
+                first = other.src.display(),
+                second = path.display()
+              )
+            );
+          }
+          n += 1;
+          tmp_name = format!("{}~{}", dir_name, n);
+        }
+        let mut ctx = ModCtx {
+          tmp: tmp_dir.join(&tmp_name),
+          src: path,
+          error: None,
+          report: ModReport::default(),
+          order: None,
+        };
+        if let Some(w) = duplicate {
+          ctx.warn(w);
+        }
+        run_stage(cofg, &mut ctx, "copy", |ctx| {
+          fs_utils::copy_dir_all(&ctx.src, &ctx.tmp).with_path(&ctx.src)?;
+          info!(
+            "    {}",
+            t!("copy.done", path = ctx.src.display().to_string().replace("/", "\\"))
+          );
+          Ok(())
+        });
+        mods.insert(tmp_name, ctx);
       }
-      info!("    {}", t!("copy.done", path = path.display().to_string().replace("/", "\\")));
     }
   }
   info!("=== {} ===", t!("copy_to_tmp.done"));
+  mods.into_values().collect()
+}
+
+/// 內置的構建步驟, 按運行順序
+fn builtin_stages() -> Vec<Box<dyn Stage>> {
+  vec![
+    // 複製文件到臨時目錄
+    builtin(
+      "copy",
+      |_| true,
+      |cofg, mods| {
+        *mods = copy_to_tmp(cofg);
+        Ok(())
+      }
+    ),
+    // 處理 TypeScript 文件
+    builtin(
+      "ts",
+      |cofg| cofg.ts_process,
+      |cofg, mods| {
+        process_ts_files(cofg, mods);
+        Ok(())
+      }
+    ),
+    // 替換構建常量
+    builtin(
+      "defines",
+      |_| true,
+      |cofg, mods| {
+        inject_defines(cofg, mods);
+        Ok(())
+      }
+    ),
+    // 打包 ES 模塊
+    builtin(
+      "bundle",
+      |cofg| cofg.mods.values().any(|m| m.bundle.is_some()),
+      |cofg, mods| {
+        bundle_mods(cofg, mods);
+        Ok(())
+      }
+    ),
+    // 處理 boot.json 文件
+    builtin(
+      "boot_json",
+      |_| true,
+      |cofg, mods| {
+        process_boot_json_files(cofg, mods);
+        Ok(())
+      }
+    ),
+    // 壓縮 JS/CSS
+    builtin(
+      "minify",
+      |cofg| cofg.minify,
+      |cofg, mods| {
+        minify_mods(cofg, mods);
+        Ok(())
+      }
+    ),
+    // 壓縮打包 mod 文件
+    builtin("compress", |_| true, |cofg, mods| compress_mod_folders(cofg, mods))
+  ]
+}
+
+/// 執行子命令
+fn run_command(cofg: &Cofg, command: Command) -> Result<(), Error> {
+  match command {
+    Command::New { list: true, .. } => {
+      for (name, desc) in scaffold::list_templates(cofg) {
+        println!("{name}\t{desc}");
+      }
+    }
+    Command::New { name, template, version, root, .. } => {
+      scaffold::new_mod(cofg, &name.unwrap_or_default(), &version, &template, root.as_deref())?;
+    }
+    Command::Init { from_dir, name, version, force } => {
+      scaffold::init_from_dir(cofg, &from_dir, name, version, force)?;
+    }
+    Command::Import { zip, root, sources } => {
+      import::import_mod(cofg, &zip, root.as_deref(), sources)?;
+    }
+    Command::Inspect { zip } => inspect::inspect(&zip)?,
+    Command::Diff { old, new } => inspect::diff(&old, &new)?,
+    Command::Types { dirs } => typings::run(cofg, dirs)?,
+  }
+  Ok(())
+}
+
+/// 輸出警告和錯誤的數量, 開啟 `deny_warnings` 時有警告則構建失敗
+/// * `exit_code` - 構建錯誤或失敗的mod的退出碼
+/// # 返回
+/// * 最終的退出碼
+fn summarize(deny_warnings: bool, exit_code: Option<i32>, warnings: usize, errors: usize) -> Option<i32> {
+  info!("{}", t!("build.summary", warnings = warnings, errors = errors));
+  if deny_warnings && warnings > 0 && exit_code.is_none() {
+    let e = Error::DeniedWarnings(warnings);
+    error!("{}", e);
+    return Some(e.exit_code());
+  }
+  exit_code
 }
 
 /// 主函數
+/// * 退出碼見 [`Error::exit_code`], 有多個mod失敗時使用第一個的
 fn main() {
-  // 設置 panic 處理
+  // 設置 panic 處理: release 構建中 human_panic 把崩潰報告寫入文件, 方便用戶反饋;
+  // 之後以固定的退出碼退出, 和 [`Error::exit_code`] 的錯誤區分開
   human_panic::setup_panic!();
+  let panic_hook = std::panic::take_hook();
+  std::panic::set_hook(
+    Box::new(move |info| {
+      panic_hook(info);
+      std::process::exit(error::PANIC_EXIT_CODE);
+    })
+  );
+  let start = Instant::now();
 
-  // 初始化配置
-  let cofg = Cofg::new();
-  cofg.init();
-  cofg.write_file();
+  // 初始化配置(日誌系統初始化之前只能直接輸出到 stderr)
+  let mut cofg = match Cofg::new() {
+    Ok(cofg) => cofg,
+    Err(e) => {
+      eprintln!("{}", e);
+      std::process::exit(e.exit_code());
+    }
+  };
+  if let Err(e) = cofg.init() {
+    error!("{}", e);
+    std::process::exit(e.exit_code());
+  }
 
   // 調試模式下打印配置信息
   if cfg!(debug_assertions) {
@@ -255,22 +856,113 @@ fn main() {
     println!("stdout");
   }
 
-  // 複製文件到臨時目錄
-  copy_to_tmp(&cofg);
+  // 執行子命令
+  if let Some(command) = cofg.command.take() {
+    if let Err(e) = run_command(&cofg, command) {
+      error!("{}", e);
+      std::process::exit(e.exit_code());
+    }
+    return;
+  }
+  if let Err(e) = cofg.prepare_dirs() {
+    error!("{}", e);
+    std::process::exit(e.exit_code());
+  }
+
+  let pipeline = match stage::pipeline(&cofg, builtin_stages()) {
+    Ok(pipeline) => pipeline,
+    Err(e) => {
+      error!("{}", e);
+      std::process::exit(e.exit_code());
+    }
+  };
 
-  // 如果需要處理 TypeScript 文件
-  if cofg.ts_process {
-    process_ts_files(&cofg);
+  // 按順序運行所有步驟, 出現需要中止構建的錯誤時停止
+  let mut mods = Vec::new();
+  let mut build_error = None;
+  for stage in pipeline.iter().filter(|stage| stage.enabled(&cofg)) {
+    if let Err(e) = stage.run(&cofg, &mut mods) {
+      error!("{}", e);
+      build_error = Some(e);
+      break;
+    }
   }
-  // 處理 boot.json 文件
-  process_boot_json_files(&cofg);
+  let mut exit_code = build_error.as_ref().map(Error::exit_code);
 
-  // 壓縮打包 mod 文件
-  compress_mod_folders(&cofg);
+  // 保存構建成功的mod的列表順序
+  if build_error.is_none() {
+    for ctx in mods.iter_mut().filter(|ctx| ctx.ok()) {
+      if let Some(state) = ctx.order.take() && let Err(e) = state.save() {
+        ctx.fail(e);
+      }
+    }
+  }
+
+  // 列出所有失敗的mod
+  let failed: Vec<&ModCtx> = mods.iter().filter(|ctx| !ctx.ok()).collect();
+  if !failed.is_empty() {
+    error!("{}", t!("build.failed", count = failed.len(), total = mods.len()));
+    for ctx in &failed {
+      error!("    {}", ctx.src.display());
+    }
+    exit_code = exit_code.or(failed[0].error.as_ref().map(Error::exit_code));
+  }
+
+  // 輸出 CI 使用的診斷信息
+  let mut all_diagnostics: Vec<ModDiagnostics> = mods.iter().map(ModCtx::diagnostics).collect();
+  if let Some(e) = &build_error {
+    all_diagnostics.push(ModDiagnostics {
+      name: "mod-dev".to_string(),
+      time_ms: 0,
+      diagnostics: vec![Diagnostic {
+        severity: Severity::Error,
+        key: e.key().to_string(),
+        message: e.to_string(),
+        file: None,
+        line: None,
+        column: None,
+      }],
+    });
+  }
+  let (warnings, errors) = diagnostics::counts(&all_diagnostics);
+  exit_code = summarize(cofg.deny_warnings, exit_code, warnings, errors);
+  if let Some(out) = diagnostics::render(cofg.diagnostic_format, &all_diagnostics) {
+    match &cofg.diagnostic_output {
+      Some(path) => {
+        if let Err(e) = fs::write(path, out).with_path(path) {
+          error!("{}", e);
+          exit_code = exit_code.or(Some(e.exit_code()));
+        }
+      }
+      None => print!("{}", out),
+    }
+  }
+
+  // 輸出構建報告
+  if let Some(path) = &cofg.report {
+    let report = Report::new(
+      cofg.profile.as_deref(),
+      exit_code.unwrap_or(0),
+      (warnings, errors),
+      start,
+      build_error.as_ref(),
+      mods.iter().map(|ctx| (ctx.src.as_path(), ctx.error.as_ref(), &ctx.report))
+    );
+    match report.write(path) {
+      Ok(_) => info!("{}", t!("report.done", path = path.display())),
+      Err(e) => {
+        error!("{}", e);
+        exit_code = exit_code.or(Some(e.exit_code()));
+      }
+    }
+  }
 
   // 如果需要暫停，等待用戶輸入
   if cofg.pause {
     info!("press any key to exit:");
-    std::io::stdin().read_line(&mut String::new()).unwrap();
+    let _ = std::io::stdin().read_line(&mut String::new());
+  }
+  if let Some(code) = exit_code {
+    std::process::exit(code);
   }
 }
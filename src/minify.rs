@@ -0,0 +1,409 @@
+//! 壓縮 JS 和 CSS (`minify`)
+//!
+//! 只去掉註釋和多餘的空白, 不改名, 不改寫語法, 所以壓縮後的代碼與原來的行為相同。
+//! JS 中的換行可能影響自動插入分號, 只有確定不會影響時才去掉。
+//! 有 source map 時, 按壓縮前後每個詞的位置調整 `mappings`。
+
+use std::fs;
+use std::path::Path;
+use oxc_allocator::Allocator;
+use oxc_ast::ast::*;
+use oxc_ast_visit::Visit;
+use oxc_parser::Parser;
+use oxc_span::{ SourceType, Span };
+use crate::error::{ Error, WithPath };
+use crate::sourcemap::{ self, Segment };
+
+/// 換行後面是這些字符時, 去掉換行不會影響自動插入分號
+const JOIN_BEFORE: &[char] = &[')', ']', '}', ',', ';', '.', '?', ':', '='];
+/// 換行前面是這些字符時, 去掉換行不會影響自動插入分號
+/// * 不含 `+`, `-`(後綴 `++`/`--`), `/`(正則表達式的結尾) 和 `)`
+const JOIN_AFTER: &[char] = &['{', '(', '[', ',', ';', ':', '=', '?', '&', '|', '^', '!', '~', '*', '%', '<', '>'];
+
+/// 是否是標識符, 關鍵字或數字中的字符
+fn is_word(c: char) -> bool {
+  c.is_alphanumeric() || c == '_' || c == '$' || c == '\\' || !c.is_ascii()
+}
+
+/// 是否是換行
+fn is_newline(c: char) -> bool {
+  matches!(c, '\n' | '\r' | '\u{2028}' | '\u{2029}')
+}
+
+/// 是否是空白(包括換行)
+fn is_space(c: char) -> bool {
+  c.is_whitespace() || c == '\u{feff}'
+}
+
+/// 壓縮後的代碼
+#[derive(Debug)]
+pub(crate) struct Minified {
+  pub code: String,
+  /// 輸出中每一行的詞: (輸出中的列, 輸入中的行, 輸入中的列), 都從 0 開始, 列按 UTF-16 計算
+  marks: Vec<Vec<(i64, i64, i64)>>,
+}
+
+impl Minified {
+  /// 調整 source map 的映射
+  /// * `original` - 壓縮前的代碼的映射
+  /// # 返回
+  /// * 壓縮後的代碼的映射
+  pub(crate) fn map(&self, original: &[Vec<Segment>]) -> Vec<Vec<Segment>> {
+    self.marks
+      .iter()
+      .map(|marks| {
+        let mut segments: Vec<Segment> = Vec::new();
+        for &(column, line, original_column) in marks {
+          let Some(found) = original
+            .get(line as usize)
+            .and_then(|segments| segments.iter().rev().find(|s| s.column <= original_column))
+          else {
+            continue;
+          };
+          // 與前一個映射到同一位置時不重複
+          if segments.last().is_some_and(|last| (last.source, last.line, last.original_column) == (found.source, found.line, found.original_column)) {
+            continue;
+          }
+          segments.push(Segment { column, ..*found });
+        }
+        segments
+      })
+      .collect()
+  }
+}
+
+/// 按順序輸出代碼, 記錄輸入和輸出的位置
+struct Writer<'s> {
+  source: &'s str,
+  /// 輸入中的字節偏移
+  offset: usize,
+  /// 輸入中的 (行, 列)
+  input: (i64, i64),
+  out: String,
+  /// 輸出中的列
+  column: i64,
+  marks: Vec<Vec<(i64, i64, i64)>>,
+}
+
+impl<'s> Writer<'s> {
+  fn new(source: &'s str) -> Writer<'s> {
+    Writer { source, offset: 0, input: (0, 0), out: String::with_capacity(source.len()), column: 0, marks: vec![Vec::new()] }
+  }
+
+  /// 下一個輸入字符
+  fn peek(&self) -> Option<char> {
+    self.source[self.offset..].chars().next()
+  }
+
+  /// 跳過或複製輸入直到 `end`
+  fn advance(&mut self, end: usize, copy: bool) {
+    let text = &self.source[self.offset..end];
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+      if copy {
+        self.push(c);
+      }
+      match c {
+        '\r' if chars.peek() == Some(&'\n') => {}
+        c if is_newline(c) => {
+          self.input = (self.input.0 + 1, 0);
+        }
+        c => {
+          self.input.1 += c.len_utf16() as i64;
+        }
+      }
+    }
+    self.offset = end;
+  }
+
+  /// 輸出一個字符
+  fn push(&mut self, c: char) {
+    self.out.push(c);
+    if c == '\n' {
+      self.column = 0;
+      self.marks.push(Vec::new());
+    } else if !is_newline(c) {
+      self.column += c.len_utf16() as i64;
+    }
+  }
+
+  /// 記錄當前輸入位置對應的輸出位置
+  fn mark(&mut self) {
+    let (line, column) = self.input;
+    if let Some(marks) = self.marks.last_mut() {
+      marks.push((self.column, line, column));
+    }
+  }
+
+  fn last(&self) -> Option<char> {
+    self.out.chars().next_back()
+  }
+
+  fn finish(self) -> Minified {
+    Minified { code: self.out, marks: self.marks }
+  }
+}
+
+/// 不能修改的部分: 字符串, 模板字符串, 正則表達式
+#[derive(Default)]
+struct Literals {
+  spans: Vec<Span>,
+  /// 正則表達式的結尾, 後面緊跟的詞會被當成標誌
+  regex_ends: Vec<u32>,
+}
+
+impl<'a> Visit<'a> for Literals {
+  fn visit_string_literal(&mut self, it: &StringLiteral<'a>) {
+    self.spans.push(it.span);
+  }
+
+  fn visit_template_element(&mut self, it: &TemplateElement<'a>) {
+    self.spans.push(it.span);
+  }
+
+  fn visit_reg_exp_literal(&mut self, it: &RegExpLiteral<'a>) {
+    self.spans.push(it.span);
+    self.regex_ends.push(it.span.end);
+  }
+}
+
+/// 去掉空白後, 兩個字符之間是否需要空格
+fn needs_space(prev: char, next: char, after_regex: bool) -> bool {
+  ((is_word(prev) || after_regex) && is_word(next)) ||
+    matches!((prev, next), ('+', '+') | ('-', '-') | ('/', '/') | ('/', '*') | ('<', '!') | ('-', '>')) ||
+    // `1 .toString()`
+    (prev.is_ascii_digit() && next == '.')
+}
+
+/// 壓縮 JS
+/// * 保留 `/*!`, `@license` 和 `@preserve` 註釋
+/// # 錯誤
+/// * 語法錯誤: (字節偏移, 消息)
+pub(crate) fn minify_js(source: &str) -> Result<Minified, (usize, String)> {
+  let allocator = Allocator::default();
+  let ret = Parser::new(&allocator, source, SourceType::unambiguous()).parse();
+  if let Some(e) = ret.errors.first() {
+    let offset = e.labels
+      .as_ref()
+      .and_then(|labels| labels.first())
+      .map_or(0, |label| label.offset());
+    return Err((offset, e.message.to_string()));
+  }
+  let mut literals = Literals::default();
+  literals.visit_program(&ret.program);
+
+  // (範圍, 是否保留, 後面是否必須換行); 字面量和註釋不會重疊
+  let mut regions: Vec<(Span, bool, bool)> = literals.spans
+    .iter()
+    .map(|span| (*span, true, false))
+    .chain(ret.program.hashbang.as_ref().map(|h| (h.span, true, true)))
+    .chain(ret.program.comments.iter().map(|c| (c.span, c.is_legal(), c.is_line())))
+    .collect();
+  regions.sort_by_key(|(span, ..)| span.start);
+
+  let mut w = Writer::new(source);
+  let mut regions = regions.into_iter().peekable();
+  // 跳過的空白和註釋中是否有換行; 沒有跳過時為 None
+  let mut gap: Option<bool> = None;
+  let mut after_regex = false;
+  let mut after_line_comment = false;
+  let mut in_word = false;
+  while let Some(c) = w.peek() {
+    if let Some(&(span, keep, line_comment)) = regions.peek() && span.start as usize == w.offset {
+      regions.next();
+      if !keep {
+        let text = span.source_text(source);
+        gap = Some(gap.unwrap_or(false) || text.contains(is_newline));
+        w.advance(span.end as usize, false);
+        continue;
+      }
+      separate(&mut w, gap.take(), c, after_regex, after_line_comment);
+      w.mark();
+      w.advance(span.end as usize, true);
+      after_regex = literals.regex_ends.contains(&span.end);
+      after_line_comment = line_comment;
+      in_word = false;
+      continue;
+    }
+    if is_space(c) {
+      gap = Some(gap.unwrap_or(false) || is_newline(c));
+      w.advance(w.offset + c.len_utf8(), false);
+      continue;
+    }
+    let spaced = gap.is_some();
+    separate(&mut w, gap.take(), c, after_regex, after_line_comment);
+    if !(in_word && is_word(c)) || spaced {
+      w.mark();
+    }
+    w.advance(w.offset + c.len_utf8(), true);
+    in_word = is_word(c);
+    after_regex = false;
+    after_line_comment = false;
+  }
+  Ok(w.finish())
+}
+
+/// 在去掉的空白處輸出需要的換行或空格
+/// * `gap` - 去掉的部分中是否有換行, 沒有去掉任何東西時為 `None`
+fn separate(w: &mut Writer, gap: Option<bool>, next: char, after_regex: bool, after_line_comment: bool) {
+  let (Some(newline), Some(prev)) = (gap, w.last()) else {
+    return;
+  };
+  if after_line_comment || (newline && !JOIN_AFTER.contains(&prev) && !JOIN_BEFORE.contains(&next)) {
+    w.push('\n');
+  } else if needs_space(prev, next, after_regex) {
+    w.push(' ');
+  }
+}
+
+/// 壓縮 CSS
+/// * 保留 `/*!` 註釋
+pub(crate) fn minify_css(source: &str) -> Minified {
+  let mut w = Writer::new(source);
+  let mut gap = false;
+  while let Some(c) = w.peek() {
+    let rest = &source[w.offset..];
+    // 註釋
+    if let Some(comment) = rest.strip_prefix("/*") {
+      let end = comment.find("*/").map_or(source.len(), |i| w.offset + i + 4);
+      if rest.starts_with("/*!") {
+        w.mark();
+        w.advance(end, true);
+      } else {
+        gap = true;
+        w.advance(end, false);
+      }
+      continue;
+    }
+    if is_space(c) {
+      gap = true;
+      w.advance(w.offset + c.len_utf8(), false);
+      continue;
+    }
+    if gap && let Some(prev) = w.last() && !"{};,>~:(".contains(prev) && !"{};,>~)!".contains(c) {
+      w.push(' ');
+    }
+    gap = false;
+    // 最後一個聲明後面的分號
+    if c == '}' && w.last() == Some(';') {
+      w.out.pop();
+      w.column -= 1;
+      if let Some(marks) = w.marks.last_mut() && marks.last().is_some_and(|mark| mark.0 == w.column) {
+        marks.pop();
+      }
+    }
+    w.mark();
+    let end = match c {
+      '"' | '\'' => css_string_end(source, w.offset, c),
+      // 轉義的字符(如 `.a\ b` 中的空格)原樣保留
+      '\\' => rest[1..].chars().next().map_or(source.len(), |next| w.offset + 1 + next.len_utf8()),
+      'u' | 'U' if rest.get(..4).is_some_and(|s| s.eq_ignore_ascii_case("url(")) && !w.last().is_some_and(is_word) => {
+        let inner = rest[4..].trim_start();
+        if inner.starts_with(['"', '\'']) { w.offset + 4 } else { rest.find(')').map_or(source.len(), |i| w.offset + i + 1) }
+      }
+      _ => w.offset + c.len_utf8(),
+    };
+    w.advance(end, true);
+  }
+  w.finish()
+}
+
+/// CSS 字符串的結尾(引號後面)
+fn css_string_end(source: &str, start: usize, quote: char) -> usize {
+  let mut chars = source[start + 1..].char_indices();
+  while let Some((i, c)) = chars.next() {
+    match c {
+      '\\' => {
+        chars.next();
+      }
+      c if c == quote || c == '\n' => {
+        return start + 1 + i + c.len_utf8();
+      }
+      _ => {}
+    }
+  }
+  source.len()
+}
+
+/// 壓縮一個文件的結果
+#[derive(Debug)]
+pub(crate) enum Outcome {
+  /// (壓縮前, 壓縮後) 的字節數
+  Minified(u64, u64),
+  /// 有語法錯誤, 沒有壓縮: (行, 列, 消息)
+  Skipped(usize, usize, String),
+}
+
+/// 壓縮一個 `.js` 或 `.css` 文件
+/// * `source_map` - 是否保留並調整 source map; 否則去掉 `sourceMappingURL`
+pub(crate) fn minify_file(path: &Path, source_map: bool) -> Result<Outcome, Error> {
+  let code = fs::read_to_string(path).with_path(path)?;
+  let css = path.extension().is_some_and(|ext| ext == "css");
+  let minified = if css {
+    minify_css(&code)
+  } else {
+    match minify_js(&code) {
+      Ok(minified) => minified,
+      Err((offset, msg)) => {
+        let (line, column) = sourcemap::position(&code, offset);
+        return Ok(Outcome::Skipped(line, column, msg));
+      }
+    }
+  };
+  let mut out = minified.code.clone();
+
+  if source_map && let Some((map_file, mut map)) = sourcemap::read_source_map(path, &code) {
+    map.mappings = sourcemap::encode_mappings(&minified.map(&sourcemap::decode_mappings(&map.mappings)));
+    map.names.clear();
+    let json = serde_json::to_string(&map)?;
+    let url = match (&map_file, sourcemap::mapping_url(&code)) {
+      (Some(file), url) => {
+        fs::write(file, &json).with_path(file)?;
+        url.map(|(_, url)| url.to_string())
+      }
+      (None, _) => Some(format!("data:application/json;charset=utf-8;base64,{}", sourcemap::base64(json.as_bytes()))),
+    };
+    if let Some(url) = url {
+      out.push_str(&if css { format!("\n/*# sourceMappingURL={} */", url) } else { format!("\n//# sourceMappingURL={}", url) });
+    }
+  }
+  out.push('\n');
+
+  fs::write(path, &out).with_path(path)?;
+  Ok(Outcome::Minified(code.len() as u64, out.len() as u64))
+}
+
+/// 一個mod的壓縮結果
+#[derive(Debug, Default)]
+pub(crate) struct MinifyResult {
+  /// 壓縮的文件數量
+  pub files: usize,
+  /// 壓縮前的總字節數
+  pub before: u64,
+  /// 壓縮後的總字節數
+  pub after: u64,
+  /// 沒有壓縮的文件: (文件, 行, 列, 消息)
+  pub skipped: Vec<(String, usize, usize, String)>,
+}
+
+/// 壓縮mod中的腳本和樣式
+/// * `root` - mod文件夾
+/// * `files` - 要壓縮的文件(相對mod文件夾), 不存在的和 `.min.js`/`.min.css` 會跳過
+pub(crate) fn minify_mod(root: &Path, files: &[&str], source_map: bool) -> Result<MinifyResult, Error> {
+  let mut result = MinifyResult::default();
+  for file in files {
+    let path = root.join(file);
+    if file.ends_with(".min.js") || file.ends_with(".min.css") || !path.is_file() {
+      continue;
+    }
+    match minify_file(&path, source_map)? {
+      Outcome::Minified(before, after) => {
+        result.files += 1;
+        result.before += before;
+        result.after += after;
+      }
+      Outcome::Skipped(line, column, msg) => result.skipped.push((file.to_string(), line, column, msg)),
+    }
+  }
+  Ok(result)
+}
@@ -0,0 +1,181 @@
+//! scriptFileList 和 styleFileList 的排序 (`order`)
+//!
+//! ModLoader 按列表順序執行腳本和加載樣式, 新文件按 glob 順序加在末尾時,
+//! 被依賴的文件(如 `00_util.js`)可能排在依賴它的文件後面。
+
+use std::cmp::Ordering;
+use std::fs;
+use std::path::{ Path, PathBuf };
+use glob::{ MatchOptions, Pattern };
+use serde_json::{ Map, Value };
+use crate::cofg::OrderCofg;
+use crate::diagnostics::{ Warning, warning };
+use crate::error::{ Error, WithPath };
+
+/// 排序規則的 glob 匹配選項, `*` 不匹配 `/`
+const OPTIONS: MatchOptions = MatchOptions {
+  case_sensitive: true,
+  require_literal_separator: true,
+  require_literal_leading_dot: false,
+};
+
+/// 自然排序: 數字按數值比較, `file2` 在 `file10` 前面
+pub(crate) fn natural_cmp(a: &str, b: &str) -> Ordering {
+  let (mut a_chars, mut b_chars) = (a.chars().peekable(), b.chars().peekable());
+  loop {
+    match (a_chars.peek().copied(), b_chars.peek().copied()) {
+      (None, None) => return a.cmp(b),
+      (None, Some(_)) => return Ordering::Less,
+      (Some(_), None) => return Ordering::Greater,
+      (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+        let take = |chars: &mut std::iter::Peekable<std::str::Chars>| {
+          let mut digits = String::new();
+          while let Some(c) = chars.next_if(char::is_ascii_digit) {
+            digits.push(c);
+          }
+          digits
+        };
+        let (x, y) = (take(&mut a_chars), take(&mut b_chars));
+        let (x_trim, y_trim) = (x.trim_start_matches('0'), y.trim_start_matches('0'));
+        let ordering = x_trim.len().cmp(&y_trim.len()).then_with(|| x_trim.cmp(y_trim));
+        if ordering != Ordering::Equal {
+          return ordering;
+        }
+      }
+      (Some(x), Some(y)) => {
+        let ordering = x.to_lowercase().cmp(y.to_lowercase());
+        if ordering != Ordering::Equal {
+          return ordering;
+        }
+        a_chars.next();
+        b_chars.next();
+      }
+    }
+  }
+}
+
+/// 編譯後的排序規則
+struct Rules {
+  first: Vec<Pattern>,
+  last: Vec<Pattern>,
+  priority: Vec<(Pattern, i64)>,
+}
+
+impl Rules {
+  fn new(order: &OrderCofg) -> Result<Rules, Error> {
+    let compile = |globs: &[String]| globs.iter().map(|g| Pattern::new(g)).collect::<Result<Vec<_>, _>>();
+    Ok(Rules {
+      first: compile(&order.first)?,
+      last: compile(&order.last)?,
+      priority: order.priority
+        .iter()
+        .map(|rule| Ok((Pattern::new(&rule.glob)?, rule.priority)))
+        .collect::<Result<Vec<_>, Error>>()?,
+    })
+  }
+
+  /// 排序鍵: (`first` 為 0, 其他為 1, `last` 為 2; 組內的順序)
+  fn key(&self, file: &str) -> (u8, i64) {
+    let position = |patterns: &[Pattern]| patterns.iter().position(|p| p.matches_with(file, OPTIONS));
+    if let Some(i) = position(&self.first) {
+      return (0, i as i64);
+    }
+    if let Some(i) = position(&self.last) {
+      return (2, i as i64);
+    }
+    let priority = self.priority
+      .iter()
+      .find(|(p, _)| p.matches_with(file, OPTIONS))
+      .map_or(0, |(_, priority)| *priority);
+    (1, -priority)
+  }
+}
+
+/// 按規則排序文件列表
+/// * `listed` - boot.json 中原有的文件, 保持原來的順序; 其他新加入的文件在 `natural` 時按自然排序
+/// * 然後按 `first`, `priority`, `last` 穩定排序, 沒有匹配任何規則的文件順序不變
+pub(crate) fn sort_list(list: &mut Vec<String>, order: &OrderCofg, listed: &[String]) -> Result<(), Error> {
+  if order.natural {
+    let (mut old, mut new): (Vec<String>, Vec<String>) = list.drain(..).partition(|f| listed.contains(f));
+    new.sort_by(|a, b| natural_cmp(a, b));
+    old.append(&mut new);
+    *list = old;
+  }
+  let rules = Rules::new(order)?;
+  list.sort_by_key(|file| rules.key(file));
+  Ok(())
+}
+
+/// 與上次構建相比相對順序改變了的文件
+/// * 只比較兩次都有的文件, 不在最長的順序不變子序列中的文件視為移動了
+/// # 返回
+/// * (文件, 上次的位置, 這次的位置), 位置從 1 開始
+pub(crate) fn moved(old: &[String], new: &[String]) -> Vec<(String, usize, usize)> {
+  let common: Vec<(usize, usize)> = new
+    .iter()
+    .enumerate()
+    .filter_map(|(j, f)| old.iter().position(|o| o == f).map(|i| (i, j)))
+    .collect();
+  // 按這次的順序, 上次位置的最長遞增子序列
+  let mut best = vec![1usize; common.len()];
+  let mut prev = vec![usize::MAX; common.len()];
+  for i in 0..common.len() {
+    for j in 0..i {
+      if common[j].0 < common[i].0 && best[j] + 1 > best[i] {
+        best[i] = best[j] + 1;
+        prev[i] = j;
+      }
+    }
+  }
+  let mut keep = vec![false; common.len()];
+  let mut cur = (0..common.len()).max_by_key(|&i| (best[i], usize::MAX - i));
+  while let Some(i) = cur {
+    keep[i] = true;
+    cur = (prev[i] != usize::MAX).then_some(prev[i]);
+  }
+  common
+    .iter()
+    .zip(keep)
+    .filter(|(_, keep)| !keep)
+    .map(|(&(i, j), _)| (new[j].clone(), i + 1, j + 1))
+    .collect()
+}
+
+/// 這次構建的列表順序, mod構建成功後才保存, 失敗的構建不會覆蓋上次的順序
+pub(crate) struct OrderState {
+  path: PathBuf,
+  lists: Map<String, Value>,
+}
+
+impl OrderState {
+  /// 寫入保存順序的文件
+  pub(crate) fn save(&self) -> Result<(), Error> {
+    if let Some(parent) = self.path.parent() {
+      fs::create_dir_all(parent).with_path(parent)?;
+    }
+    fs::write(&self.path, serde_json::to_string_pretty(&self.lists)?).with_path(&self.path)
+  }
+}
+
+/// 對比上次構建的列表順序
+/// * `state` - 保存順序的文件
+/// * `lists` - (列表名稱, 文件)
+/// # 返回
+/// * 順序改變的警告, 這次的順序
+pub(crate) fn track(state: &Path, lists: &[(&str, &[String])]) -> (Vec<Warning>, OrderState) {
+  let previous: Map<String, Value> = fs::read_to_string(state)
+    .ok()
+    .and_then(|text| serde_json::from_str(&text).ok())
+    .unwrap_or_default();
+  let mut warnings = Vec::new();
+  let mut current = Map::new();
+  for (name, files) in lists {
+    if let Some(old) = previous.get(*name).and_then(|v| serde_json::from_value::<Vec<String>>(v.clone()).ok()) {
+      for (file, from, to) in moved(&old, files) {
+        warnings.push(warning!("order.moved", list = name, file = file, from = from, to = to).with_file(&file));
+      }
+    }
+    current.insert(name.to_string(), Value::from(files.to_vec()));
+  }
+  (warnings, OrderState { path: state.to_path_buf(), lists: current })
+}
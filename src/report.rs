@@ -0,0 +1,154 @@
+//! `--report <path>`: 以 JSON 格式輸出構建報告
+
+use std::collections::BTreeMap;
+use std::path::{ Path, PathBuf };
+use std::time::Instant;
+use serde::Serialize;
+use crate::diagnostics::Warning;
+use crate::error::{ Error, WithPath };
+use crate::tsc::TscDiagnostic;
+
+/// tsc 的編譯結果
+#[derive(Serialize, Debug, Default)]
+pub(crate) struct TscReport {
+  /// 是否編譯成功
+  pub success: bool,
+  /// 錯誤數量
+  pub errors: usize,
+  /// tsc 輸出的診斷信息, 文件為mod源文件夾中的路徑
+  pub diagnostics: Vec<TscDiagnostic>,
+}
+
+/// 壓縮 JS/CSS 的結果
+#[derive(Serialize, Debug, Default)]
+pub(crate) struct MinifyReport {
+  /// 壓縮的文件數量
+  pub files: usize,
+  /// 壓縮前的總大小(字節)
+  pub before: u64,
+  /// 壓縮後的總大小(字節)
+  pub after: u64,
+}
+
+/// 一個mod在構建過程中收集的信息
+#[derive(Serialize, Debug, Default)]
+pub(crate) struct ModReport {
+  /// boot.json 中的名稱
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub name: Option<String>,
+  /// 輸出的 zip 文件
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub zip: Option<PathBuf>,
+  /// zip 文件大小(字節)
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub zip_size: Option<u64>,
+  /// 每個列表的文件數量
+  pub lists: BTreeMap<&'static str, usize>,
+  /// 自動加入列表的文件
+  pub added: Vec<String>,
+  /// 不在任何列表中, 沒有打包的文件
+  pub pruned: Vec<String>,
+  /// tsc 的編譯結果, 沒有 ts 文件時為空
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub tsc: Option<TscReport>,
+  /// 壓縮 JS/CSS 的結果, 沒有啟用 `minify` 時為空
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub minify: Option<MinifyReport>,
+  /// 每個步驟的用時(毫秒)
+  pub timings: BTreeMap<String, u64>,
+  /// 所有警告
+  pub warnings: Vec<Warning>,
+}
+
+impl ModReport {
+  /// 記錄一個步驟的用時
+  pub(crate) fn time(&mut self, stage: &str, start: Instant) {
+    *self.timings.entry(stage.to_string()).or_default() += start.elapsed().as_millis() as u64;
+  }
+}
+
+/// 錯誤的消息鍵和消息
+#[derive(Serialize, Debug)]
+struct ErrorReport {
+  key: &'static str,
+  message: String,
+}
+
+impl From<&Error> for ErrorReport {
+  fn from(e: &Error) -> Self {
+    ErrorReport { key: e.key(), message: e.to_string() }
+  }
+}
+
+/// 報告中的一個mod
+#[derive(Serialize, Debug)]
+struct ModEntry<'a> {
+  /// mod源文件夾
+  source: &'a Path,
+  /// 是否構建成功
+  success: bool,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  error: Option<ErrorReport>,
+  #[serde(flatten)]
+  report: &'a ModReport,
+}
+
+/// 整個構建的報告
+#[derive(Serialize, Debug)]
+pub(crate) struct Report<'a> {
+  /// mod-dev 版本
+  version: &'static str,
+  /// 使用的構建配置
+  profile: Option<&'a str>,
+  /// 退出碼
+  exit_code: i32,
+  /// 警告數量
+  warnings: usize,
+  /// 錯誤數量
+  errors: usize,
+  /// 總用時(毫秒)
+  duration_ms: u64,
+  /// 中止整個構建的錯誤(如衝突)
+  #[serde(skip_serializing_if = "Option::is_none")]
+  error: Option<ErrorReport>,
+  mods: Vec<ModEntry<'a>>,
+}
+
+impl<'a> Report<'a> {
+  /// 創建報告
+  /// * `counts` - (警告數量, 錯誤數量)
+  /// * `mods` - (源文件夾, 錯誤, 收集的信息)
+  /// * `error` - 中止整個構建的錯誤
+  pub(crate) fn new(
+    profile: Option<&'a str>,
+    exit_code: i32,
+    (warnings, errors): (usize, usize),
+    start: Instant,
+    error: Option<&Error>,
+    mods: impl IntoIterator<Item = (&'a Path, Option<&'a Error>, &'a ModReport)>
+  ) -> Report<'a> {
+    Report {
+      version: crate::r#const::VERSION,
+      profile,
+      exit_code,
+      warnings,
+      errors,
+      duration_ms: start.elapsed().as_millis() as u64,
+      error: error.map(ErrorReport::from),
+      mods: mods
+        .into_iter()
+        .map(|(source, error, report)| ModEntry {
+          source,
+          success: error.is_none(),
+          error: error.map(ErrorReport::from),
+          report,
+        })
+        .collect(),
+    }
+  }
+
+  /// 寫入 JSON 文件
+  pub(crate) fn write(&self, path: &Path) -> Result<(), Error> {
+    std::fs::write(path, serde_json::to_string_pretty(self)?).with_path(path)
+  }
+}
@@ -0,0 +1,289 @@
+//! `mod-dev new`: 用模板創建新的mod文件夾
+//! `mod-dev init`: 為沒有 boot.json 的文件夾生成 boot.json
+
+use std::fs;
+use std::io::{ IsTerminal, Write };
+use std::path::{ Path, PathBuf };
+use log::{ info, warn };
+use rust_i18n::t;
+use walkdir::WalkDir;
+use crate::boot_json::{ BootJson, process_file_path };
+use crate::cofg::Cofg;
+use crate::error::{ Error, WithPath };
+use crate::order;
+use crate::template::sanitize_file_name;
+use crate::typings;
+
+/// 一個模板文件: (相對路徑, 內容)
+type TemplateFile = (&'static str, &'static [u8]);
+/// 讀取後的模板文件: (相對路徑, 內容)
+type LoadedFile = (PathBuf, Vec<u8>);
+
+/// 內置模板: (名稱, 說明, 文件)
+const BUILTIN_TEMPLATES: [(&str, &str, &[TemplateFile]); 4] = [
+  (
+    "twee",
+    "twee-only mod",
+    &[
+      ("boot.json", include_bytes!("templates/twee/boot.json")),
+      ("README.md", include_bytes!("templates/twee/README.md")),
+      ("twee/main.twee", include_bytes!("templates/twee/twee/main.twee")),
+    ],
+  ),
+  (
+    "img",
+    "image replacement mod (ImageLoaderHook)",
+    &[
+      ("boot.json", include_bytes!("templates/img/boot.json")),
+      ("README.md", include_bytes!("templates/img/README.md")),
+      ("img/.gitkeep", include_bytes!("templates/img/img/.gitkeep")),
+    ],
+  ),
+  (
+    "ts",
+    "TypeScript mod (tsconfig.json)",
+    &[
+      ("boot.json", include_bytes!("templates/ts/boot.json")),
+      ("README.md", include_bytes!("templates/ts/README.md")),
+      ("tsconfig.json", include_bytes!("templates/ts/tsconfig.json")),
+      ("src/main.ts", include_bytes!("templates/ts/src/main.ts")),
+    ],
+  ),
+  (
+    "tweereplacer",
+    "TweeReplacer patch mod",
+    &[
+      ("boot.json", include_bytes!("templates/tweereplacer/boot.json")),
+      ("README.md", include_bytes!("templates/tweereplacer/README.md")),
+      ("twee/main.twee", include_bytes!("templates/tweereplacer/twee/main.twee")),
+    ],
+  ),
+];
+
+/// 列出所有可用的模板: (名稱, 說明)
+/// * 本地模板(`templates_path` 下含 boot.json 的文件夾)會覆蓋同名的內置模板
+pub(crate) fn list_templates(cofg: &Cofg) -> Vec<(String, String)> {
+  let mut list: Vec<(String, String)> = BUILTIN_TEMPLATES.iter()
+    .map(|(name, desc, _)| (name.to_string(), desc.to_string()))
+    .collect();
+  if let Ok(entries) = fs::read_dir(&cofg.path.templates_path) {
+    for entry in entries.flatten() {
+      let path = entry.path();
+      if !path.join("boot.json").is_file() {
+        continue;
+      }
+      let name = entry.file_name().to_string_lossy().to_string();
+      list.retain(|(n, _)| *n != name);
+      list.push((name, path.display().to_string()));
+    }
+  }
+  list
+}
+
+/// 讀取模板的所有文件
+fn load_template(
+  cofg: &Cofg,
+  template: &str
+) -> Result<Vec<LoadedFile>, Error> {
+  let local = Path::new(&cofg.path.templates_path).join(template);
+  if local.join("boot.json").is_file() {
+    let mut files = Vec::new();
+    for entry in WalkDir::new(&local).sort_by_file_name() {
+      let entry = entry?;
+      if entry.file_type().is_file() {
+        let rel = PathBuf::from(process_file_path(entry.path(), &local)?);
+        files.push((rel, fs::read(entry.path()).with_path(entry.path())?));
+      }
+    }
+    return Ok(files);
+  }
+
+  BUILTIN_TEMPLATES.iter()
+    .find(|(name, _, _)| *name == template)
+    .map(|(_, _, files)| {
+      files
+        .iter()
+        .map(|(path, content)| (PathBuf::from(path), content.to_vec()))
+        .collect()
+    })
+    .ok_or_else(|| {
+      let list = list_templates(cofg)
+        .into_iter()
+        .map(|(name, _)| name)
+        .collect::<Vec<_>>()
+        .join(", ");
+      Error::Usage(t!("new.unknown_template", name = template, list = list).to_string())
+    })
+}
+
+/// 把名稱轉換成可以用在 widget / passage 名稱中的標識符
+pub(crate) fn to_ident(name: &str) -> String {
+  let mut id: String = name
+    .chars()
+    .map(|c| if c.is_alphanumeric() { c } else { '_' })
+    .collect();
+  if id.is_empty() || id.starts_with(|c: char| c.is_ascii_digit()) {
+    id.insert(0, '_');
+  }
+  id
+}
+
+/// 替換模板文件中的 `{{name}}`, `{{version}}`, `{{id}}`
+/// * `.json` 文件中的值會按 JSON 字符串轉義
+pub(crate) fn fill_placeholders(content: &str, path: &Path, name: &str, version: &str) -> String {
+  let is_json = path.extension().is_some_and(|ext| ext == "json");
+  let escape = |s: &str| {
+    if is_json {
+      let quoted = serde_json::to_string(s).unwrap();
+      quoted[1..quoted.len() - 1].to_string()
+    } else {
+      s.to_string()
+    }
+  };
+  content
+    .replace("{{name}}", &escape(name))
+    .replace("{{version}}", &escape(version))
+    .replace("{{id}}", &escape(&to_ident(name)))
+}
+
+/// 用模板創建新的mod文件夾
+/// * `name` - mod名稱
+/// * `version` - mod版本
+/// * `template` - 模板名稱
+/// * `root` - 創建在哪個目錄下, 默認為 `mods_path` 的第一個目錄
+/// # 返回
+/// * 新mod文件夾的路徑
+pub(crate) fn new_mod(
+  cofg: &Cofg,
+  name: &str,
+  version: &str,
+  template: &str,
+  root: Option<&Path>
+) -> Result<PathBuf, Error> {
+  let files = load_template(cofg, template)?;
+  let root = match root {
+    Some(root) => root.to_path_buf(),
+    None =>
+      cofg.path.mods_path
+        .roots()
+        .into_iter()
+        .next()
+        .ok_or_else(|| Error::Usage(t!("new.no_mods_path").to_string()))?,
+  };
+  let dir = root.join(sanitize_file_name(name));
+  if dir.exists() {
+    return Err(Error::Usage(t!("new.exists", path = dir.display()).to_string()));
+  }
+
+  for (rel, content) in files {
+    let dest = dir.join(&rel);
+    if let Some(parent) = dest.parent() {
+      fs::create_dir_all(parent).with_path(parent)?;
+    }
+    match String::from_utf8(content) {
+      Ok(text) => fs::write(&dest, fill_placeholders(&text, &rel, name, version)),
+      Err(e) => fs::write(&dest, e.into_bytes()),
+    }.with_path(&dest)?;
+  }
+
+  let boot_json_path = dir.join("boot.json");
+  if let Err(e) = BootJson::new(&boot_json_path.to_string_lossy()) {
+    fs::remove_dir_all(&dir).with_path(&dir)?;
+    return Err(Error::Usage(t!("new.invalid_boot_json", name = template, e = e).to_string()));
+  }
+  if dir.join("tsconfig.json").is_file() {
+    typings::write_types(&dir)?;
+  }
+
+  info!("{}", t!("new.done", path = dir.display(), template = template));
+  Ok(dir)
+}
+
+/// 詢問用戶輸入, 沒有 TTY 時直接返回默認值
+/// * 輸入為空時返回默認值
+fn ask(prompt: &str, default: String) -> String {
+  if !std::io::stdin().is_terminal() {
+    return default;
+  }
+  print!("{prompt} [{default}]: ");
+  let _ = std::io::stdout().flush();
+  let mut input = String::new();
+  if std::io::stdin().read_line(&mut input).is_err() {
+    return default;
+  }
+  match input.trim() {
+    "" => default,
+    s => s.to_string(),
+  }
+}
+
+/// 從 package.json 中讀取名稱和版本
+fn read_package_json(dir: &Path) -> (Option<String>, Option<String>) {
+  let Ok(content) = fs::read(dir.join("package.json")) else {
+    return (None, None);
+  };
+  let Ok(json) = serde_json::from_slice::<serde_json::Value>(&content) else {
+    return (None, None);
+  };
+  let get = |key: &str| json.get(key).and_then(|v| v.as_str()).map(|s| s.to_string());
+  (get("name"), get("version"))
+}
+
+/// 為沒有 boot.json 的文件夾生成 boot.json
+/// * 名稱默認為 package.json 的 name 或文件夾名, 版本默認為 package.json 的 version 或 `1.0.0`
+/// * 有 TTY 時詢問名稱和版本
+/// * 文件的分類規則與 [`BootJson::update_file_lists`] 相同
+/// # 返回
+/// * 生成的 boot.json 路徑
+pub(crate) fn init_from_dir(
+  cofg: &Cofg,
+  dir: &Path,
+  name: Option<String>,
+  version: Option<String>,
+  force: bool
+) -> Result<PathBuf, Error> {
+  if !dir.is_dir() {
+    return Err(Error::Usage(t!("init.not_dir", path = dir.display()).to_string()));
+  }
+  let boot_json_path = dir.join("boot.json");
+  if boot_json_path.exists() && !force {
+    return Err(
+      Error::Usage(t!("init.exists", path = boot_json_path.display()).to_string())
+    );
+  }
+
+  let (pkg_name, pkg_version) = read_package_json(dir);
+  let name = name.unwrap_or_else(|| {
+    let default = pkg_name.unwrap_or_else(|| {
+      fs::canonicalize(dir)
+        .ok()
+        .and_then(|p| p.file_name().map(|n| n.to_string_lossy().to_string()))
+        .unwrap_or("unknown".to_string())
+    });
+    ask(&t!("init.ask_name"), default)
+  });
+  let version = version.unwrap_or_else(|| {
+    ask(&t!("init.ask_version"), pkg_version.unwrap_or("1.0.0".to_string()))
+  });
+
+  let mut boot_json = BootJson::empty(name, version);
+  boot_json.update_file_lists(dir, cofg.source_map)?;
+  let folder = dir.file_name().unwrap_or_default().to_string_lossy().to_string();
+  for (_, files) in boot_json.ordered_lists_mut() {
+    order::sort_list(files, cofg.order_for(&folder), &[])?;
+  }
+
+  for entry in WalkDir::new(dir).sort_by_file_name().into_iter().flatten() {
+    if !entry.file_type().is_file() {
+      continue;
+    }
+    let rel = process_file_path(entry.path(), dir)?.replace('\\', "/");
+    if !boot_json.in_list(&rel) {
+      warn!("    {}", t!("init.unlisted", path = rel));
+    }
+  }
+
+  fs::write(&boot_json_path, serde_json::to_string_pretty(&boot_json)?).with_path(&boot_json_path)?;
+  info!("{}", t!("init.done", path = boot_json_path.display()));
+  Ok(boot_json_path)
+}
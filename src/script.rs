@@ -0,0 +1,240 @@
+//! Rhai 腳本編寫的自定義步驟
+//!
+//! 腳本中可以使用:
+//! * `boot` - boot.json 的內容(對象), 修改後寫回
+//! * `mod_dir` - mod文件夾名稱, `profile` - 構建配置名稱(未指定時為空)
+//! * `files()`, `exists(path)`, `read(path)`, `write(path, text)`, `remove(path)` - 讀寫mod中的文件, 路徑相對mod文件夾;
+//!   boot.json 只能通過 `boot` 修改
+//! * `warn(msg)` - 發出警告, `print` / `debug` 寫入日誌
+//!
+//! 文件的修改先保存在內存中, 腳本成功運行後才寫入臨時目錄。
+
+use std::cell::RefCell;
+use std::collections::{ BTreeMap, BTreeSet };
+use std::fs;
+use std::path::{ Path, PathBuf };
+use std::rc::Rc;
+use log::{ debug, info };
+use rhai::{ Array, Dynamic, Engine, EvalAltResult, Scope, AST };
+use rust_i18n::t;
+use walkdir::WalkDir;
+use crate::ModCtx;
+use crate::boot_json::BootJson;
+use crate::cofg::{ Cofg, StageCofg };
+use crate::diagnostics::warning;
+use crate::error::{ Error, WithPath };
+use crate::fs_utils;
+use crate::stage::Stage;
+
+/// 腳本中能執行的最大操作數, 防止死循環
+const MAX_OPERATIONS: u64 = 100_000_000;
+
+/// 運行 Rhai 腳本的自定義步驟
+pub(crate) struct ScriptStage {
+  name: String,
+  path: PathBuf,
+  mods: Vec<String>,
+  ast: AST,
+}
+
+impl ScriptStage {
+  /// 讀取並編譯腳本
+  pub(crate) fn new(stage: &StageCofg) -> Result<ScriptStage, Error> {
+    let path = PathBuf::from(&stage.script);
+    let ast = Engine::new().compile_file(path.clone()).map_err(|e| script_error(&path, *e))?;
+    Ok(ScriptStage { name: stage.name.clone(), path, mods: stage.mods.clone(), ast })
+  }
+
+  /// 對一個mod運行腳本
+  fn run_mod(&self, cofg: &Cofg, ctx: &mut ModCtx) -> Result<(), Error> {
+    let boot_json_path = ctx.tmp.join("boot.json");
+    let boot_json = BootJson::new(&boot_json_path.to_string_lossy())?;
+    let files = Rc::new(RefCell::new(Files::new(&ctx.tmp)?));
+    let warnings = Rc::new(RefCell::new(Vec::new()));
+    let engine = engine(&files, &warnings);
+
+    let mut scope = Scope::new();
+    scope.push("boot", rhai::serde::to_dynamic(&boot_json).map_err(|e| script_error(&self.path, *e))?);
+    scope.push_constant("mod_dir", ctx.src.file_name().unwrap_or_default().to_string_lossy().to_string());
+    scope.push_constant("profile", cofg.profile.clone().unwrap_or_default());
+    let result = engine.run_ast_with_scope(&mut scope, &self.ast);
+    for msg in warnings.take() {
+      ctx.warn(warning!("script.warn", stage = self.name, msg = msg));
+    }
+    result.map_err(|e| script_error(&self.path, *e))?;
+
+    // 先檢查 boot.json, 再寫入所有修改
+    let boot = scope.get_value::<Dynamic>("boot").unwrap_or_default();
+    let boot: serde_json::Value = rhai::serde::from_dynamic(&boot).map_err(|e| script_error(&self.path, *e))?;
+    let boot_json: BootJson = serde_json::from_value(boot).map_err(|e| Error::BootJson {
+      path: boot_json_path.clone(),
+      msg: e.to_string(),
+      pos: None,
+    })?;
+    let changed = files.borrow().apply()?;
+    fs::write(&boot_json_path, serde_json::to_string_pretty(&boot_json)?).with_path(&boot_json_path)?;
+    info!("    {}", t!("script.done", stage = self.name, path = ctx.src.display(), count = changed));
+    Ok(())
+  }
+}
+
+impl Stage for ScriptStage {
+  fn name(&self) -> &str {
+    &self.name
+  }
+
+  fn run(&self, cofg: &Cofg, mods: &mut Vec<ModCtx>) -> Result<(), Error> {
+    info!("### {} ###", t!("script.start", stage = self.name));
+    for ctx in mods.iter_mut().filter(|ctx| ctx.ok()) {
+      let dir = ctx.src.file_name().unwrap_or_default().to_string_lossy().to_string();
+      if !self.mods.is_empty() && !self.mods.contains(&dir) {
+        continue;
+      }
+      crate::run_stage(cofg, ctx, &self.name, |ctx| self.run_mod(cofg, ctx));
+    }
+    info!("=== {} ===", t!("script.end", stage = self.name));
+    Ok(())
+  }
+}
+
+/// 腳本錯誤, 位置從錯誤信息中分離出來
+fn script_error(path: &Path, mut e: EvalAltResult) -> Error {
+  let pos = e.take_position();
+  Error::Script {
+    path: path.to_path_buf(),
+    msg: e.to_string(),
+    pos: pos.line().map(|line| (line, pos.position().unwrap_or(1))),
+  }
+}
+
+/// 創建註冊了文件函數的腳本引擎
+fn engine(files: &Rc<RefCell<Files>>, warnings: &Rc<RefCell<Vec<String>>>) -> Engine {
+  let mut engine = Engine::new();
+  engine.set_max_operations(MAX_OPERATIONS);
+  engine.on_print(|text| info!("        {}", text));
+  engine.on_debug(|text, _, _| debug!("        {}", text));
+
+  let f = files.clone();
+  engine.register_fn("files", move || -> Array { f.borrow().list().into_iter().map(Dynamic::from).collect() });
+  let f = files.clone();
+  engine.register_fn("exists", move |path: &str| f.borrow().exists(path));
+  let f = files.clone();
+  engine.register_fn("read", move |path: &str| -> Result<String, Box<EvalAltResult>> {
+    f.borrow().read(path).map_err(Into::into)
+  });
+  let f = files.clone();
+  engine.register_fn("write", move |path: &str, text: &str| -> Result<(), Box<EvalAltResult>> {
+    f.borrow_mut().write(path, text).map_err(Into::into)
+  });
+  let f = files.clone();
+  engine.register_fn("remove", move |path: &str| -> Result<(), Box<EvalAltResult>> {
+    f.borrow_mut().remove(path).map_err(Into::into)
+  });
+  let w = warnings.clone();
+  engine.register_fn("warn", move |msg: &str| w.borrow_mut().push(msg.to_string()));
+  engine
+}
+
+/// mod文件夾中的文件和腳本做的修改
+struct Files {
+  root: PathBuf,
+  /// 臨時目錄中原有的文件, 相對路徑使用 `/`
+  existing: BTreeSet<String>,
+  /// 寫入(`Some`)或刪除(`None`)的文件
+  changes: BTreeMap<String, Option<String>>,
+}
+
+impl Files {
+  fn new(root: &Path) -> Result<Files, Error> {
+    let mut existing = BTreeSet::new();
+    for entry in WalkDir::new(root) {
+      let entry = entry?;
+      if entry.file_type().is_file() {
+        let rel = entry.path().strip_prefix(root).unwrap_or(entry.path());
+        existing.insert(rel.to_string_lossy().replace('\\', "/"));
+      }
+    }
+    Ok(Files { root: root.to_path_buf(), existing, changes: BTreeMap::new() })
+  }
+
+  /// 規範化相對路徑, 不能離開mod文件夾
+  fn key(&self, path: &str) -> Result<String, String> {
+    fs_utils
+      ::safe_join(&self.root, path)
+      .and_then(|full| full.strip_prefix(&self.root).ok().map(|rel| rel.to_string_lossy().replace('\\', "/")))
+      .ok_or_else(|| t!("script.invalid_path", path = path).to_string())
+  }
+
+  fn exists(&self, path: &str) -> bool {
+    match self.key(path) {
+      Ok(key) => match self.changes.get(&key) {
+        Some(change) => change.is_some(),
+        None => self.existing.contains(&key),
+      },
+      Err(_) => false,
+    }
+  }
+
+  /// 所有文件, 包括腳本寫入的
+  fn list(&self) -> Vec<String> {
+    let mut files = self.existing.clone();
+    for (key, change) in &self.changes {
+      match change {
+        Some(_) => files.insert(key.clone()),
+        None => files.remove(key),
+      };
+    }
+    files.into_iter().collect()
+  }
+
+  fn read(&self, path: &str) -> Result<String, String> {
+    let key = self.key(path)?;
+    match self.changes.get(&key) {
+      Some(Some(text)) => Ok(text.clone()),
+      Some(None) => Err(t!("script.not_found", path = path).to_string()),
+      None if self.existing.contains(&key) => fs::read_to_string(self.root.join(&key)).map_err(|e| format!("{}: {}", path, e)),
+      None => Err(t!("script.not_found", path = path).to_string()),
+    }
+  }
+
+  fn write(&mut self, path: &str, text: &str) -> Result<(), String> {
+    let key = self.key(path)?;
+    if key == "boot.json" {
+      return Err(t!("script.boot_json").to_string());
+    }
+    self.changes.insert(key, Some(text.to_string()));
+    Ok(())
+  }
+
+  fn remove(&mut self, path: &str) -> Result<(), String> {
+    let key = self.key(path)?;
+    if key == "boot.json" {
+      return Err(t!("script.boot_json").to_string());
+    }
+    if !self.exists(path) {
+      return Err(t!("script.not_found", path = path).to_string());
+    }
+    self.changes.insert(key, None);
+    Ok(())
+  }
+
+  /// 把修改寫入mod文件夾
+  /// # 返回
+  /// * 修改的文件數量
+  fn apply(&self) -> Result<usize, Error> {
+    for (key, change) in &self.changes {
+      let path = self.root.join(key);
+      match change {
+        Some(text) => {
+          if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).with_path(parent)?;
+          }
+          fs::write(&path, text).with_path(&path)?;
+        }
+        None if path.exists() => fs::remove_file(&path).with_path(&path)?,
+        None => {}
+      }
+    }
+    Ok(self.changes.len())
+  }
+}
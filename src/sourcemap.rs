@@ -0,0 +1,248 @@
+//! source map 的編碼, 解碼和讀取
+
+// 解碼, 讀取和路徑處理主要用於打包
+#![cfg_attr(not(feature = "bundle"), allow(dead_code))]
+
+use std::fs;
+use std::path::{ Component, Path, PathBuf };
+use serde::{ Deserialize, Serialize };
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Base64 VLQ 編碼
+pub(crate) fn vlq(out: &mut String, value: i64) {
+  let mut v = if value < 0 { ((-value as u64) << 1) | 1 } else { (value as u64) << 1 };
+  loop {
+    let mut digit = (v & 31) as usize;
+    v >>= 5;
+    if v > 0 {
+      digit |= 32;
+    }
+    out.push(BASE64[digit] as char);
+    if v == 0 {
+      break;
+    }
+  }
+}
+
+/// Base64 編碼, 用於 `inlineSourceMap`
+#[cfg(any(feature = "builtin-ts", feature = "minify"))]
+pub(crate) fn base64(data: &[u8]) -> String {
+  let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+  for chunk in data.chunks(3) {
+    let n = chunk.iter().enumerate().fold(0u32, |n, (i, b)| n | ((*b as u32) << (16 - i * 8)));
+    for i in 0..4 {
+      if i <= chunk.len() {
+        out.push(BASE64[((n >> (18 - i * 6)) & 63) as usize] as char);
+      } else {
+        out.push('=');
+      }
+    }
+  }
+  out
+}
+
+/// Base64 解碼, 忽略無效字符
+fn base64_decode(text: &str) -> Vec<u8> {
+  let mut out = Vec::with_capacity(text.len() / 4 * 3);
+  let (mut n, mut bits) = (0u32, 0);
+  for c in text.bytes() {
+    let Some(digit) = BASE64.iter().position(|&b| b == c) else {
+      continue;
+    };
+    n = (n << 6) | (digit as u32);
+    bits += 6;
+    if bits >= 8 {
+      bits -= 8;
+      out.push((n >> bits) as u8);
+    }
+  }
+  out
+}
+
+/// `to` 相對於目錄 `from` 的路徑, 使用 `/`
+pub(crate) fn relative(from: &Path, to: &Path) -> String {
+  let from: Vec<Component> = from.components().collect();
+  let to: Vec<Component> = to.components().collect();
+  let common = from
+    .iter()
+    .zip(&to)
+    .take_while(|(a, b)| a == b)
+    .count();
+  let mut parts: Vec<String> = vec!["..".to_string(); from.len() - common];
+  parts.extend(to[common..].iter().map(|c| c.as_os_str().to_string_lossy().to_string()));
+  parts.join("/")
+}
+
+/// 去掉路徑中的 `.` 和 `..`, 不訪問文件系統
+pub(crate) fn normalize(path: &Path) -> PathBuf {
+  let mut out = PathBuf::new();
+  for component in path.components() {
+    match component {
+      Component::CurDir => {}
+      Component::ParentDir if matches!(out.components().next_back(), Some(Component::Normal(_))) => {
+        out.pop();
+      }
+      component => out.push(component),
+    }
+  }
+  out
+}
+
+/// 字節偏移對應的 (行, 列), 都從 1 開始, 列按 UTF-16 計算 (與 tsc 相同)
+pub(crate) fn position(source: &str, offset: usize) -> (usize, usize) {
+  let (mut line, mut column) = (1, 1);
+  let mut chars = source[..offset.min(source.len())].chars().peekable();
+  while let Some(c) = chars.next() {
+    match c {
+      '\r' if chars.peek() == Some(&'\n') => {}
+      '\n' | '\r' | '\u{2028}' | '\u{2029}' => {
+        line += 1;
+        column = 1;
+      }
+      _ => {
+        column += c.len_utf16();
+      }
+    }
+  }
+  (line, column)
+}
+
+/// 一個映射, 都從 0 開始, 列按 UTF-16 計算
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Segment {
+  /// 輸出中的列
+  pub column: i64,
+  /// `sources` 中的索引
+  pub source: i64,
+  /// 源文件中的行
+  pub line: i64,
+  /// 源文件中的列
+  pub original_column: i64,
+}
+
+/// 解碼 `mappings`, 返回輸出中每一行的映射
+/// * 沒有源文件的映射會被丟棄
+pub(crate) fn decode_mappings(mappings: &str) -> Vec<Vec<Segment>> {
+  let mut lines = Vec::new();
+  let mut state = [0i64; 4];
+  for line in mappings.split(';') {
+    state[0] = 0;
+    let mut segments = Vec::new();
+    for segment in line.split(',').filter(|s| !s.is_empty()) {
+      let mut fields = Vec::with_capacity(5);
+      let (mut value, mut shift) = (0i64, 0);
+      for c in segment.bytes() {
+        let Some(digit) = BASE64.iter().position(|&b| b == c) else {
+          break;
+        };
+        value |= ((digit & 31) as i64) << shift;
+        if digit & 32 != 0 {
+          shift += 5;
+          continue;
+        }
+        fields.push(if value & 1 == 1 { -(value >> 1) } else { value >> 1 });
+        (value, shift) = (0, 0);
+      }
+      for (i, field) in fields.iter().take(4).enumerate() {
+        state[i] += field;
+      }
+      if fields.len() >= 4 {
+        segments.push(Segment { column: state[0], source: state[1], line: state[2], original_column: state[3] });
+      }
+    }
+    lines.push(segments);
+  }
+  lines
+}
+
+/// 編碼 `mappings`
+/// * `lines` - 輸出中每一行的映射, 按列排序
+pub(crate) fn encode_mappings(lines: &[Vec<Segment>]) -> String {
+  let mut out = String::new();
+  let mut prev = [0i64; 4];
+  for (i, segments) in lines.iter().enumerate() {
+    if i > 0 {
+      out.push(';');
+    }
+    prev[0] = 0;
+    for (j, s) in segments.iter().enumerate() {
+      if j > 0 {
+        out.push(',');
+      }
+      for (k, value) in [s.column, s.source, s.line, s.original_column].into_iter().enumerate() {
+        vlq(&mut out, value - prev[k]);
+        prev[k] = value;
+      }
+    }
+  }
+  out
+}
+
+/// source map 文件
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct SourceMap {
+  pub version: u32,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub file: Option<String>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub source_root: Option<String>,
+  #[serde(default)]
+  pub sources: Vec<String>,
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub sources_content: Vec<Option<String>>,
+  #[serde(default)]
+  pub names: Vec<String>,
+  #[serde(default)]
+  pub mappings: String,
+}
+
+impl SourceMap {
+  /// 源文件的路徑
+  /// * `dir` - source map 所在的目錄
+  pub(crate) fn source_paths(&self, dir: &Path) -> Vec<PathBuf> {
+    let root = dir.join(self.source_root.as_deref().unwrap_or_default());
+    self.sources.iter().map(|source| normalize(&root.join(source))).collect()
+  }
+}
+
+/// JS 或 CSS 末尾的 `sourceMappingURL` 註釋
+/// # 返回
+/// * (註釋的字節範圍, URL)
+pub(crate) fn mapping_url(code: &str) -> Option<(std::ops::Range<usize>, &str)> {
+  let start = code.rfind("sourceMappingURL=")?;
+  let line_start = code[..start].rfind('\n').map_or(0, |i| i + 1);
+  let prefix = code[line_start..start].trim();
+  if !["//#", "//@", "/*#", "/*@"].contains(&prefix) {
+    return None;
+  }
+  let line_end = code[start..].find('\n').map_or(code.len(), |i| start + i);
+  let url = code[start + "sourceMappingURL=".len()..line_end].trim().trim_end_matches("*/").trim();
+  Some((line_start..line_end, url))
+}
+
+/// 讀取 JS 或 CSS 文件的 source map
+/// * 支持 `sourceMappingURL` 指向的文件, 內聯的 data URL 和同名的 `.map` 文件
+/// # 返回
+/// * (source map 文件, 內聯時為 `None`; source map)
+pub(crate) fn read_source_map(path: &Path, code: &str) -> Option<(Option<PathBuf>, SourceMap)> {
+  let dir = path.parent().unwrap_or(Path::new(""));
+  let (file, text) = match mapping_url(code).map(|(_, url)| url) {
+    Some(url) if url.starts_with("data:") => {
+      let data = url.split_once(";base64,")?.1;
+      (None, String::from_utf8(base64_decode(data)).ok()?)
+    }
+    Some(url) => {
+      let file = normalize(&dir.join(url));
+      let text = fs::read_to_string(&file).ok()?;
+      (Some(file), text)
+    }
+    None => {
+      let file = PathBuf::from(format!("{}.map", path.display()));
+      let text = fs::read_to_string(&file).ok()?;
+      (Some(file), text)
+    }
+  };
+  Some((file, serde_json::from_str(&text).ok()?))
+}
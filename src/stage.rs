@@ -0,0 +1,103 @@
+//! 構建流程的步驟 (`stages`)
+//!
+//! 構建流程是按順序運行的步驟列表: 內置的 copy, ts, defines, bundle, boot_json, minify, compress,
+//! 以及 cofg.json 中 `stages` 配置的自定義步驟(Rhai 腳本), 插入到 `after` 指定的步驟之後。
+
+use rust_i18n::t;
+use crate::ModCtx;
+use crate::cofg::{ Cofg, StageCofg };
+use crate::error::Error;
+use crate::hooks::STAGES;
+
+/// 構建流程中的一個步驟
+pub(crate) trait Stage {
+  /// 步驟名稱, 用於 hooks 和構建報告中的用時
+  fn name(&self) -> &str;
+
+  /// 是否運行這個步驟
+  fn enabled(&self, _cofg: &Cofg) -> bool {
+    true
+  }
+
+  /// 處理所有mod, 單個mod的錯誤記錄到mod中
+  /// # 返回
+  /// * 需要中止構建的錯誤(如輸出路徑衝突)
+  fn run(&self, cofg: &Cofg, mods: &mut Vec<ModCtx>) -> Result<(), Error>;
+}
+
+/// 內置的步驟
+struct Builtin {
+  name: &'static str,
+  enabled: fn(&Cofg) -> bool,
+  run: fn(&Cofg, &mut Vec<ModCtx>) -> Result<(), Error>,
+}
+
+/// 創建內置的步驟
+/// * `enabled` - 是否運行這個步驟
+/// * `run` - 處理所有mod
+pub(crate) fn builtin(
+  name: &'static str,
+  enabled: fn(&Cofg) -> bool,
+  run: fn(&Cofg, &mut Vec<ModCtx>) -> Result<(), Error>
+) -> Box<dyn Stage> {
+  Box::new(Builtin { name, enabled, run })
+}
+
+impl Stage for Builtin {
+  fn name(&self) -> &str {
+    self.name
+  }
+
+  fn enabled(&self, cofg: &Cofg) -> bool {
+    (self.enabled)(cofg)
+  }
+
+  fn run(&self, cofg: &Cofg, mods: &mut Vec<ModCtx>) -> Result<(), Error> {
+    (self.run)(cofg, mods)
+  }
+}
+
+/// 檢查自定義步驟: 名稱不能重複, `after` 必須是內置步驟或前面的自定義步驟
+pub(crate) fn validate(stages: &[StageCofg]) -> Result<(), Error> {
+  let mut names: Vec<&str> = STAGES.to_vec();
+  for stage in stages {
+    if !names.contains(&stage.after.as_str()) {
+      return Err(Error::Config(t!("stage.unknown_after", name = stage.name, after = stage.after).to_string()));
+    }
+    if names.contains(&stage.name.as_str()) {
+      return Err(Error::Config(t!("stage.duplicate", name = stage.name).to_string()));
+    }
+    names.push(&stage.name);
+  }
+  Ok(())
+}
+
+/// 把自定義步驟插入內置步驟中
+/// * 自定義步驟插入到 `after` 指定的步驟和緊接其後的自定義步驟之後, 同一步驟之後的按配置的順序
+/// * `builtin` - 內置步驟, 按運行順序
+pub(crate) fn pipeline(cofg: &Cofg, builtin: Vec<Box<dyn Stage>>) -> Result<Vec<Box<dyn Stage>>, Error> {
+  let mut stages = builtin;
+  for stage in &cofg.stages {
+    let Some(mut i) = stages.iter().position(|s| s.name() == stage.after) else {
+      return Err(Error::Config(t!("stage.unknown_after", name = stage.name, after = stage.after).to_string()));
+    };
+    i += 1;
+    while i < stages.len() && !STAGES.contains(&stages[i].name()) {
+      i += 1;
+    }
+    stages.insert(i, custom(stage)?);
+  }
+  Ok(stages)
+}
+
+/// 編譯自定義步驟的腳本
+#[cfg(feature = "script")]
+fn custom(stage: &StageCofg) -> Result<Box<dyn Stage>, Error> {
+  Ok(Box::new(crate::script::ScriptStage::new(stage)?))
+}
+
+/// 沒有啟用 `script` 功能時不能使用自定義步驟
+#[cfg(not(feature = "script"))]
+fn custom(_stage: &StageCofg) -> Result<Box<dyn Stage>, Error> {
+  Err(Error::Config(t!("script.disabled").to_string()))
+}
@@ -0,0 +1,179 @@
+//! `file_name` 模板的解析和文件名清理
+
+use rust_i18n::t;
+use crate::error::Error;
+
+/// 支持的佔位符
+/// * `{name}` -- mod名稱
+/// * `{ver}` -- mod版本
+/// * `{hash}` -- mod源文件夾的 git 短哈希
+/// * `{date}` / `{date:<strftime>}` -- 當前日期, 默認 `%Y-%m-%d`
+/// * `{profile}` -- 當前構建配置
+/// * `{nick}` -- boot.json 的 nickName
+/// * `{game}` -- dependenceInfo 中 GameVersion 的版本
+pub(crate) const PLACEHOLDERS: [&str; 7] = [
+  "name",
+  "ver",
+  "hash",
+  "date",
+  "profile",
+  "nick",
+  "game",
+];
+
+/// Windows 文件名中不允許的字符
+const ILLEGAL_CHARS: [char; 9] = ['<', '>', ':', '"', '/', '\\', '|', '?', '*'];
+
+/// Windows 的保留文件名
+pub(crate) const RESERVED_NAMES: [&str; 22] = [
+  "CON",
+  "PRN",
+  "AUX",
+  "NUL",
+  "COM1",
+  "COM2",
+  "COM3",
+  "COM4",
+  "COM5",
+  "COM6",
+  "COM7",
+  "COM8",
+  "COM9",
+  "LPT1",
+  "LPT2",
+  "LPT3",
+  "LPT4",
+  "LPT5",
+  "LPT6",
+  "LPT7",
+  "LPT8",
+  "LPT9",
+];
+
+/// 模板中的一段
+#[derive(Debug, PartialEq)]
+enum Segment<'a> {
+  /// 原樣輸出的文本
+  Text(String),
+  /// `{key}` 或 `{key:arg}`
+  Placeholder(&'a str, Option<&'a str>),
+}
+
+/// 未知的佔位符
+pub(crate) fn unknown(key: &str) -> Error {
+  Error::Config(t!("template.unknown", name = key, list = PLACEHOLDERS.join(", ")).to_string())
+}
+
+/// 模板格式錯誤
+fn malformed(template: &str, pos: usize) -> Error {
+  Error::Config(t!("template.malformed", template = template, pos = pos).to_string())
+}
+
+/// 解析模板
+/// * `{{` 和 `}}` 表示字面的 `{` 和 `}`
+fn parse(template: &str) -> Result<Vec<Segment<'_>>, Error> {
+  let mut segments = Vec::new();
+  let mut text = String::new();
+  let mut chars = template.char_indices().peekable();
+
+  while let Some((i, c)) = chars.next() {
+    match c {
+      '{' if chars.peek().map(|(_, c)| *c) == Some('{') => {
+        chars.next();
+        text.push('{');
+      }
+      '}' if chars.peek().map(|(_, c)| *c) == Some('}') => {
+        chars.next();
+        text.push('}');
+      }
+      '{' => {
+        let end = template[i..]
+          .find('}')
+          .map(|e| i + e)
+          .ok_or_else(|| {
+            Error::Config(t!("template.unclosed", template = template, pos = i).to_string())
+          })?;
+        let inner = &template[i + 1..end];
+        if inner.is_empty() || inner.contains('{') {
+          return Err(malformed(template, i));
+        }
+        let (key, arg) = match inner.split_once(':') {
+          Some((key, arg)) => (key, Some(arg)),
+          None => (inner, None),
+        };
+        if !PLACEHOLDERS.contains(&key) {
+          return Err(unknown(key));
+        }
+        if arg.is_some() && key != "date" {
+          return Err(malformed(template, i));
+        }
+        if let Some(fmt) = arg {
+          use chrono::format::{ Item, StrftimeItems };
+          if StrftimeItems::new(fmt).any(|item| matches!(item, Item::Error)) {
+            return Err(Error::Config(t!("template.bad_date", fmt = fmt).to_string()));
+          }
+        }
+        if !text.is_empty() {
+          segments.push(Segment::Text(std::mem::take(&mut text)));
+        }
+        segments.push(Segment::Placeholder(key, arg));
+        while chars.peek().is_some_and(|(j, _)| *j <= end) {
+          chars.next();
+        }
+      }
+      '}' => {
+        return Err(malformed(template, i));
+      }
+      c => text.push(c),
+    }
+  }
+  if !text.is_empty() {
+    segments.push(Segment::Text(text));
+  }
+  Ok(segments)
+}
+
+/// 檢查模板格式和佔位符是否合法
+pub(crate) fn validate(template: &str) -> Result<(), Error> {
+  parse(template).map(|_| ())
+}
+
+/// 渲染模板並清理結果文件名
+/// * `template` - 模板字符串
+/// * `lookup` - 根據佔位符名稱返回值, 只在模板用到時調用; 不支持的佔位符返回 [`unknown`]
+pub(crate) fn render(
+  template: &str,
+  mut lookup: impl FnMut(&str) -> Result<String, Error>
+) -> Result<String, Error> {
+  let mut out = String::new();
+  for segment in parse(template)? {
+    match segment {
+      Segment::Text(text) => out.push_str(&text),
+      Segment::Placeholder("date", fmt) =>
+        out.push_str(&chrono::Local::now().format(fmt.unwrap_or("%Y-%m-%d")).to_string()),
+      Segment::Placeholder(key, _) => out.push_str(&lookup(key)?),
+    }
+  }
+  Ok(sanitize_file_name(&out))
+}
+
+/// 清理文件名中 Windows 不允許的部分
+/// * 非法字符和控制字符替換為 `_`
+/// * 去掉結尾的 `.` 和空格
+/// * 保留文件名(如 `CON`)前加 `_`
+pub(crate) fn sanitize_file_name(name: &str) -> String {
+  let mut name: String = name
+    .chars()
+    .map(|c| if ILLEGAL_CHARS.contains(&c) || c.is_control() { '_' } else { c })
+    .collect();
+  name.truncate(name.trim_end_matches(['.', ' ']).len());
+
+  let stem = name.split('.').next().unwrap_or_default().trim_end();
+  if RESERVED_NAMES.iter().any(|r| r.eq_ignore_ascii_case(stem)) {
+    name.insert(0, '_');
+  }
+  if name.is_empty() {
+    name.push('_');
+  }
+  name
+}
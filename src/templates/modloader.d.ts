@@ -0,0 +1,176 @@
+// ModLoader 和 SugarCube 的全局 API
+// 只聲明了常用的部分, 其他成員都是 any
+
+/** boot.json 中的 addon 插件 */
+interface ModBootJsonAddonPlugin<P = any> {
+  modName: string;
+  addonName: string;
+  modVersion: string;
+  params?: P;
+}
+
+/** boot.json 中的依賴 */
+interface ModBootJsonDependence {
+  modName: string;
+  version: string;
+}
+
+/** TweeReplacer 的參數 */
+interface TweeReplacerParam {
+  passage: string;
+  findString?: string;
+  findRegex?: string;
+  replace?: string;
+  replaceFile?: string;
+  all?: boolean;
+  debug?: boolean;
+}
+
+/** mod 的 boot.json */
+interface ModBootJson {
+  name: string;
+  version: string;
+  nickName?: string | Record<string, string>;
+  alias?: string[];
+  additionFile: string[];
+  imgFileList: string[];
+  scriptFileList: string[];
+  tweeFileList: string[];
+  styleFileList: string[];
+  scriptFileList_preload?: string[];
+  scriptFileList_earlyload?: string[];
+  scriptFileList_inject_early?: string[];
+  addonPlugin?: ModBootJsonAddonPlugin[];
+  dependenceInfo?: ModBootJsonDependence[];
+}
+
+/** 已加載的 mod */
+interface ModInfo<B extends ModBootJson = ModBootJson> {
+  name: string;
+  version: string;
+  bootJson: B;
+  /** 圖片路徑 */
+  imgs: { path: string; [key: string]: any }[];
+  /** 文件名 -> 內容 */
+  scriptFileList_preload: [string, string][];
+  scriptFileList_earlyload: [string, string][];
+  scriptFileList_inject_early: [string, string][];
+  [key: string]: any;
+}
+
+/** 劇情段落 */
+interface PassageDataItem {
+  name: string;
+  tags: string[];
+  content: string;
+  id?: number;
+  [key: string]: any;
+}
+
+/** `modUtils.getLogger()` 的返回值 */
+interface ModLoggerWrapper {
+  log(s: string): void;
+  warn(s: string): void;
+  error(s: string): void;
+}
+
+/** `window.modUtils` */
+interface ModUtils {
+  /** ModLoader 的版本 */
+  version: string;
+  getThisWindow(): Window;
+  getModListName(): string[];
+  getModListNameNoAlias(): string[];
+  getMod(name: string): ModInfo | undefined;
+  getAnyModByNameNoAlias(name: string): ModInfo | undefined;
+  getModZip(name: string): any;
+  /** 正在執行的 mod 腳本所屬的 mod */
+  getNowRunningModName(): string | undefined;
+  getPassageData(): Map<string, PassageDataItem>;
+  updatePassageData(name: string, content: string, tags?: string[], pid?: number): void;
+  getImage(path: string): Promise<string | undefined>;
+  getLogger(): ModLoggerWrapper;
+  getModLoader(): any;
+  getModLoadController(): any;
+  getAddonPluginManager(): any;
+  [key: string]: any;
+}
+
+/** `window.modSC2DataManager` */
+interface SC2DataManager {
+  getModLoader(): any;
+  getModUtils(): ModUtils;
+  getModLoadController(): any;
+  getAddonPluginManager(): any;
+  getSc2DataInfoCache(): any;
+  [key: string]: any;
+}
+
+/** `window.addonTweeReplacer` */
+interface TweeReplacerAddon {
+  [key: string]: any;
+}
+
+/** `window.modImgLoaderHooker` */
+interface ImgLoaderHooker {
+  [key: string]: any;
+}
+
+/** SugarCube 的 `State.variables`, 可以用同名接口擴展 */
+interface SugarCubeStoryVariables {
+  [key: string]: any;
+}
+
+/** SugarCube 的 `State.temporary`, 可以用同名接口擴展 */
+interface SugarCubeTemporaryVariables {
+  [key: string]: any;
+}
+
+/** SugarCube 的 `setup`, 可以用同名接口擴展 */
+interface SugarCubeSetupObject {
+  [key: string]: any;
+}
+
+/** SugarCube 的 `State` */
+interface SugarCubeStateAPI {
+  readonly variables: SugarCubeStoryVariables;
+  readonly temporary: SugarCubeTemporaryVariables;
+  readonly passage: string;
+  readonly turns: number;
+  readonly length: number;
+  has(passage: string): boolean;
+  hasPlayed(passage: string): boolean;
+  getVar(name: string): any;
+  setVar(name: string, value: any): boolean;
+  [key: string]: any;
+}
+
+interface Window {
+  modUtils: ModUtils;
+  modSC2DataManager: SC2DataManager;
+  addonTweeReplacer: TweeReplacerAddon;
+  modImgLoaderHooker: ImgLoaderHooker;
+  SugarCube: {
+    State: SugarCubeStateAPI;
+    setup: SugarCubeSetupObject;
+    [key: string]: any;
+  };
+}
+
+declare var modUtils: ModUtils;
+declare var modSC2DataManager: SC2DataManager;
+declare var addonTweeReplacer: TweeReplacerAddon;
+declare var modImgLoaderHooker: ImgLoaderHooker;
+declare var State: SugarCubeStateAPI;
+declare var setup: SugarCubeSetupObject;
+/** DoL 的 `V`, 即 `State.variables` */
+declare var V: SugarCubeStoryVariables;
+/** DoL 的 `T`, 即 `State.temporary` */
+declare var T: SugarCubeTemporaryVariables;
+
+/** 構建時替換為 boot.json 的 `name` */
+declare const __MOD_NAME__: string;
+/** 構建時替換為 boot.json 的 `version` */
+declare const __MOD_VERSION__: string;
+/** 構建時替換為 mod 源文件夾的 git 短哈希 */
+declare const __BUILD_HASH__: string;
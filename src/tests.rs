@@ -2,10 +2,55 @@
 
 #![cfg(test)]
 
-use std::path::Path;
+use std::path::{ Path, PathBuf };
+use std::sync::atomic::{ AtomicUsize, Ordering };
 
 use crate::boot_json::process_file_path;
 
+/// 測試用的臨時目錄, 名稱不重複, 離開作用域時刪除
+struct TempDir(PathBuf);
+
+impl TempDir {
+  fn new(name: &str) -> TempDir {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let n = COUNTER.fetch_add(1, Ordering::Relaxed);
+    let path = std::env::temp_dir().join(format!("mod-dev-test-{}-{}-{}", name, std::process::id(), n));
+    let _ = std::fs::remove_dir_all(&path);
+    std::fs::create_dir_all(&path).unwrap();
+    TempDir(path)
+  }
+}
+
+impl std::ops::Deref for TempDir {
+  type Target = Path;
+
+  fn deref(&self) -> &Path {
+    &self.0
+  }
+}
+
+impl Drop for TempDir {
+  fn drop(&mut self) {
+    let _ = std::fs::remove_dir_all(&self.0);
+  }
+}
+
+/// 測試用的配置, `extra` 中的字段覆蓋默認值
+fn test_cofg(extra: serde_json::Value) -> crate::cofg::Cofg {
+  let mut value = serde_json::json!({
+    "locale": "en",
+    "loglv": "info",
+    "path": { "tmp_path": "./tmp", "results_path": "./results", "mods_path": "./mods" },
+    "pause": false,
+    "ts_process": true,
+    "file_name": "{name}.zip"
+  });
+  if let (Some(value), serde_json::Value::Object(extra)) = (value.as_object_mut(), extra) {
+    value.extend(extra);
+  }
+  serde_json::from_value(value).unwrap()
+}
+
 #[test]
 fn test_process_file_path() {
   assert_eq!(
@@ -20,3 +65,931 @@ fn test_process_error_file_path() {
     "Failed to strip prefix: c:a/e from path: c:a/b/c/d".to_string()
   );
 }
+
+#[test]
+fn test_update_file_lists_early() {
+  use crate::boot_json::BootJson;
+  // 掃描到的腳本已經在 early 列表中時不加入 scriptFileList
+  let root = TempDir::new("early-lists");
+  std::fs::write(root.join("early.js"), "").unwrap();
+  std::fs::write(root.join("main.js"), "").unwrap();
+  let mut boot_json = BootJson::from_slice(br#"{"name":"a","version":"1","scriptFileList_earlyload":["early.js"]}"#).unwrap();
+  boot_json.update_file_lists(&root, false).unwrap();
+  let lists = boot_json.file_lists();
+  let scripts = lists.iter().find(|(list, _)| *list == "scriptFileList").unwrap().1;
+  assert_eq!(scripts, ["main.js"]);
+  let json = serde_json::to_value(&boot_json).unwrap();
+  assert_eq!(json["scriptFileList_earlyload"], serde_json::json!(["early.js"]));
+}
+
+#[test]
+fn test_apply_profile() {
+  let mut cofg = crate::cofg::Cofg::default();
+  cofg.profile = Some("release".to_string());
+  cofg.apply_profile().unwrap();
+  assert!(!cofg.source_map);
+  assert!(cofg.minify);
+  assert_eq!(cofg.compression_level, Some(9));
+  assert_eq!(cofg.file_name, "{name}.mod.zip");
+}
+#[test]
+fn test_apply_unknown_profile() {
+  let mut cofg = crate::cofg::Cofg::default();
+  cofg.profile = Some("nope".to_string());
+  assert!(cofg.apply_profile().is_err());
+}
+
+#[test]
+fn test_render_file_name() {
+  use crate::template::render;
+  let lookup = |key: &str| {
+    match key {
+      "name" => Ok("Mod:A?".to_string()),
+      "ver" => Ok("1.2.0".to_string()),
+      "game" => Ok("0.5.2.8".to_string()),
+      key => Err(crate::template::unknown(key)),
+    }
+  };
+  assert_eq!(render("{name}-{ver}.mod.zip", lookup).unwrap(), "Mod_A_-1.2.0.mod.zip");
+  assert_eq!(render("{{{game}}}.zip", lookup).unwrap(), "{0.5.2.8}.zip");
+  // 模板中合法但調用方不支持的佔位符返回錯誤而不是 panic
+  assert!(matches!(render("{nick}.zip", lookup), Err(crate::error::Error::Config(_))));
+}
+#[test]
+fn test_render_bad_file_name() {
+  use crate::template::validate;
+  assert!(validate("{name").is_err());
+  assert!(validate("name}").is_err());
+  assert!(validate("{}").is_err());
+  assert!(validate("{author}").is_err());
+  assert!(validate("{ver:%Y}").is_err());
+  assert!(validate("{date:%Q}").is_err());
+  assert!(validate("{date:%Y%m%d}-{hash}-{profile}-{nick}").is_ok());
+}
+#[test]
+fn test_sanitize_file_name() {
+  use crate::template::sanitize_file_name;
+  assert_eq!(sanitize_file_name("a<b>c|d*e"), "a_b_c_d_e");
+  assert_eq!(sanitize_file_name("mod. ."), "mod");
+  assert_eq!(sanitize_file_name("con.mod.zip"), "_con.mod.zip");
+  assert_eq!(sanitize_file_name("console.mod.zip"), "console.mod.zip");
+}
+
+#[test]
+fn test_find_collisions() {
+  use crate::collision::{ find_collisions, Collision };
+  let mods = [
+    ("a", Path::new("results/A.zip"), Path::new("mods/a")),
+    ("b", Path::new("results/b.zip"), Path::new("mods/b")),
+    ("a", Path::new("results/a.zip"), Path::new("mods/a2")),
+  ];
+  let collisions = find_collisions(mods);
+  assert_eq!(collisions, [
+    Collision::Name {
+      name: "a".to_string(),
+      first: "mods/a".into(),
+      second: "mods/a2".into(),
+    },
+    Collision::Output {
+      path: "results/a.zip".into(),
+      first: "mods/a".into(),
+      second: "mods/a2".into(),
+    },
+  ]);
+  assert_eq!(collisions[1].second(), Path::new("mods/a2"));
+  assert_eq!(collisions[1].to_warning().key, "collision.output");
+}
+
+#[test]
+fn test_mods_path() {
+  use crate::cofg::ModsPath;
+  let one: ModsPath = serde_json::from_str(r#""./mods""#).unwrap();
+  assert_eq!(one.patterns(), ["./mods"]);
+  let many: ModsPath = serde_json::from_str(r#"["./mods", "../*/mods"]"#).unwrap();
+  assert_eq!(many.patterns(), ["./mods", "../*/mods"]);
+  assert_eq!(serde_json::to_string(&many).unwrap(), r#"["./mods","../*/mods"]"#);
+}
+
+#[test]
+fn test_fill_placeholders() {
+  use crate::scaffold::{ fill_placeholders, to_ident };
+  assert_eq!(to_ident("My Mod: 2"), "My_Mod__2");
+  assert_eq!(to_ident("2nd"), "_2nd");
+  assert_eq!(
+    fill_placeholders(r#"{"name": "{{name}}"}"#, Path::new("boot.json"), "a\"b", "1"),
+    r#"{"name": "a\"b"}"#
+  );
+  assert_eq!(
+    fill_placeholders(":: {{id}} [widget] {{version}}", Path::new("a.twee"), "a b", "1.2"),
+    ":: a_b [widget] 1.2"
+  );
+}
+
+#[test]
+fn test_init_from_dir() {
+  use crate::boot_json::BootJson;
+  use crate::scaffold::init_from_dir;
+  let root = TempDir::new("init");
+  for rel in ["img", "js", "twee"] {
+    std::fs::create_dir_all(root.join(rel)).unwrap();
+  }
+  for rel in ["README.md", "img/a.png", "js/b.js", "js/a.js", "js/a.js.map", "style.css", "twee/main.twee", "notes.txt"] {
+    std::fs::write(root.join(rel), "").unwrap();
+  }
+  let cofg = test_cofg(serde_json::json!({ "source_map": false }));
+
+  let path = init_from_dir(&cofg, &root, Some("M".to_string()), Some("1.0.0".to_string()), false).unwrap();
+  let boot_json = BootJson::new(&path.to_string_lossy()).unwrap();
+  assert_eq!(boot_json.name, "M");
+  assert_eq!(boot_json.version.as_deref(), Some("1.0.0"));
+  assert_eq!(boot_json.file_lists(), [
+    ("additionFile", &["README.md".to_string()][..]),
+    ("imgFileList", &["img/a.png".to_string()][..]),
+    ("scriptFileList", &["js/a.js".to_string(), "js/b.js".to_string()][..]),
+    ("tweeFileList", &["twee/main.twee".to_string()][..]),
+    ("styleFileList", &["style.css".to_string()][..]),
+  ]);
+  assert!(!boot_json.in_list("notes.txt"));
+
+  // 已有 boot.json 時需要 force
+  let e = init_from_dir(&cofg, &root, Some("M".to_string()), Some("1.0.0".to_string()), false).unwrap_err();
+  assert!(matches!(e, crate::error::Error::Usage(_)));
+  assert!(init_from_dir(&cofg, &root, Some("N".to_string()), Some("1.0.0".to_string()), true).is_ok());
+}
+
+#[test]
+fn test_safe_join() {
+  use crate::fs_utils::safe_join;
+  let base = Path::new("mods/a");
+  assert_eq!(safe_join(base, "img/./b.png"), Some(base.join("img").join("b.png")));
+  assert_eq!(safe_join(base, "dist/../src/a.ts"), Some(base.join("src").join("a.ts")));
+  assert_eq!(safe_join(base, "../b/boot.json"), None);
+  assert_eq!(safe_join(base, "/etc/passwd"), None);
+  assert_eq!(safe_join(base, "C:\\x.js"), None);
+}
+
+#[test]
+fn test_parse_passages() {
+  use crate::inspect::parse_passages;
+  let twee = "ignored\n:: Start [tag] {\"position\":\"0,0\"}\nHello\n\n:: Esc\\[aped\\] name\nWorld\n";
+  assert_eq!(parse_passages(twee), [
+    ("Start".to_string(), "Hello".to_string()),
+    ("Esc[aped] name".to_string(), "World".to_string()),
+  ]);
+}
+#[test]
+fn test_format_size() {
+  use crate::inspect::format_size;
+  assert_eq!(format_size(12), "12 B");
+  assert_eq!(format_size(1536), "1.5 KiB");
+}
+
+#[test]
+fn test_verify_mod_zip() {
+  use std::io::Write;
+  use zip::write::SimpleFileOptions;
+  let root = TempDir::new("verify");
+  let zip_path = root.join("a.zip");
+  let mut zip = zip::ZipWriter::new(std::fs::File::create(&zip_path).unwrap());
+  let files = [
+    ("boot.json", r#"{"name":"a","scriptFileList":["a.js","b.js"]}"#),
+    ("a.js", ""),
+    ("img\\c.png", ""),
+  ];
+  for (name, content) in files {
+    zip.start_file(name, SimpleFileOptions::default()).unwrap();
+    zip.write_all(content.as_bytes()).unwrap();
+  }
+  zip.finish().unwrap();
+
+  let problems = crate::verify::verify_mod_zip(&zip_path).unwrap();
+  assert_eq!(problems, [
+    "Entry name uses backslashes: img\\c.png",
+    "Listed in scriptFileList but missing from the zip: b.js",
+    "In the zip but not in any list: img\\c.png",
+  ]);
+}
+
+#[test]
+fn test_import_mod() {
+  use std::io::Write;
+  use zip::write::SimpleFileOptions;
+  let root = TempDir::new("import");
+  let zip_path = root.join("a.zip");
+  let mut zip = zip::ZipWriter::new(std::fs::File::create(&zip_path).unwrap());
+  // 從 `./` 打包的zip, 條目名稱帶有 `./` 前綴
+  zip.add_directory("./", SimpleFileOptions::default()).unwrap();
+  for (name, content) in [("./boot.json", r#"{"name":"a","version":"1"}"#), ("./js/a.js", "a();")] {
+    zip.start_file(name, SimpleFileOptions::default()).unwrap();
+    zip.write_all(content.as_bytes()).unwrap();
+  }
+  zip.finish().unwrap();
+
+  let dir = crate::import::import_mod(&test_cofg(serde_json::json!({})), &zip_path, Some(&root), false).unwrap();
+  assert_eq!(dir, root.join("a"));
+  assert_eq!(std::fs::read_to_string(dir.join("js/a.js")).unwrap(), "a();");
+}
+
+#[test]
+fn test_lint_path() {
+  use crate::lint::lint_path;
+  assert!(lint_path("img/foo.png").is_empty());
+  assert!(lint_path("img/console.png").is_empty());
+  assert_eq!(lint_path("img/nul.png").len(), 1);
+  assert_eq!(lint_path("img./a.png").len(), 1);
+  assert_eq!(lint_path("img/cafe\u{301}.png").len(), 1);
+  assert!(lint_path("img/caf\u{e9}.png").is_empty());
+}
+
+#[test]
+fn test_lint_mod() {
+  use crate::lint::lint_mod;
+  let root = TempDir::new("lint");
+  std::fs::create_dir_all(root.join("img")).unwrap();
+  for rel in ["img/A.png", "img/a.png", "img/B.png"] {
+    std::fs::write(root.join(rel), "").unwrap();
+  }
+  let boot_json = crate::boot_json::BootJson
+    ::from_slice(br#"{"name":"a","version":"1","imgFileList":["img/A.png","img/a.png","img/b.png"]}"#)
+    .unwrap();
+  let keys: Vec<_> = lint_mod(&root, &boot_json).iter().map(|w| w.key).collect();
+  // 只有大小寫不同的文件都存在時不算大小寫不匹配
+  assert_eq!(keys, ["lint.case_collision", "lint.case_mismatch"]);
+}
+
+#[test]
+fn test_error_exit_code() {
+  use crate::error::Error;
+  let errors = [
+    Error::Config(String::new()),
+    Error::Io { path: "a".into(), source: std::io::ErrorKind::NotFound.into() },
+    Error::BootJson { path: "a".into(), msg: String::new(), pos: None },
+    Error::Tsc { path: "a".into(), errors: 1 },
+    Error::Verify { path: "a".into(), problems: vec![] },
+    Error::Collision(1),
+    Error::Usage(String::new()),
+    Error::DeniedWarnings(1),
+    Error::Hook { stage: String::new(), cmd: String::new(), msg: String::new() },
+    Error::Script { path: "a".into(), msg: String::new(), pos: None },
+  ];
+  let codes: std::collections::BTreeSet<i32> = errors.iter().map(Error::exit_code).collect();
+  assert_eq!(codes.len(), errors.len());
+  assert!(!codes.contains(&0) && !codes.contains(&1));
+}
+
+#[test]
+fn test_report() {
+  use crate::report::{ ModReport, Report };
+  let mut report = ModReport::default();
+  report.lists.insert("tweeFileList", 2);
+  report.warnings.push(crate::diagnostics::warning!("lint.not_nfc", path = "a"));
+  let error = crate::error::Error::Tsc { path: "tmp/a".into(), errors: 1 };
+  let json = serde_json::to_value(
+    Report::new(
+      None,
+      5,
+      (1, 1),
+      std::time::Instant::now(),
+      None,
+      [(Path::new("mods/a"), Some(&error), &report)]
+    )
+  ).unwrap();
+  assert_eq!((json["warnings"].clone(), json["errors"].clone()), (1.into(), 1.into()));
+  let entry = &json["mods"][0];
+  assert_eq!(entry["source"], "mods/a");
+  assert_eq!(entry["success"], false);
+  assert_eq!(entry["error"]["key"], "ts.tsc_error");
+  assert_eq!(entry["lists"]["tweeFileList"], 2);
+  assert_eq!(entry["warnings"][0]["key"], "lint.not_nfc");
+  assert!(entry.get("zip").is_none());
+}
+
+#[test]
+fn test_counts() {
+  use crate::error::Error;
+  let ctx = |error: Option<Error>| crate::ModCtx {
+    src: "mods/a".into(),
+    tmp: "tmp/a".into(),
+    error,
+    report: Default::default(),
+    order: None,
+  };
+  // tsc 失敗: 每個 tsc 錯誤計一次, 日誌中的其他錯誤行不計入
+  let mut tsc = ctx(Some(Error::Tsc { path: "tmp/a".into(), errors: 2 }));
+  tsc.report.tsc = Some(crate::report::TscReport {
+    success: false,
+    errors: 2,
+    diagnostics: crate::tsc::parse_output(
+      "a.ts(1,1): error TS1: a.\na.ts(2,1): error TS2: b.\na.ts(3,1): message TS3: c.\n"
+    ),
+  });
+  let mut warned = ctx(None);
+  warned.report.warnings.push(crate::diagnostics::warning!("lint.not_nfc", path = "a"));
+  let failed = ctx(Some(Error::Config(String::new())));
+  let all: Vec<_> = [tsc, warned, failed].iter().map(crate::ModCtx::diagnostics).collect();
+  assert_eq!(crate::diagnostics::counts(&all), (1, 3));
+
+  // --deny-warnings 只在沒有其他錯誤時決定退出碼
+  assert_eq!(crate::summarize(true, None, 1, 0), Some(9));
+  assert_eq!(crate::summarize(true, Some(5), 1, 1), Some(5));
+  assert_eq!(crate::summarize(true, None, 0, 0), None);
+  assert_eq!(crate::summarize(false, None, 1, 0), None);
+}
+
+#[test]
+fn test_render_diagnostics() {
+  use crate::diagnostics::{ render, Diagnostic, Format, ModDiagnostics, Severity };
+  let mods = [
+    ModDiagnostics {
+      name: "a&b".to_string(),
+      time_ms: 1500,
+      diagnostics: vec![Diagnostic {
+        severity: Severity::Error,
+        key: "errors.boot_json".to_string(),
+        message: "bad\n100%".to_string(),
+        file: Some("./mods/a,b/boot.json".into()),
+        line: Some(3),
+        column: Some(2),
+      }],
+    },
+    ModDiagnostics { name: "c".to_string(), time_ms: 0, diagnostics: vec![] },
+  ];
+  assert!(render(Format::Text, &mods).is_none());
+  assert_eq!(
+    render(Format::Github, &mods).unwrap(),
+    "::error file=mods/a%2Cb/boot.json,line=3,col=2,title=errors.boot_json::bad%0A100%25\n"
+  );
+  let sarif: serde_json::Value = serde_json::from_str(&render(Format::Sarif, &mods).unwrap()).unwrap();
+  let result = &sarif["runs"][0]["results"][0];
+  assert_eq!(result["level"], "error");
+  assert_eq!(result["locations"][0]["physicalLocation"]["region"]["startLine"], 3);
+  let junit = render(Format::Junit, &mods).unwrap();
+  assert!(junit.contains("tests=\"2\" failures=\"1\""));
+  assert!(junit.contains("<testcase name=\"a&amp;b\" classname=\"mods\" time=\"1.500\">"));
+}
+
+#[test]
+fn test_parse_tsc_output() {
+  use crate::tsc::parse_output;
+  let plain = parse_output(
+    "tmp/a/src/main.ts(3,7): error TS2322: Type 'string' is not assignable.\n  Did you mean 'x'?\n\
+     error TS5058: The specified path does not exist: 'tmp/b'.\n"
+  );
+  assert_eq!(plain.len(), 2);
+  assert_eq!(plain[0].file.as_deref(), Some(Path::new("tmp/a/src/main.ts")));
+  assert_eq!((plain[0].line, plain[0].column), (Some(3), Some(7)));
+  assert_eq!(plain[0].code.as_deref(), Some("TS2322"));
+  assert_eq!(plain[0].message, "Type 'string' is not assignable.\nDid you mean 'x'?");
+  assert!(plain[1].file.is_none() && plain[1].is_error());
+
+  let pretty = parse_output(
+    "\x1b[96mtmp/a/src/main.ts\x1b[0m:\x1b[93m5\x1b[0m:\x1b[93m1\x1b[0m - \x1b[91merror\x1b[0m TS2304: Cannot find name 'foo'.\n\n\
+     5 foo();\n  ~~~\n\nFound 1 error in tmp/a/src/main.ts:5\n"
+  );
+  assert_eq!(pretty.len(), 1);
+  assert_eq!((pretty[0].line, pretty[0].column), (Some(5), Some(1)));
+  assert_eq!(pretty[0].message, "Cannot find name 'foo'.");
+
+  let other = parse_output("Version 5.4.5\n");
+  assert_eq!(other[0].message, "Version 5.4.5");
+  assert!(!other[0].is_error());
+}
+
+#[test]
+fn test_compiler_for() {
+  let mut cofg = test_cofg(serde_json::json!({
+    "mods": { "a": { "compiler": { "command": "esbuild" } } }
+  }));
+  assert_eq!(cofg.compiler_for("b").command, "tsc");
+  assert_eq!(cofg.compiler_for("b").args, ["--project", "{build_dir}"]);
+  assert_eq!(cofg.compiler_for("a").command, "esbuild");
+  cofg.mods.clear();
+  assert_eq!(cofg.compiler_for("a").command, "tsc");
+
+  let compiler = |value: serde_json::Value| -> crate::cofg::CompilerCofg { serde_json::from_value(value).unwrap() };
+  assert!(cofg.compiler.accepts_tsc_args());
+  assert!(compiler(serde_json::json!({ "command": "builtin" })).accepts_tsc_args());
+  assert!(compiler(serde_json::json!({ "command": "./node_modules/.bin/tsc" })).accepts_tsc_args());
+  assert!(compiler(serde_json::json!({ "command": "tsc.cmd" })).accepts_tsc_args());
+  assert!(compiler(serde_json::json!({ "command": "npx", "args": ["--no-install", "tsc", "-p", "."] })).accepts_tsc_args());
+  assert!(compiler(serde_json::json!({ "command": "pnpm", "args": ["exec", "tsc"] })).accepts_tsc_args());
+  assert!(!compiler(serde_json::json!({ "command": "npx", "args": ["esbuild", "tsc"] })).accepts_tsc_args());
+  assert!(!compiler(serde_json::json!({ "command": "esbuild" })).accepts_tsc_args());
+  assert!(compiler(serde_json::json!({ "command": "esbuild", "accepts_tsc_args": true })).accepts_tsc_args());
+}
+
+#[test]
+#[cfg(feature = "builtin-ts")]
+fn test_strip_types() {
+  use crate::transpile::{ strip_types, Options };
+  let source = "\
+import { Foo, bar } from './lib';
+import type { Baz } from './baz';
+interface Point { x: number }
+type Id = string | number;
+declare const setup: any;
+export abstract class Shape<T> extends Base<T> implements Foo {
+  private readonly name?: string = 'a';
+  declare tag: Baz;
+  abstract area(): number;
+  static of(this: void, p: Point): Shape<Point> { return bar(p as any)!; }
+}
+function f(a?: number): void;
+function f(a?: number) { let n!: Id; return <any>a satisfies unknown; }
+";
+  let emit = strip_types(source, &Options::default()).unwrap();
+  assert_eq!(emit.prefix, 0);
+  let expected = "\
+import {      bar } from './lib';
+                                 
+                             
+                          
+                         
+export          class Shape    extends Base                   {
+                   name          = 'a';
+                   
+                          
+  static of(            p       )               { return bar(p       ) ; }
+}
+                             
+function f(a         ) { let n     ; return      a                  ; }
+";
+  assert_eq!(emit.code, expected);
+  assert!(emit.code.lines().zip(source.lines()).all(|(a, b)| a.len() == b.len()));
+
+  // 非模塊文件在 strict 時加 "use strict";
+  let options = Options { strict: true, ..Default::default() };
+  let emit = strip_types("let a: number = 1;\n", &options).unwrap();
+  assert_eq!(emit.code, "\"use strict\";let a         = 1;\n");
+  assert_eq!(emit.prefix, 13);
+
+  // 需要生成代碼的語法報錯
+  let errors = strip_types("const a = 1;\nenum E { A }\n", &Options::default()).unwrap_err();
+  assert_eq!((errors[0].line, errors[0].column), (Some(2), Some(1)));
+  assert!(errors[0].message.contains("enum"));
+  assert!(strip_types("let = ;", &Options::default()).is_err());
+}
+
+#[test]
+#[cfg(feature = "builtin-ts")]
+fn test_builtin_source_map() {
+  use crate::transpile::{ source_map, Emit };
+  use crate::tsc::strip_jsonc;
+  let emit = Emit { code: "let a = 1;\nfoo();".to_string(), prefix: 0 };
+  let map = source_map(
+    &emit,
+    Path::new("/m/out/a.js"),
+    Path::new("/m/out/a.js.map"),
+    Path::new("/m/src/a.ts"),
+    Some("let a: number = 1;\nfoo();")
+  );
+  let map: serde_json::Value = serde_json::from_str(&map).unwrap();
+  assert_eq!(map["file"], "a.js");
+  assert_eq!(map["sources"][0], "../src/a.ts");
+  assert_eq!(map["mappings"], "AAAA,IAAI,EAAE,EAAE;AACR");
+  assert_eq!(map["sourcesContent"][0], "let a: number = 1;\nfoo();");
+
+  // 加了 "use strict"; 時第一行向右移動
+  let emit = Emit { code: "\"use strict\";a;".to_string(), prefix: 13 };
+  let map = source_map(&emit, Path::new("a.js"), Path::new("a.js.map"), Path::new("a.ts"), None);
+  assert!(map.contains("\"mappings\":\"aAAA\""));
+
+  let json = strip_jsonc("{\n  // c\n  \"a\": [1, 2,], /* x */\n  \"b\": \"//,}\",\n}");
+  let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+  assert_eq!(json, serde_json::json!({ "a": [1, 2], "b": "//,}" }));
+}
+
+#[test]
+#[cfg(feature = "builtin-ts")]
+fn test_builtin_compile() {
+  use crate::transpile::compile;
+  let root = TempDir::new("builtin-compile");
+  std::fs::create_dir_all(root.join("src/lib")).unwrap();
+  std::fs::write(root.join("src/lib/a.ts"), "let a: number = 1;").unwrap();
+  let args = |extra: &[&str]| -> Vec<String> {
+    ["src/lib/a.ts", "--sourceMap", "--outDir", "dist/js"].iter().chain(extra).map(|s| s.to_string()).collect()
+  };
+
+  // 輸出目錄不存在時先創建, 再寫入 source map
+  assert!(compile(&args(&[]), &root).unwrap().is_empty());
+  assert_eq!(std::fs::read_to_string(root.join("dist/js/a.js")).unwrap(), "let a         = 1;\n//# sourceMappingURL=a.js.map");
+  assert!(root.join("dist/js/a.js.map").is_file());
+
+  // 需要語法降級的 target 報錯
+  let diagnostics = compile(&args(&["--target", "ES2020"]), &root).unwrap();
+  assert!(diagnostics[0].is_error() && diagnostics[0].message.contains("es2020"));
+  assert!(compile(&args(&["--target", "ESNext"]), &root).unwrap().is_empty());
+}
+
+#[test]
+fn test_build_dir_sync() {
+  use crate::build_dir::{ build_dir, sync_outputs, sync_sources };
+  // 按源文件夾的路徑區分, 不同 mods_path 下的同名mod不共用構建目錄
+  assert_eq!(build_dir("build", None, Path::new("./mods/a")), Path::new("build/default/mods/a"));
+  assert_eq!(build_dir("build", Some("dev"), Path::new("../x/mods/a")), Path::new("build/dev/__/x/mods/a"));
+  let root = TempDir::new("build-dir");
+  let (src, build, tmp) = (root.join("src"), root.join("build"), root.join("tmp"));
+  std::fs::create_dir_all(src.join("lib")).unwrap();
+  std::fs::create_dir_all(&tmp).unwrap();
+  std::fs::write(src.join("lib/a.ts"), "let a = 1;").unwrap();
+  std::fs::write(src.join("lib/a.js"), "stale").unwrap();
+  std::fs::write(src.join("boot.json"), "{}").unwrap();
+  std::fs::write(src.join("img.png"), "").unwrap();
+
+  sync_sources(&src, &build, &[]).unwrap();
+  assert!(build.join("lib/a.ts").is_file() && build.join("boot.json").is_file());
+  // 圖片不需要同步, ts 旁邊的舊輸出也不是源文件
+  assert!(!build.join("img.png").exists() && !build.join("lib/a.js").exists());
+
+  // 編譯輸出和緩存在下次同步後仍然保留
+  std::fs::write(build.join("lib/a.js"), "let a = 1;").unwrap();
+  std::fs::write(build.join("tsconfig.tsbuildinfo"), "{}").unwrap();
+  sync_sources(&src, &build, &[]).unwrap();
+  assert!(build.join("tsconfig.tsbuildinfo").is_file());
+  assert_eq!(sync_outputs(&src, &build, &tmp).unwrap(), ["lib/a.js"]);
+  assert_eq!(std::fs::read_to_string(tmp.join("lib/a.js")).unwrap(), "let a = 1;");
+
+  // 增刪源文件不清空, 刪除的源文件和它的輸出從構建目錄中刪除; node_modules 不同步
+  std::fs::create_dir_all(src.join("node_modules/x")).unwrap();
+  std::fs::write(src.join("node_modules/x/index.js"), "").unwrap();
+  std::fs::write(src.join("lib/b.ts"), "let b = 1;").unwrap();
+  sync_sources(&src, &build, &[]).unwrap();
+  assert!(build.join("lib/b.ts").is_file() && !build.join("node_modules").exists());
+  std::fs::write(build.join("lib/b.js"), "let b = 1;").unwrap();
+  std::fs::remove_file(src.join("lib/b.ts")).unwrap();
+  sync_sources(&src, &build, &[]).unwrap();
+  assert!(!build.join("lib/b.ts").exists() && !build.join("lib/b.js").exists());
+  assert!(build.join("tsconfig.tsbuildinfo").is_file() && build.join("lib/a.js").is_file());
+
+  // 參數變化時清空
+  sync_sources(&src, &build, &["--outDir".to_string(), "dist".to_string()]).unwrap();
+  assert!(!build.join("tsconfig.tsbuildinfo").exists());
+}
+
+#[test]
+fn test_typings() {
+  use crate::typings::{ TYPES_FILE, reference_types, render };
+  let dts = render(
+    &serde_json::json!({
+      "name": "A \"mod\"",
+      "version": "1.0.0",
+      "nickName": null,
+      "scriptFileList": ["a.js"],
+      "addonPlugin": [{ "modName": "TweeReplacer", "addonName": "TweeReplacerAddon", "modVersion": "^1.0.0", "params": [] }]
+    })
+  );
+  assert!(dts.contains("declare var modUtils: ModUtils;"));
+  assert!(dts.contains("  name: \"A \\\"mod\\\"\";"));
+  assert!(dts.contains("\"params\": TweeReplacerParam[]"));
+  assert!(dts.contains("getMod(name: \"A \\\"mod\\\"\"): ModInfo<ThisModBootJson> | undefined;"));
+  assert!(!dts.contains("scriptFileList: ["));
+  assert!(!dts.contains("  nickName: null;"));
+  #[cfg(feature = "builtin-ts")]
+  assert!(crate::transpile::strip_types(&dts, &Default::default()).is_ok());
+
+  let root = TempDir::new("typings");
+  let tsconfig = root.join("tsconfig.json");
+  let text = || std::fs::read_to_string(&tsconfig).unwrap();
+  let read = || serde_json::from_str::<serde_json::Value>(&crate::tsc::strip_jsonc(&text())).unwrap();
+
+  // 在原文中插入, 保留註釋和格式
+  std::fs::write(&tsconfig, "{\n  // c\n  \"include\": [\"src/**/*.ts\",],\n}").unwrap();
+  assert!(reference_types(&tsconfig).unwrap());
+  assert_eq!(text(), format!("{{\n  // c\n  \"include\": [\"src/**/*.ts\", \"{}\"],\n}}", TYPES_FILE));
+  assert!(!reference_types(&tsconfig).unwrap());
+  std::fs::write(&tsconfig, "{\n  \"a\": { \"include\": 1 },\n  \"include\": [\n    \"src\" /* ] */\n  ]\n}").unwrap();
+  assert!(reference_types(&tsconfig).unwrap());
+  assert_eq!(text(), format!("{{\n  \"a\": {{ \"include\": 1 }},\n  \"include\": [\n    \"src\",\n    \"{}\" /* ] */\n  ]\n}}", TYPES_FILE));
+
+  for include in ["./types", "types/*.d.ts", "**/*"] {
+    std::fs::write(&tsconfig, serde_json::json!({ "include": [include] }).to_string()).unwrap();
+    assert!(!reference_types(&tsconfig).unwrap(), "{include}");
+  }
+  std::fs::write(&tsconfig, "{ \"files\": [\"src/main.ts\"] }").unwrap();
+  assert!(reference_types(&tsconfig).unwrap());
+  assert_eq!(read()["files"], serde_json::json!(["src/main.ts", TYPES_FILE]));
+  std::fs::write(&tsconfig, "{}").unwrap();
+  assert!(!reference_types(&tsconfig).unwrap());
+}
+
+#[test]
+#[cfg(feature = "bundle")]
+fn test_bundle() {
+  use crate::bundle::bundle_mod;
+  use crate::cofg::BundleCofg;
+  let root = TempDir::new("bundle");
+  std::fs::create_dir_all(root.join("src/lib")).unwrap();
+  let write = |rel: &str, code: &str| std::fs::write(root.join(rel), code).unwrap();
+  write("src/lib/util.js", "export let n = 1;\nexport default function add(a, b) {\n  return a + b;\n}\n");
+  write("src/main.js", "import add, { n as m } from \"./lib/util\";\nimport { x } from \"./other.js\";\nadd(m, { m }.m, x);\n");
+  write("src/other.js", "export { n as x } from \"./lib/util.js\";\n");
+
+  // main 導入了 other, other 的 bundle 在前面
+  let cofg = BundleCofg { entries: vec!["src/main.js".to_string(), "./src/other.js".to_string()], out_dir: "dist".to_string() };
+  let bundled = bundle_mod(&root, "Test", &cofg, true).unwrap();
+  assert_eq!(bundled.bundles, ["dist/other.js", "dist/main.js"]);
+  assert_eq!(bundled.modules, ["src/lib/util.js", "src/main.js", "src/other.js"]);
+  assert!(!root.join("src/main.js").exists());
+
+  let other = std::fs::read_to_string(root.join("dist/other.js")).unwrap();
+  assert!(other.contains("__bundle_modules[\"src/lib/util.js\"]"));
+  assert!(other.contains("\"x\": function () { return __bundle_m0.n; }"));
+  let main = std::fs::read_to_string(root.join("dist/main.js")).unwrap();
+  assert!(!main.contains("__bundle_modules[\"src/lib/util.js\"]"));
+  assert!(main.contains("\n(0, __bundle_m0.default)(__bundle_m0.n, { m: __bundle_m0.n }.m, __bundle_m1.x);\n"));
+  assert!(main.ends_with("__bundle_require(\"src/main.js\");\n})();\n//# sourceMappingURL=main.js.map\n"));
+  let map: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(root.join("dist/main.js.map")).unwrap()).unwrap();
+  assert_eq!(map["sources"], serde_json::json!(["../src/main.js"]));
+
+  write("src/bare.js", "\nimport $ from \"jquery\";\n");
+  let cofg = BundleCofg { entries: vec!["src/bare.js".to_string()], out_dir: ".".to_string() };
+  let e = bundle_mod(&root, "Test", &cofg, false).unwrap_err();
+  assert_eq!(e.position(), Some((2, 15)));
+  for code in ["let f = () => import(\"./lib/util.js\");\n", "console.log(import.meta.url);\n"] {
+    write("src/bare.js", code);
+    let e = bundle_mod(&root, "Test", &cofg, false).unwrap_err();
+    assert!(e.to_string().contains("not supported in bundles"), "{e}");
+  }
+}
+
+#[test]
+#[cfg(any(feature = "builtin-ts", feature = "bundle"))]
+fn test_source_map_mappings() {
+  use crate::sourcemap::{ Segment, decode_mappings, encode_mappings };
+  let mappings = "AAAA,IAAI;;ACCA,EAAE";
+  let lines = decode_mappings(mappings);
+  assert_eq!(lines.len(), 3);
+  assert_eq!(lines[2][0], Segment { column: 0, source: 1, line: 1, original_column: 4 });
+  assert_eq!(lines[2][1], Segment { column: 2, source: 1, line: 1, original_column: 6 });
+  assert_eq!(encode_mappings(&lines), mappings);
+}
+
+#[test]
+fn test_order() {
+  use crate::cofg::{ OrderCofg, PriorityRule };
+  use crate::order::{ moved, natural_cmp, sort_list, track };
+  let strings = |list: &[&str]| list.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+  assert_eq!(natural_cmp("file2.js", "file10.js"), std::cmp::Ordering::Less);
+  assert_eq!(natural_cmp("a/File1.js", "a/file1.js"), std::cmp::Ordering::Less);
+  assert_eq!(natural_cmp("a.js", "a/b.js"), "a.js".cmp("a/b.js"));
+
+  // 原有的文件保持順序, 新文件按自然排序加在後面
+  let listed = strings(&["src/z.js", "src/b.js"]);
+  let mut list = strings(&["src/z.js", "src/file10.js", "src/b.js", "src/file2.js"]);
+  let mut order = OrderCofg { first: vec![], last: vec![], priority: vec![], natural: true };
+  sort_list(&mut list, &order, &listed).unwrap();
+  assert_eq!(list, strings(&["src/z.js", "src/b.js", "src/file2.js", "src/file10.js"]));
+
+  order.first = strings(&["src/file*.js"]);
+  order.last = strings(&["src/z.js"]);
+  order.priority = vec![PriorityRule { glob: "*.js".to_string(), priority: 5 }, PriorityRule { glob: "src/*.js".to_string(), priority: 1 }];
+  list.push("top.js".to_string());
+  sort_list(&mut list, &order, &listed).unwrap();
+  assert_eq!(list, strings(&["src/file2.js", "src/file10.js", "top.js", "src/b.js", "src/z.js"]));
+
+  let old = strings(&["a", "b", "c", "d"]);
+  assert_eq!(moved(&old, &strings(&["a", "b", "c", "d", "e"])), vec![]);
+  assert_eq!(moved(&old, &strings(&["d", "a", "c"])), vec![("d".to_string(), 4, 1)]);
+
+  let root = TempDir::new("order");
+  let state = root.join("mod.order.json");
+  let (warnings, order_state) = track(&state, &[("scriptFileList", &old)]);
+  assert!(warnings.is_empty());
+  // 保存之前不寫入, 失敗的構建不覆蓋上次的順序
+  assert!(!state.exists());
+  order_state.save().unwrap();
+  let (warnings, _) = track(&state, &[("scriptFileList", &strings(&["b", "a", "c", "d"]))]);
+  assert_eq!(warnings.len(), 1);
+  assert_eq!(warnings[0].key, "order.moved");
+  let (warnings, _) = track(&state, &[("scriptFileList", &strings(&["b", "a", "c", "d"]))]);
+  assert_eq!(warnings.len(), 1);
+}
+
+#[test]
+#[cfg(feature = "minify")]
+fn test_minify() {
+  use crate::minify::{ minify_css, minify_js };
+  use crate::sourcemap::Segment;
+  let js = "#!/usr/bin/env node\n/*! keep */\n// comment\nlet a = 1 /* x */ + +b;\nlet s = `  ${ a  +  1 }  `, r = /  a/g\nin_ = a\n++b\nreturn_ = 1 .toString();\nif (a) {\n  f(a,\n    \"  \");\n}\n//# sourceMappingURL=a.js.map\n";
+  let minified = minify_js(js).unwrap();
+  assert_eq!(
+    minified.code,
+    "#!/usr/bin/env node\n/*! keep */\nlet a=1+ +b;let s=`  ${a+1}  `,r=/  a/g\nin_=a\n++b\nreturn_=1 .toString();if(a){f(a,\"  \");}"
+  );
+  assert!(minify_js("let = ;").is_err());
+
+  // 每一行映射到自己, 壓縮後的每個詞對應原來的位置
+  let identity: Vec<Vec<Segment>> = (0..js.lines().count() as i64)
+    .map(|line| (0..40).map(|column| Segment { column, source: 0, line, original_column: column }).collect())
+    .collect();
+  let map = minified.map(&identity);
+  assert_eq!(map.len(), 6);
+  let at = |line: usize, column: i64| map[line].iter().rev().find(|s| s.column <= column).map(|s| (s.line, s.original_column));
+  // `let a=1+ +b;` 中的 `+b`
+  assert_eq!(at(2, 9), Some((3, 20)));
+  assert_eq!(at(5, 8), Some((7, 10)));
+
+  let css = "/* c */\n.a  > .b ,\n.c :hover {\n  color : red ;\n  background: url( a b.png ) no-repeat;\n  content: \"  ;  \";\n  width: calc(1px + 2px);\n}\n";
+  assert_eq!(
+    minify_css(css).code,
+    ".a>.b,.c :hover{color :red;background:url( a b.png ) no-repeat;content:\"  ;  \";width:calc(1px + 2px)}"
+  );
+  // 轉義的空白字符是選擇器的一部分
+  assert_eq!(minify_css(".a\\ b  .c\\\n{ }").code, ".a\\ b .c\\\n{}");
+}
+
+#[test]
+fn test_boot_json_early_lists() {
+  use crate::boot_json::BootJson;
+  let boot_json = BootJson::from_slice(br#"{"name":"a","version":"1","scriptFileList_earlyload":["early.js"]}"#).unwrap();
+  let lists: Vec<&str> = boot_json.file_lists().iter().map(|(list, _)| *list).collect();
+  assert!(lists.contains(&"scriptFileList_earlyload"));
+  assert!(!lists.contains(&"scriptFileList_preload"));
+  assert!(boot_json.in_list("early.js"));
+  let json = serde_json::to_value(&boot_json).unwrap();
+  assert_eq!(json["scriptFileList_earlyload"], serde_json::json!(["early.js"]));
+  assert!(json.get("scriptFileList_preload").is_none());
+}
+
+#[test]
+fn test_defines() {
+  use crate::defines::{ apply, replace_text, validate_name };
+  let defines: std::collections::BTreeMap<String, serde_json::Value> = serde_json::from_str(
+    r#"{ "__MOD_VERSION__": "1.2\"3", "DEBUG": false, "N": 2 }"#
+  ).unwrap();
+  #[cfg(feature = "defines")]
+  {
+    let replace_js = |code: &str| crate::defines::replace_js(code, &defines).unwrap();
+    assert_eq!(
+      replace_js("const v = __MOD_VERSION__, d = DEBUG; // __MOD_VERSION__\n"),
+      "const v = \"1.2\\\"3\", d = false; // 1.2\"3\n"
+    );
+    assert_eq!(replace_js("'v__MOD_VERSION__' + \"__MOD_VERSION__\""), "'v__MOD_VERSION__' + \"1.2\\\"3\"");
+    assert_eq!(replace_js("`${N}: __MOD_VERSION__ ${ { a: `${N}` }.a }`"), "`${2}: 1.2\"3 ${ { a: `${2}` }.a }`");
+    assert_eq!(replace_js("x = /N\"/g.test(a) / N; o.N2 = $N"), "x = /N\"/g.test(a) / 2; o.N2 = $N");
+    assert_eq!(
+      replace_js("f(a.N, a?.N, [...N], { N: N, N }, c ? N : { N }); class A { #N; N = N; m() { return this.#N; } }"),
+      "f(a.N, a?.N, [...2], { N: 2, N: 2 }, c ? 2 : { N: 2 }); class A { #N; N = 2; m() { return this.#N; } }"
+    );
+    // 本地綁定: 聲明, 參數, 解構和導入
+    assert_eq!(replace_js("let a = 1, N = a; f(N);"), "let a = 1, N = a; f(N);");
+    assert_eq!(
+      replace_js("function f(N) { return N; } const g = (DEBUG) => DEBUG; try {} catch (N) { N; } f(N, DEBUG);"),
+      "function f(N) { return N; } const g = (DEBUG) => DEBUG; try {} catch (N) { N; } f(2, false);"
+    );
+    assert_eq!(
+      replace_js("const { N } = o; let [a, DEBUG] = x; log(N, DEBUG); [a, N] = x;"),
+      "const { N } = o; let [a, DEBUG] = x; log(N, DEBUG); [a, N] = x;"
+    );
+    assert_eq!(replace_js("[b, N] = x; ({ DEBUG } = o); N++;"), "[b, N] = x; ({ DEBUG } = o); N++;");
+    assert_eq!(
+      replace_js("import { N } from \"./x\";\nexport { N };\nlog(N, DEBUG);"),
+      "import { N } from \"./x\";\nexport { N };\nlog(N, false);"
+    );
+    assert!(crate::defines::replace_js("let N = ;", &defines).is_err());
+  }
+  assert_eq!(replace_text(":: Start\nv__MOD_VERSION__ N DEBUG", &defines), ":: Start\nv__MOD_VERSION__ 2 false");
+  assert_eq!(replace_text("<<set $N to N>> $DEBUG", &defines), "<<set $N to 2>> $DEBUG");
+  assert!(validate_name("__A1$").is_ok());
+  assert!(validate_name("1A").is_err());
+  assert!(validate_name("a-b").is_err());
+
+  // 只替換 boot.json 的元數據和列表中的文件
+  let root = TempDir::new("defines");
+  std::fs::write(
+    root.join("boot.json"),
+    r#"{ "name": "M", "version": "1.0.0+__BUILD_HASH__", "additionFile": ["__MOD_VERSION__.txt"], "styleFileList": ["a.css"] }"#
+  ).unwrap();
+  std::fs::write(root.join("a.css"), ".a::after { content: \"__MOD_VERSION__\" }").unwrap();
+  std::fs::write(root.join("notes.txt"), "__MOD_VERSION__").unwrap();
+  #[cfg(feature = "defines")]
+  std::fs::write(root.join("a.js"), "log(__MOD_NAME__, __MOD_VERSION__);").unwrap();
+  let changed = apply(&root, &defines, "abc123").unwrap();
+  let boot_json: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(root.join("boot.json")).unwrap()).unwrap();
+  assert_eq!(boot_json["version"], "1.0.0+abc123");
+  assert_eq!(boot_json["additionFile"], serde_json::json!(["__MOD_VERSION__.txt"]));
+  assert_eq!(std::fs::read_to_string(root.join("a.css")).unwrap(), ".a::after { content: \"1.0.0+abc123\" }");
+  assert_eq!(std::fs::read_to_string(root.join("notes.txt")).unwrap(), "__MOD_VERSION__");
+  #[cfg(feature = "defines")]
+  {
+    assert_eq!(changed, ["boot.json", "a.css", "a.js"]);
+    assert_eq!(std::fs::read_to_string(root.join("a.js")).unwrap(), "log(\"M\", \"1.0.0+abc123\");");
+    std::fs::write(root.join("a.js"), "log(__MOD_NAME__;").unwrap();
+    let e = apply(&root, &defines, "abc123").unwrap_err();
+    assert_eq!((e.exit_code(), e.position()), (5, Some((1, 17))));
+  }
+  #[cfg(not(feature = "defines"))]
+  assert_eq!(changed, ["boot.json", "a.css"]);
+}
+
+#[test]
+fn test_hooks() {
+  use crate::error::Error;
+  use crate::hooks::{ run, validate_stage };
+  let cofg = test_cofg(serde_json::json!({
+    "hooks": { "ts": { "pre": ["a"], "post": ["b"] } },
+    "mods": { "m": { "hooks": { "ts": { "pre": ["c"] }, "compress": { "post": ["d"] } } } }
+  }));
+  assert_eq!(cofg.hooks_for("m", "ts", false), ["a", "c"]);
+  assert_eq!(cofg.hooks_for("m", "ts", true), ["b"]);
+  assert_eq!(cofg.hooks_for("x", "compress", true), Vec::<&str>::new());
+  assert_eq!(cofg.hooks_for("m", "compress", true), ["d"]);
+  assert!(validate_stage("boot_json", &[]).is_ok());
+  assert!(validate_stage("zip", &[]).is_err());
+  assert!(validate_stage("zip", &["zip"]).is_ok());
+
+  if cfg!(unix) {
+    let root = TempDir::new("hooks");
+    let env = [("MOD_DEV_MOD_NAME", "M".to_string())];
+    run("ts", "echo \"$MOD_DEV_MOD_NAME\" > out.txt", &root, &env).unwrap();
+    assert_eq!(std::fs::read_to_string(root.join("out.txt")).unwrap().trim(), "M");
+    run("ts", "echo warning >&2", &root, &env).unwrap();
+    let e = run("ts", "exit 3", &root, &env).unwrap_err();
+    assert!(matches!(&e, Error::Hook { cmd, .. } if cmd == "exit 3"));
+    assert_eq!(e.exit_code(), 10);
+  }
+}
+
+#[test]
+fn test_stage_validate() {
+  use crate::stage::validate;
+  let stages = |value: serde_json::Value| -> Vec<crate::cofg::StageCofg> { serde_json::from_value(value).unwrap() };
+  let ok = stages(serde_json::json!([
+    { "name": "a", "script": "a.rhai", "after": "ts" },
+    { "name": "b", "script": "b.rhai", "after": "a" }
+  ]));
+  assert!(validate(&ok).is_ok());
+  let unknown = stages(serde_json::json!([
+    { "name": "b", "script": "b.rhai", "after": "a" },
+    { "name": "a", "script": "a.rhai", "after": "ts" }
+  ]));
+  assert!(validate(&unknown).is_err());
+  let duplicate = stages(serde_json::json!([{ "name": "copy", "script": "a.rhai", "after": "ts" }]));
+  assert!(validate(&duplicate).is_err());
+}
+
+#[test]
+#[cfg(feature = "script")]
+fn test_script_stage() {
+  use crate::stage::pipeline;
+  let root = TempDir::new("script");
+  let src = root.join("mods/m");
+  let tmp = root.join("tmp/m");
+  std::fs::create_dir_all(src.join("js")).unwrap();
+  std::fs::create_dir_all(tmp.join("js")).unwrap();
+  std::fs::write(tmp.join("boot.json"), r#"{ "name": "M", "version": "1.0.0", "scriptFileList": ["js/a.js"] }"#).unwrap();
+  std::fs::write(tmp.join("js/a.js"), "a();").unwrap();
+  std::fs::write(tmp.join("js/old.js"), "old();").unwrap();
+  std::fs::write(
+    root.join("edit.rhai"),
+    r#"
+      for f in files() {
+        if f in boot.scriptFileList { write(f, "/* " + boot.name + " */" + read(f)); }
+      }
+      remove("js/old.js");
+      write("js/new.js", mod_dir);
+      boot.scriptFileList.push("js/new.js");
+      boot.version += "-x";
+      warn("done");
+    "#
+  ).unwrap();
+  std::fs::write(root.join("bad.rhai"), "write(\"js/a.js\", \"\");\nwrite(\"../x.js\", \"\");").unwrap();
+
+  let mut cofg = test_cofg(serde_json::json!({
+    "stages": [
+      { "name": "edit", "script": root.join("edit.rhai"), "after": "boot_json" },
+      { "name": "bad", "script": root.join("bad.rhai"), "after": "copy" },
+      { "name": "late", "script": root.join("bad.rhai"), "after": "boot_json", "mods": ["other"] }
+    ]
+  }));
+  let builtin = crate::builtin_stages();
+  let stages = pipeline(&cofg, builtin).unwrap();
+  let names: Vec<&str> = stages.iter().map(|s| s.name()).collect();
+  assert_eq!(names, ["copy", "bad", "ts", "defines", "bundle", "boot_json", "edit", "late", "minify", "compress"]);
+
+  let ctx = || crate::ModCtx { src: src.clone(), tmp: tmp.clone(), error: None, report: Default::default(), order: None };
+  // 出錯時不修改文件
+  let mut mods = vec![ctx()];
+  stages[1].run(&cofg, &mut mods).unwrap();
+  assert!(matches!(&mods[0].error, Some(crate::error::Error::Script { pos: Some((2, _)), .. })));
+  assert_eq!(std::fs::read_to_string(tmp.join("js/a.js")).unwrap(), "a();");
+  // 不在 mods 中的mod不處理
+  let mut mods = vec![ctx()];
+  stages[7].run(&cofg, &mut mods).unwrap();
+  assert!(mods[0].ok());
+
+  let mut mods = vec![ctx()];
+  stages[6].run(&cofg, &mut mods).unwrap();
+  assert!(mods[0].ok());
+  assert_eq!(mods[0].report.warnings.len(), 1);
+  assert!(mods[0].report.timings.contains_key("edit"));
+  assert_eq!(std::fs::read_to_string(tmp.join("js/a.js")).unwrap(), "/* M */a();");
+  assert_eq!(std::fs::read_to_string(tmp.join("js/new.js")).unwrap(), "m");
+  assert!(!tmp.join("js/old.js").exists());
+  let boot_json = crate::boot_json::BootJson::new(&tmp.join("boot.json").to_string_lossy()).unwrap();
+  assert_eq!(boot_json.version.as_deref(), Some("1.0.0-x"));
+  assert!(boot_json.in_list("js/new.js"));
+
+  cofg.stages[0].script = root.join("missing.rhai").display().to_string();
+  assert!(pipeline(&cofg, crate::builtin_stages()).is_err());
+}
@@ -0,0 +1,863 @@
+//! 內置的 TypeScript 編譯器 (`compiler.command = "builtin"`)
+//!
+//! 不需要 Node.js 和 tsc, 只去掉類型: 類型註解, `interface`, `type` 等換成同樣寬度的空格,
+//! 所以輸出的 JS 和源文件的行, 列完全相同, source map 只需要一一對應。
+//! 不做類型檢查和語法降級, 需要生成代碼的語法 (`enum`, `namespace`, 參數屬性, 裝飾器等) 會報錯。
+
+use std::path::{ Path, PathBuf };
+use glob::{ Pattern, glob };
+use log::debug;
+use oxc_allocator::Allocator;
+use oxc_ast::ast::*;
+use oxc_ast_visit::{ Visit, walk };
+use oxc_parser::Parser;
+use oxc_semantic::{ ScopeFlags, Scoping, SemanticBuilder };
+use oxc_span::{ GetSpan, SourceType, Span };
+use rust_i18n::t;
+use serde_json::{ Value, json };
+use crate::error::{ Error, WithPath };
+use crate::sourcemap::{ base64, position, relative, vlq };
+use crate::tsc::{ TscDiagnostic, strip_jsonc };
+
+/// 支持的布爾選項 (小寫)
+const BOOL_OPTIONS: [&str; 7] = [
+  "sourcemap",
+  "inlinesourcemap",
+  "inlinesources",
+  "noemit",
+  "strict",
+  "alwaysstrict",
+  "usedefineforclassfields",
+];
+/// 支持的字符串選項 (小寫)
+const STRING_OPTIONS: [&str; 4] = ["outdir", "rootdir", "target", "module"];
+/// 支持的 target (小寫): 不做語法降級, 只有不需要降級的 target 才能保證輸出符合 target
+const SUPPORTED_TARGETS: [&str; 4] = ["es2022", "es2023", "es2024", "esnext"];
+/// 輸出 CommonJS 等模塊代碼的 module
+const WRAPPED_MODULES: [&str; 4] = ["commonjs", "amd", "umd", "system"];
+/// 類成員上只有 TypeScript 才有的修飾符
+const TS_MODIFIERS: [&str; 7] = [
+  "public",
+  "private",
+  "protected",
+  "readonly",
+  "override",
+  "declare",
+  "abstract",
+];
+
+/// 編譯選項, 來自 tsconfig.json 的 `compilerOptions` 和命令行參數
+#[derive(Debug, Default, Clone)]
+pub(crate) struct Options {
+  pub source_map: bool,
+  pub inline_source_map: bool,
+  pub inline_sources: bool,
+  pub out_dir: Option<PathBuf>,
+  pub root_dir: Option<PathBuf>,
+  /// 如 `es2020`, 未設置時不檢查
+  pub target: Option<String>,
+  /// 如 `esnext`, 未設置時視為 ES 模塊
+  pub module: Option<String>,
+  pub no_emit: bool,
+  pub strict: bool,
+  pub always_strict: Option<bool>,
+  pub use_define_for_class_fields: Option<bool>,
+}
+
+impl Options {
+  /// 設置一個選項, 鍵不區分大小寫
+  /// * `base` - 相對路徑的基準目錄
+  /// # 返回
+  /// * 不支持的選項返回 `false`
+  fn set(&mut self, key: &str, value: &Value, base: &Path) -> bool {
+    let flag = value.as_bool().unwrap_or_default();
+    let text = value.as_str().map(str::to_lowercase);
+    let path = || value.as_str().map(|p| base.join(p));
+    match key.to_lowercase().as_str() {
+      "sourcemap" => {
+        self.source_map = flag;
+      }
+      "inlinesourcemap" => {
+        self.inline_source_map = flag;
+      }
+      "inlinesources" => {
+        self.inline_sources = flag;
+      }
+      "noemit" => {
+        self.no_emit = flag;
+      }
+      "strict" => {
+        self.strict = flag;
+      }
+      "alwaysstrict" => {
+        self.always_strict = Some(flag);
+      }
+      "usedefineforclassfields" => {
+        self.use_define_for_class_fields = Some(flag);
+      }
+      "outdir" => {
+        self.out_dir = path();
+      }
+      "rootdir" => {
+        self.root_dir = path();
+      }
+      "target" => {
+        self.target = text;
+      }
+      "module" => {
+        self.module = text;
+      }
+      _ => {
+        return false;
+      }
+    }
+    true
+  }
+
+  /// 是否在非模塊文件開頭加 `"use strict";`
+  fn always_strict(&self) -> bool {
+    self.always_strict.unwrap_or(self.strict)
+  }
+
+  /// 沒有初始值的類字段是否保留 (ES2022 的 define 語義)
+  fn use_define(&self) -> bool {
+    self.use_define_for_class_fields.unwrap_or_else(|| {
+      match self.target.as_deref() {
+        None | Some("esnext") => true,
+        Some(target) =>
+          target
+            .strip_prefix("es")
+            .and_then(|year| year.parse::<u32>().ok())
+            .is_some_and(|year| year >= 2022),
+      }
+    })
+  }
+
+  /// 是否輸出 ES 模塊 (而不是 CommonJS 等)
+  fn es_module(&self) -> bool {
+    !self.module.as_deref().is_some_and(|m| WRAPPED_MODULES.contains(&m) || m == "none")
+  }
+}
+
+/// 命令行參數
+#[derive(Debug, Default)]
+struct Args {
+  /// `--project`
+  project: Option<PathBuf>,
+  /// 直接指定的文件, 指定時不讀取 tsconfig.json
+  files: Vec<PathBuf>,
+  options: Vec<(String, Value)>,
+}
+
+/// 解析 tsc 風格的命令行參數, 不支持的選項只記錄在 debug 日誌中
+fn parse_args(args: &[String], cwd: &Path) -> Args {
+  let mut parsed = Args::default();
+  let mut iter = args.iter().peekable();
+  while let Some(arg) = iter.next() {
+    let Some(name) = arg.strip_prefix("--").or_else(|| arg.strip_prefix('-')) else {
+      parsed.files.push(cwd.join(arg));
+      continue;
+    };
+    let key = name.to_lowercase();
+    if key == "project" || key == "p" {
+      parsed.project = iter.next().map(|p| cwd.join(p));
+    } else if BOOL_OPTIONS.contains(&key.as_str()) {
+      let value = match iter.peek().map(|v| v.as_str()) {
+        Some("true") | Some("false") => iter.next().is_some_and(|v| v == "true"),
+        _ => true,
+      };
+      parsed.options.push((key, Value::Bool(value)));
+    } else if STRING_OPTIONS.contains(&key.as_str()) {
+      if let Some(value) = iter.next() {
+        parsed.options.push((key, Value::String(value.clone())));
+      }
+    } else {
+      debug!("{}", t!("ts.builtin_ignored", option = arg));
+      // 不知道是否帶值, 下一個參數不像選項或文件時當作值
+      if iter.peek().is_some_and(|v| !v.starts_with('-') && !v.ends_with(".ts")) {
+        iter.next();
+      }
+    }
+  }
+  parsed
+}
+
+/// 一條錯誤, 位置由調用者填寫
+fn error(message: String) -> TscDiagnostic {
+  TscDiagnostic {
+    file: None,
+    line: None,
+    column: None,
+    category: "error".to_string(),
+    code: None,
+    message,
+  }
+}
+
+/// 去掉類型後的代碼
+#[derive(Debug)]
+pub(crate) struct Emit {
+  pub code: String,
+  /// 在第一行前面加的 `"use strict";` 的長度
+  pub prefix: usize,
+}
+
+/// 收集要換成空格的範圍
+struct Stripper<'s> {
+  source: &'s str,
+  scoping: &'s Scoping,
+  /// 是否保留沒有初始值的類字段
+  use_define: bool,
+  blanks: Vec<(u32, u32)>,
+  /// (位置, 消息)
+  errors: Vec<(u32, String)>,
+  /// 輸出中保留的頂層 import/export 數量
+  module_items: usize,
+}
+
+impl<'s> Stripper<'s> {
+  fn blank(&mut self, start: u32, end: u32) {
+    if start < end {
+      self.blanks.push((start, end));
+    }
+  }
+
+  fn blank_span(&mut self, span: Span) {
+    self.blank(span.start, span.end);
+  }
+
+  fn unsupported(&mut self, span: Span, what: &str) {
+    self.errors.push((span.start, t!("ts.builtin_unsupported", what = what).to_string()));
+  }
+
+  /// 從 `from` 開始跳過空白和 `]`, 下一個字符是 `c` 時把它換成空格
+  /// # 返回
+  /// * 是否找到
+  fn blank_next(&mut self, from: u32, c: char) -> bool {
+    let rest = &self.source[from as usize..];
+    let skipped = rest.len() - rest.trim_start_matches(|c: char| c.is_whitespace() || c == ']').len();
+    if rest[skipped..].starts_with(c) {
+      let at = from + (skipped as u32);
+      self.blank(at, at + 1);
+      return true;
+    }
+    false
+  }
+
+  /// 去掉 `[start, key)` 之間的 `public`, `readonly` 等修飾符
+  fn blank_modifiers(&mut self, start: u32, decorators: &[Decorator], key: u32) {
+    let start = decorators.last().map_or(start, |d| d.span.end);
+    let text = &self.source[start as usize..key as usize];
+    let mut offset = 0;
+    for word in text.split(|c: char| !(c.is_alphanumeric() || c == '_' || c == '$')) {
+      if TS_MODIFIERS.contains(&word) {
+        let at = start + (offset as u32);
+        self.blank(at, at + (word.len() as u32));
+      }
+      offset += word.len() + 1;
+    }
+  }
+
+  /// 綁定是否作為值使用過 (而不是只在類型中使用)
+  fn used_as_value(&self, ident: &BindingIdentifier) -> bool {
+    match ident.symbol_id.get() {
+      Some(symbol) => self.scoping.get_resolved_references(symbol).any(|r| r.is_value()),
+      None => true,
+    }
+  }
+
+  /// `export { Foo }` 中的 `Foo` 是否只是類型
+  fn is_type_symbol(&self, name: &ModuleExportName) -> bool {
+    let ModuleExportName::IdentifierReference(ident) = name else {
+      return false;
+    };
+    ident.reference_id
+      .get()
+      .and_then(|id| self.scoping.get_reference(id).symbol_id())
+      .is_some_and(|symbol| {
+        let flags = self.scoping.symbol_flags(symbol);
+        flags.is_type() && !flags.is_value()
+      })
+  }
+}
+
+/// 聲明是否只有類型, 輸出時整個去掉
+fn declaration_is_type_only(decl: &Declaration) -> bool {
+  match decl {
+    Declaration::VariableDeclaration(d) => d.declare,
+    Declaration::FunctionDeclaration(f) => f.declare || f.body.is_none(),
+    Declaration::ClassDeclaration(c) => c.declare,
+    Declaration::TSTypeAliasDeclaration(_) |
+    Declaration::TSInterfaceDeclaration(_) |
+    Declaration::TSGlobalDeclaration(_) => true,
+    Declaration::TSEnumDeclaration(e) => e.declare,
+    Declaration::TSModuleDeclaration(m) => module_is_type_only(m),
+    Declaration::TSImportEqualsDeclaration(d) => d.import_kind.is_type(),
+  }
+}
+
+/// `namespace` 是否只有類型
+fn module_is_type_only(module: &TSModuleDeclaration) -> bool {
+  module.declare ||
+    (match &module.body {
+      None => true,
+      Some(TSModuleDeclarationBody::TSModuleDeclaration(inner)) => module_is_type_only(inner),
+      Some(TSModuleDeclarationBody::TSModuleBlock(block)) => block.body.iter().all(statement_is_type_only),
+    })
+}
+
+/// 語句是否只有類型, 輸出時整個去掉
+fn statement_is_type_only(stmt: &Statement) -> bool {
+  match stmt {
+    Statement::ImportDeclaration(i) => i.import_kind.is_type(),
+    Statement::ExportNamedDeclaration(e) =>
+      e.export_kind.is_type() || e.declaration.as_ref().is_some_and(declaration_is_type_only),
+    Statement::ExportDefaultDeclaration(e) =>
+      match &e.declaration {
+        ExportDefaultDeclarationKind::FunctionDeclaration(f) => f.body.is_none(),
+        ExportDefaultDeclarationKind::TSInterfaceDeclaration(_) => true,
+        _ => false,
+      }
+    Statement::ExportAllDeclaration(e) => e.export_kind.is_type(),
+    Statement::TSNamespaceExportDeclaration(_) => true,
+    _ => stmt.as_declaration().is_some_and(declaration_is_type_only),
+  }
+}
+
+impl<'a> Visit<'a> for Stripper<'_> {
+  fn visit_statement(&mut self, it: &Statement<'a>) {
+    if statement_is_type_only(it) {
+      self.blank_span(it.span());
+      return;
+    }
+    if it.is_module_declaration() {
+      self.module_items += 1;
+    }
+    walk::walk_statement(self, it);
+  }
+
+  fn visit_import_declaration(&mut self, it: &ImportDeclaration<'a>) {
+    let Some(specifiers) = it.specifiers.as_ref().filter(|s| !s.is_empty()) else {
+      return;
+    };
+    // 和 tsc 一樣去掉只作為類型使用的導入
+    let keep: Vec<bool> = specifiers
+      .iter()
+      .map(|s| {
+        match s {
+          ImportDeclarationSpecifier::ImportSpecifier(s) =>
+            !s.import_kind.is_type() && self.used_as_value(&s.local),
+          ImportDeclarationSpecifier::ImportDefaultSpecifier(s) => self.used_as_value(&s.local),
+          ImportDeclarationSpecifier::ImportNamespaceSpecifier(s) => self.used_as_value(&s.local),
+        }
+      })
+      .collect();
+    if !keep.contains(&true) {
+      self.blank_span(it.span);
+      self.module_items -= 1;
+      return;
+    }
+    for (i, s) in specifiers.iter().enumerate().filter(|(i, _)| !keep[*i]) {
+      match s {
+        // `import a, * as b`: 連同前面的逗號一起去掉
+        ImportDeclarationSpecifier::ImportNamespaceSpecifier(_) if i > 0 => {
+          self.blank(specifiers[i - 1].span().end, s.span().end);
+        }
+        _ => {
+          self.blank_span(s.span());
+          self.blank_next(s.span().end, ',');
+        }
+      }
+    }
+  }
+
+  fn visit_export_named_declaration(&mut self, it: &ExportNamedDeclaration<'a>) {
+    for s in &it.specifiers {
+      if s.export_kind.is_type() || (it.source.is_none() && self.is_type_symbol(&s.local)) {
+        self.blank_span(s.span);
+        self.blank_next(s.span.end, ',');
+      }
+    }
+    walk::walk_export_named_declaration(self, it);
+  }
+
+  fn visit_ts_type_annotation(&mut self, it: &TSTypeAnnotation<'a>) {
+    self.blank_span(it.span);
+  }
+
+  fn visit_ts_type_parameter_declaration(&mut self, it: &TSTypeParameterDeclaration<'a>) {
+    self.blank_span(it.span);
+  }
+
+  fn visit_ts_type_parameter_instantiation(&mut self, it: &TSTypeParameterInstantiation<'a>) {
+    self.blank_span(it.span);
+  }
+
+  fn visit_ts_as_expression(&mut self, it: &TSAsExpression<'a>) {
+    self.blank(it.expression.span().end, it.span.end);
+    self.visit_expression(&it.expression);
+  }
+
+  fn visit_ts_satisfies_expression(&mut self, it: &TSSatisfiesExpression<'a>) {
+    self.blank(it.expression.span().end, it.span.end);
+    self.visit_expression(&it.expression);
+  }
+
+  fn visit_ts_type_assertion(&mut self, it: &TSTypeAssertion<'a>) {
+    self.blank(it.span.start, it.expression.span().start);
+    self.visit_expression(&it.expression);
+  }
+
+  fn visit_ts_non_null_expression(&mut self, it: &TSNonNullExpression<'a>) {
+    self.blank(it.span.end - 1, it.span.end);
+    self.visit_expression(&it.expression);
+  }
+
+  fn visit_function(&mut self, it: &Function<'a>, flags: ScopeFlags) {
+    if let Some(this) = &it.this_param {
+      self.blank_span(this.span);
+      self.blank_next(this.span.end, ',');
+    }
+    walk::walk_function(self, it, flags);
+  }
+
+  fn visit_formal_parameter(&mut self, it: &FormalParameter<'a>) {
+    if it.accessibility.is_some() || it.readonly || it.r#override {
+      self.unsupported(it.span, "parameter property");
+    }
+    if it.optional {
+      self.blank_next(it.pattern.span().end, '?');
+    }
+    walk::walk_formal_parameter(self, it);
+  }
+
+  fn visit_variable_declarator(&mut self, it: &VariableDeclarator<'a>) {
+    if it.definite {
+      self.blank_next(it.id.span().end, '!');
+    }
+    walk::walk_variable_declarator(self, it);
+  }
+
+  fn visit_class(&mut self, it: &Class<'a>) {
+    if it.r#abstract {
+      let start = it.span.start as usize;
+      if self.source[start..].starts_with("abstract") {
+        self.blank(it.span.start, it.span.start + 8);
+      } else if let Some(at) = self.source[..start].trim_end().strip_suffix("abstract").map(str::len) {
+        self.blank(at as u32, (at + 8) as u32);
+      }
+    }
+    if let (Some(first), Some(last)) = (it.implements.first(), it.implements.last()) {
+      let keyword = self.source[..first.span.start as usize].rfind("implements");
+      self.blank(keyword.map_or(first.span.start, |k| k as u32), last.span.end);
+    }
+    walk::walk_class(self, it);
+  }
+
+  fn visit_class_body(&mut self, it: &ClassBody<'a>) {
+    for element in &it.body {
+      match element {
+        ClassElement::TSIndexSignature(s) => {
+          self.blank_span(s.span);
+          continue;
+        }
+        ClassElement::MethodDefinition(m) => {
+          if m.r#type == MethodDefinitionType::TSAbstractMethodDefinition || m.value.body.is_none() {
+            self.blank_span(m.span);
+            continue;
+          }
+          self.blank_modifiers(m.span.start, &m.decorators, m.key.span().start);
+          if m.optional {
+            self.blank_next(m.key.span().end, '?');
+          }
+        }
+        ClassElement::PropertyDefinition(p) => {
+          let abstract_ = p.r#type == PropertyDefinitionType::TSAbstractPropertyDefinition;
+          if abstract_ || p.declare || (p.value.is_none() && !self.use_define) {
+            self.blank_span(p.span);
+            continue;
+          }
+          self.blank_modifiers(p.span.start, &p.decorators, p.key.span().start);
+          if p.optional {
+            self.blank_next(p.key.span().end, '?');
+          }
+          if p.definite {
+            self.blank_next(p.key.span().end, '!');
+          }
+        }
+        ClassElement::AccessorProperty(p) => {
+          if p.r#type == AccessorPropertyType::TSAbstractAccessorProperty {
+            self.blank_span(p.span);
+            continue;
+          }
+          self.blank_modifiers(p.span.start, &p.decorators, p.key.span().start);
+          if p.definite {
+            self.blank_next(p.key.span().end, '!');
+          }
+        }
+        ClassElement::StaticBlock(_) => {}
+      }
+      self.visit_class_element(element);
+    }
+  }
+
+  fn visit_decorator(&mut self, it: &Decorator<'a>) {
+    self.unsupported(it.span, "decorator");
+  }
+
+  fn visit_ts_enum_declaration(&mut self, it: &TSEnumDeclaration<'a>) {
+    self.unsupported(it.span, "enum");
+  }
+
+  fn visit_ts_module_declaration(&mut self, it: &TSModuleDeclaration<'a>) {
+    self.unsupported(it.span, "namespace");
+  }
+
+  fn visit_ts_import_equals_declaration(&mut self, it: &TSImportEqualsDeclaration<'a>) {
+    self.unsupported(it.span, "import = require()");
+  }
+
+  fn visit_ts_export_assignment(&mut self, it: &TSExportAssignment<'a>) {
+    self.unsupported(it.span, "export =");
+  }
+}
+
+/// 把一個 TypeScript 文件轉換為 JavaScript
+/// * 輸出與源文件的行, 列相同, 只有加了 `"use strict";` 時第一行向右移動
+/// # 錯誤
+/// * 語法錯誤和不支持的語法, 帶行和列
+pub(crate) fn strip_types(source: &str, options: &Options) -> Result<Emit, Vec<TscDiagnostic>> {
+  let allocator = Allocator::default();
+  let source_type = SourceType::ts().with_unambiguous(true);
+  let ret = Parser::new(&allocator, source, source_type).parse();
+  let located = |offset: usize, message: String| {
+    let (line, column) = position(source, offset);
+    TscDiagnostic { line: Some(line), column: Some(column), ..error(message) }
+  };
+  if !ret.errors.is_empty() {
+    return Err(
+      ret.errors
+        .iter()
+        .map(|e| {
+          let offset = e.labels
+            .as_ref()
+            .and_then(|labels| labels.first())
+            .map_or(0, |label| label.offset());
+          located(offset, e.message.to_string())
+        })
+        .collect()
+    );
+  }
+
+  let program = ret.program;
+  let semantic = SemanticBuilder::new().build(&program).semantic;
+  let mut stripper = Stripper {
+    source,
+    scoping: semantic.scoping(),
+    use_define: options.use_define(),
+    blanks: Vec::new(),
+    errors: Vec::new(),
+    module_items: 0,
+  };
+  stripper.visit_program(&program);
+
+  let has_module_syntax = ret.module_record.has_module_syntax;
+  if stripper.module_items > 0 && !options.es_module() {
+    let module = options.module.as_deref().unwrap_or_default();
+    stripper.errors.push((0, t!("ts.builtin_module", module = module).to_string()));
+  }
+  if !stripper.errors.is_empty() {
+    stripper.errors.sort_by_key(|(offset, _)| *offset);
+    return Err(
+      stripper.errors
+        .into_iter()
+        .map(|(offset, message)| located(offset as usize, message))
+        .collect()
+    );
+  }
+
+  let mut blanks = stripper.blanks;
+  blanks.sort();
+  let mut code = String::with_capacity(source.len() + 16);
+  // ES 模塊本身就是嚴格模式
+  let module_output = has_module_syntax && options.es_module();
+  let has_directive = program.directives.iter().any(|d| d.directive == "use strict");
+  if options.always_strict() && !module_output && !has_directive {
+    code.push_str("\"use strict\";");
+  }
+  let prefix = code.len();
+  let mut blank = blanks.iter().peekable();
+  for (i, c) in source.char_indices() {
+    let i = i as u32;
+    while blank.next_if(|(_, end)| *end <= i).is_some() {}
+    let blanked = blank.peek().is_some_and(|(start, _)| *start <= i);
+    match c {
+      _ if !blanked => code.push(c),
+      '\n' | '\r' | '\u{2028}' | '\u{2029}' => code.push(c),
+      _ => code.extend(std::iter::repeat_n(' ', c.len_utf16())),
+    }
+  }
+  // 和 tsc 一樣, 導入全部去掉後仍然是模塊
+  if module_output && stripper.module_items == 0 {
+    code.push_str("\nexport {};");
+  }
+  Ok(Emit { code, prefix })
+}
+
+/// 生成 source map 的 `mappings`
+/// * 輸出和源文件位置相同, 在每個單詞的開頭加一個映射
+fn mappings(emit: &Emit) -> String {
+  let mut out = String::new();
+  let (mut prev_line, mut prev_column) = (0i64, 0i64);
+  for (line, text) in emit.code.split('\n').enumerate() {
+    if line > 0 {
+      out.push(';');
+    }
+    let shift = if line == 0 { emit.prefix } else { 0 };
+    let mut prev_generated = 0i64;
+    let mut column = 0;
+    let mut after_space = true;
+    let mut first = true;
+    for c in text.chars() {
+      if column >= shift && (after_space || column == shift) && !c.is_whitespace() {
+        if !first {
+          out.push(',');
+        }
+        first = false;
+        let original = (column - shift) as i64;
+        vlq(&mut out, (column as i64) - prev_generated);
+        vlq(&mut out, 0);
+        vlq(&mut out, (line as i64) - prev_line);
+        vlq(&mut out, original - prev_column);
+        prev_generated = column as i64;
+        prev_line = line as i64;
+        prev_column = original;
+      }
+      after_space = c.is_whitespace();
+      column += c.len_utf16();
+    }
+  }
+  out
+}
+
+/// 生成 source map
+/// * `js` - 輸出的 JS 文件
+/// * `map` - source map 文件 (內聯時與 `js` 相同)
+/// * `ts` - 源文件
+/// * `source` - 源文件內容, `inlineSources` 時寫入 `sourcesContent`
+pub(crate) fn source_map(emit: &Emit, js: &Path, map: &Path, ts: &Path, source: Option<&str>) -> String {
+  let dir = map.parent().unwrap_or(Path::new(""));
+  let mut doc =
+    json!({
+    "version": 3,
+    "file": js.file_name().unwrap_or_default().to_string_lossy(),
+    "sourceRoot": "",
+    "sources": [relative(dir, ts)],
+    "names": [],
+    "mappings": mappings(emit),
+  });
+  if let Some(source) = source {
+    doc["sourcesContent"] = json!([source]);
+  }
+  doc.to_string()
+}
+
+/// 讀取 tsconfig.json, 返回 (輸入文件, 選項, 診斷信息)
+fn read_project(
+  tsconfig: &Path,
+  dir: &Path
+) -> Result<(Vec<PathBuf>, Options, Vec<TscDiagnostic>), Error> {
+  let mut options = Options::default();
+  let mut diagnostics = Vec::new();
+  let mut config = Value::Null;
+  if tsconfig.is_file() {
+    let text = std::fs::read_to_string(tsconfig).with_path(tsconfig)?;
+    match serde_json::from_str::<Value>(&strip_jsonc(&text)) {
+      Ok(value) => {
+        config = value;
+      }
+      Err(e) =>
+        diagnostics.push(TscDiagnostic {
+          file: Some(tsconfig.to_path_buf()),
+          line: Some(e.line()),
+          column: Some(e.column()),
+          ..error(t!("ts.builtin_tsconfig", e = e).to_string())
+        }),
+    }
+  } else {
+    debug!("{}", t!("ts.builtin_no_tsconfig", path = tsconfig.display()));
+  }
+  if config.get("extends").is_some() {
+    diagnostics.push(TscDiagnostic {
+      file: Some(tsconfig.to_path_buf()),
+      category: "warning".to_string(),
+      ..error(t!("ts.builtin_extends").to_string())
+    });
+  }
+  if let Some(compiler_options) = config.get("compilerOptions").and_then(Value::as_object) {
+    for (key, value) in compiler_options {
+      if !options.set(key, value, dir) {
+        debug!("{}", t!("ts.builtin_ignored", option = key));
+      }
+    }
+  }
+
+  let strings = |key: &str| -> Option<Vec<String>> {
+    config
+      .get(key)
+      .and_then(Value::as_array)
+      .map(|a| a.iter().filter_map(Value::as_str).map(str::to_string).collect())
+  };
+  let escaped = Pattern::escape(&dir.to_string_lossy());
+  let mut files: Vec<PathBuf> = strings("files")
+    .unwrap_or_default()
+    .iter()
+    .map(|f| dir.join(f))
+    .collect();
+  let include = strings("include").unwrap_or_else(|| {
+    if config.get("files").is_some() { Vec::new() } else { vec!["**/*".to_string()] }
+  });
+  let exclude = strings("exclude").unwrap_or_else(|| {
+    let mut exclude: Vec<String> = ["node_modules", "bower_components", "jspm_packages"]
+      .map(str::to_string)
+      .to_vec();
+    if let Some(out_dir) = &options.out_dir && let Ok(rel) = out_dir.strip_prefix(dir) {
+      exclude.push(rel.to_string_lossy().to_string());
+    }
+    exclude
+  });
+  let exclude: Vec<Pattern> = exclude
+    .iter()
+    .flat_map(|p| {
+      let p = p.trim_start_matches("./").trim_end_matches('/');
+      [format!("{}/{}", escaped, p), format!("{}/{}/**", escaped, p)]
+    })
+    .filter_map(|p| Pattern::new(&p).ok())
+    .collect();
+  for pattern in include {
+    let pattern = pattern.trim_start_matches("./").trim_end_matches('/');
+    // 沒有擴展名和通配符的是目錄
+    let last = pattern.rsplit('/').next().unwrap_or_default();
+    let pattern = if last.contains(['*', '.', '?']) {
+      pattern.to_string()
+    } else {
+      format!("{}/**/*", pattern)
+    };
+    for file in glob(&format!("{}/{}", escaped, pattern))?.flatten() {
+      if !exclude.iter().any(|p| p.matches_path(&file)) && !files.contains(&file) {
+        files.push(file);
+      }
+    }
+  }
+  Ok((files, options, diagnostics))
+}
+
+/// 是否是需要編譯的文件
+fn is_input(path: &Path) -> bool {
+  let name = path.to_string_lossy();
+  name.ends_with(".ts") && !name.ends_with(".d.ts") && path.is_file()
+}
+
+/// 所有輸入文件的共同目錄, 用作默認的 `rootDir`
+fn common_dir(files: &[PathBuf]) -> PathBuf {
+  let mut common = files
+    .first()
+    .and_then(|f| f.parent())
+    .map(Path::to_path_buf)
+    .unwrap_or_default();
+  for file in files.iter().skip(1) {
+    while !file.starts_with(&common) && common.pop() {}
+  }
+  common
+}
+
+/// 使用內置編譯器編譯一個項目
+/// * `args` - tsc 風格的參數, 支持 `--project`, `--sourceMap`, `--inlineSourceMap`, `--inlineSources`,
+///   `--outDir`, `--rootDir`, `--target`, `--module`, `--noEmit`, `--strict`, `--alwaysStrict`,
+///   `--useDefineForClassFields` 和文件, 其他參數被忽略
+/// * `cwd` - 相對路徑的基準目錄 (絕對路徑)
+/// # 返回
+/// * 所有診斷信息, 文件為絕對路徑; 有錯誤時不輸出任何文件
+pub(crate) fn compile(args: &[String], cwd: &Path) -> Result<Vec<TscDiagnostic>, Error> {
+  let args = parse_args(args, cwd);
+  let (files, mut options, mut diagnostics) = if args.files.is_empty() {
+    let project = args.project.clone().unwrap_or_else(|| cwd.to_path_buf());
+    let (tsconfig, dir) = if project.is_dir() {
+      (project.join("tsconfig.json"), project)
+    } else {
+      let dir = project.parent().map(Path::to_path_buf).unwrap_or_default();
+      (project, dir)
+    };
+    read_project(&tsconfig, &dir)?
+  } else {
+    (args.files.clone(), Options::default(), Vec::new())
+  };
+  for (key, value) in &args.options {
+    options.set(key, value, cwd);
+  }
+  if let Some(target) = options.target.as_deref().filter(|t| !SUPPORTED_TARGETS.contains(t)) {
+    diagnostics.push(error(t!("ts.builtin_target", target = target).to_string()));
+  }
+  if diagnostics.iter().any(|d| d.is_error()) {
+    return Ok(diagnostics);
+  }
+
+  let files: Vec<PathBuf> = files
+    .into_iter()
+    .filter(|f| is_input(f))
+    .collect();
+  let root_dir = options.root_dir.clone().unwrap_or_else(|| common_dir(&files));
+  let mut outputs = Vec::new();
+  for file in &files {
+    let source = std::fs::read_to_string(file).with_path(file)?;
+    let js = match &options.out_dir {
+      Some(out_dir) =>
+        match file.strip_prefix(&root_dir) {
+          Ok(rel) => out_dir.join(rel).with_extension("js"),
+          Err(_) => {
+            diagnostics.push(TscDiagnostic {
+              file: Some(file.clone()),
+              ..error(t!("ts.builtin_root_dir", root = root_dir.display()).to_string())
+            });
+            continue;
+          }
+        }
+      None => file.with_extension("js"),
+    };
+    match strip_types(&source, &options) {
+      Ok(emit) => outputs.push((file, source, js, emit)),
+      Err(errors) =>
+        diagnostics.extend(
+          errors.into_iter().map(|d| TscDiagnostic { file: Some(file.clone()), ..d })
+        ),
+    }
+  }
+  if options.no_emit || diagnostics.iter().any(|d| d.is_error()) {
+    return Ok(diagnostics);
+  }
+
+  for (ts, source, js, emit) in outputs {
+    let content = options.inline_sources.then_some(source.as_str());
+    let name = js.file_name().unwrap_or_default().to_string_lossy().to_string();
+    let map_path = js.with_file_name(format!("{}.map", name));
+    if let Some(parent) = js.parent() {
+      std::fs::create_dir_all(parent).with_path(parent)?;
+    }
+    let url = if options.inline_source_map {
+      let map = source_map(&emit, &js, &js, ts, content);
+      Some(format!("data:application/json;base64,{}", base64(map.as_bytes())))
+    } else if options.source_map {
+      let map = source_map(&emit, &js, &map_path, ts, content);
+      std::fs::write(&map_path, map).with_path(&map_path)?;
+      Some(format!("{}.map", name))
+    } else {
+      None
+    };
+    let mut code = emit.code;
+    if let Some(url) = url {
+      code.push_str(&format!("\n//# sourceMappingURL={}", url));
+    }
+    std::fs::write(&js, code).with_path(&js)?;
+  }
+  Ok(diagnostics)
+}
@@ -0,0 +1,215 @@
+//! 解析 tsc 的輸出和 tsconfig.json
+
+use std::path::PathBuf;
+use serde::Serialize;
+
+/// tsc 的一條診斷信息
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub(crate) struct TscDiagnostic {
+  /// 出錯的文件, 全局錯誤(如 tsconfig 錯誤)時為空
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub file: Option<PathBuf>,
+  /// 行, 從 1 開始
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub line: Option<usize>,
+  /// 列, 從 1 開始
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub column: Option<usize>,
+  /// `error`, `warning` 或 `message`
+  pub category: String,
+  /// 錯誤碼, 如 `TS2322`
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub code: Option<String>,
+  pub message: String,
+}
+
+impl TscDiagnostic {
+  /// 是否是錯誤
+  pub(crate) fn is_error(&self) -> bool {
+    self.category == "error"
+  }
+}
+
+impl std::fmt::Display for TscDiagnostic {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    if let Some(file) = &self.file {
+      write!(f, "{}", file.display())?;
+      if let (Some(line), Some(column)) = (self.line, self.column) {
+        write!(f, ":{}:{}", line, column)?;
+      }
+      write!(f, " - ")?;
+    }
+    write!(f, "{}", self.category)?;
+    if let Some(code) = &self.code {
+      write!(f, " {}", code)?;
+    }
+    write!(f, ": {}", self.message)
+  }
+}
+
+/// 去掉 `--pretty` 輸出中的 ANSI 顏色
+fn strip_ansi(line: &str) -> String {
+  let mut out = String::new();
+  let mut chars = line.chars();
+  while let Some(c) = chars.next() {
+    if c == '\x1b' {
+      for c in chars.by_ref() {
+        if c.is_ascii_alphabetic() {
+          break;
+        }
+      }
+    } else {
+      out.push(c);
+    }
+  }
+  out
+}
+
+/// 找到 `error TS1234: ` 的位置: (開始, 類別, 錯誤碼, 消息開始)
+fn find_category(line: &str) -> Option<(usize, &str, &str, usize)> {
+  for category in ["error", "warning", "message"] {
+    let mut from = 0;
+    while let Some(i) = line[from..].find(category).map(|i| i + from) {
+      let rest = &line[i + category.len()..];
+      if let Some(code) = rest.strip_prefix(" TS") {
+        let digits = code.chars().take_while(|c| c.is_ascii_digit()).count();
+        if digits > 0 && code[digits..].starts_with(": ") {
+          let start = i + category.len() + 1;
+          return Some((i, category, &line[start..start + 2 + digits], start + 4 + digits));
+        }
+      }
+      from = i + category.len();
+    }
+  }
+  None
+}
+
+/// 解析位置: `file(line,col)` 或 `file:line:col`
+fn parse_location(head: &str) -> (Option<PathBuf>, Option<usize>, Option<usize>) {
+  let head = head.trim_end_matches([':', ' ', '-']);
+  if head.is_empty() {
+    return (None, None, None);
+  }
+  if let Some(h) = head.strip_suffix(')')
+    && let Some(i) = h.rfind('(')
+    && let Some((line, col)) = h[i + 1..].split_once(',')
+    && let (Ok(line), Ok(col)) = (line.trim().parse(), col.trim().parse()) {
+    return (Some(PathBuf::from(&h[..i])), Some(line), Some(col));
+  }
+  let mut parts = head.rsplitn(3, ':');
+  if let (Some(col), Some(line), Some(file)) = (parts.next(), parts.next(), parts.next())
+    && let (Ok(line), Ok(col)) = (line.parse(), col.parse()) {
+    return (Some(PathBuf::from(file)), Some(line), Some(col));
+  }
+  (Some(PathBuf::from(head)), None, None)
+}
+
+/// 解析 tsc 的輸出
+/// * 支持默認格式 `file(line,col): error TS1234: message` 和 `--pretty` 格式 `file:line:col - error TS1234: message`
+/// * 縮進的行和無法解析的行接到上一條的消息後面
+/// * 第一條之前無法解析的行作為沒有位置的 `message`
+pub(crate) fn parse_output(output: &str) -> Vec<TscDiagnostic> {
+  let mut diagnostics: Vec<TscDiagnostic> = Vec::new();
+  for line in output.lines().map(strip_ansi) {
+    if line.trim().is_empty() {
+      continue;
+    }
+    match find_category(&line) {
+      Some((i, category, code, msg)) if !line.starts_with(' ') => {
+        let (file, line_no, column) = parse_location(&line[..i]);
+        diagnostics.push(TscDiagnostic {
+          file,
+          line: line_no,
+          column,
+          category: category.to_string(),
+          code: Some(code.to_string()),
+          message: line[msg..].trim().to_string(),
+        });
+      }
+      _ => {
+        // `--pretty` 輸出的代碼片段, `~~~` 標記和 `Found 1 error` 總結
+        let trimmed = line.trim();
+        let first = trimmed.split_whitespace().next().unwrap_or_default();
+        if
+          trimmed.chars().all(|c| c == '~' || c.is_whitespace()) ||
+          trimmed.starts_with("Found ") ||
+          (!line.starts_with(' ') && first.chars().all(|c| c.is_ascii_digit()))
+        {
+          continue;
+        }
+        match diagnostics.last_mut() {
+          Some(last) => {
+            last.message.push('\n');
+            last.message.push_str(trimmed);
+          }
+          None =>
+            diagnostics.push(TscDiagnostic {
+              file: None,
+              line: None,
+              column: None,
+              category: "message".to_string(),
+              code: None,
+              message: trimmed.to_string(),
+            }),
+        }
+      }
+    }
+  }
+  diagnostics
+}
+
+/// 去掉 JSONC 中的註釋和結尾的逗號
+pub(crate) fn strip_jsonc(text: &str) -> String {
+  // 先去掉註釋
+  let mut code = String::with_capacity(text.len());
+  let mut chars = text.chars().peekable();
+  let mut in_string = false;
+  while let Some(c) = chars.next() {
+    match (c, chars.peek()) {
+      _ if in_string => {
+        code.push(c);
+        match c {
+          '\\' => code.extend(chars.next()),
+          '"' => {
+            in_string = false;
+          }
+          _ => {}
+        }
+      }
+      ('/', Some('/')) => {
+        while chars.next_if(|&c| c != '\n').is_some() {}
+      }
+      ('/', Some('*')) => {
+        chars.next();
+        let mut prev = ' ';
+        for c in chars.by_ref() {
+          if prev == '*' && c == '/' {
+            break;
+          }
+          prev = c;
+        }
+      }
+      _ => {
+        in_string = c == '"';
+        code.push(c);
+      }
+    }
+  }
+
+  // 再去掉 `}` 和 `]` 前面的逗號
+  let mut out = String::with_capacity(code.len());
+  let mut in_string = false;
+  let mut escaped = false;
+  for (i, c) in code.char_indices() {
+    if in_string {
+      in_string = escaped || c != '"';
+      escaped = !escaped && c == '\\';
+    } else if c == '"' {
+      in_string = true;
+    } else if c == ',' && code[i + 1..].trim_start().starts_with(['}', ']']) {
+      continue;
+    }
+    out.push(c);
+  }
+  out
+}
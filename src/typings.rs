@@ -0,0 +1,301 @@
+//! 為 ts mod 生成 ModLoader API 的類型聲明 (`mod-dev types`)
+//!
+//! 聲明寫到 `types/modloader.d.ts`, 其中 ModLoader 和 SugarCube 的部分是內置的,
+//! 本 mod 的 boot.json 類型根據 boot.json 生成, 並在 tsconfig.json 中引用它。
+
+use std::fs;
+use std::path::{ Path, PathBuf };
+use glob::{ MatchOptions, Pattern };
+use log::{ info, warn };
+use rust_i18n::t;
+use serde_json::{ Map, Value };
+use crate::cofg::Cofg;
+use crate::error::{ Error, WithPath };
+use crate::tsc::strip_jsonc;
+
+/// 聲明文件相對mod文件夾的路徑
+pub(crate) const TYPES_FILE: &str = "types/modloader.d.ts";
+/// ModLoader 和 SugarCube 的聲明
+const MODLOADER_DTS: &str = include_str!("templates/modloader.d.ts");
+
+/// JSON 值對應的 TS 字面量類型
+fn ts_type(value: &Value) -> String {
+  match value {
+    Value::Array(items) => format!("[{}]", items.iter().map(ts_type).collect::<Vec<_>>().join(", ")),
+    Value::Object(map) => ts_object(map, |_, value| ts_type(value)),
+    _ => value.to_string(),
+  }
+}
+
+/// JSON 對象對應的 TS 類型, `field` 返回每個字段的類型
+fn ts_object(map: &Map<String, Value>, field: impl Fn(&str, &Value) -> String) -> String {
+  let fields = map
+    .iter()
+    .map(|(key, value)| format!("{}: {}", serde_json::to_string(key).unwrap(), field(key, value)))
+    .collect::<Vec<_>>();
+  format!("{{ {} }}", fields.join("; "))
+}
+
+/// 生成聲明文件的內容
+/// * `boot_json` - mod的 boot.json
+pub(crate) fn render(boot_json: &Value) -> String {
+  let mut fields = Vec::new();
+  // 值為 null 的字段和沒有寫一樣
+  for key in ["name", "version", "nickName", "dependenceInfo"] {
+    if let Some(value) = boot_json.get(key).filter(|value| !value.is_null()) {
+      fields.push(format!("  {}: {};", key, ts_type(value)));
+    }
+  }
+  // 參數的類型按插件區分, 文件列表會在構建時變化, 保持 string[]
+  if let Some(Value::Array(plugins)) = boot_json.get("addonPlugin") {
+    let plugins = plugins
+      .iter()
+      .map(|plugin| match plugin {
+        Value::Object(map) => {
+          let tweereplacer = plugin["modName"] == "TweeReplacer";
+          ts_object(map, |key, value| match key {
+            "params" if tweereplacer => "TweeReplacerParam[]".to_string(),
+            "params" => "any".to_string(),
+            _ => ts_type(value),
+          })
+        }
+        _ => ts_type(plugin),
+      })
+      .collect::<Vec<_>>();
+    fields.push(format!("  addonPlugin: [{}];", plugins.join(", ")));
+  }
+  let name = boot_json.get("name").filter(|value| value.is_string()).map(ts_type).unwrap_or_else(|| "string".to_string());
+
+  format!(
+    "// 由 mod-dev 生成, 請勿修改, 運行 `mod-dev types` 重新生成\n\n{}\n\
+    /** 本mod的 boot.json */\n\
+    interface ThisModBootJson extends ModBootJson {{\n{}\n}}\n\n\
+    interface ModUtils {{\n  getMod(name: {}): ModInfo<ThisModBootJson> | undefined;\n}}\n",
+    MODLOADER_DTS,
+    fields.join("\n"),
+    name
+  )
+}
+
+/// tsc 的 include 模式是否匹配文件
+/// * 沒有通配符和擴展名的模式是目錄, 匹配其中的所有文件
+fn include_matches(pattern: &str, file: &str) -> bool {
+  let pattern = pattern.trim_start_matches("./").trim_end_matches('/');
+  let is_dir = !pattern.contains(['*', '?']) && Path::new(pattern).extension().is_none();
+  let pattern = if is_dir { format!("{}/**/*", pattern) } else { pattern.to_string() };
+  let options = MatchOptions { require_literal_separator: true, ..MatchOptions::new() };
+  Pattern::new(&pattern).is_ok_and(|p| p.matches_with(file, options))
+}
+
+/// 把註釋換成空格, 字節位置不變
+fn blank_comments(text: &str) -> Vec<u8> {
+  let mut code = text.as_bytes().to_vec();
+  let mut i = 0;
+  let mut in_string = false;
+  while i < code.len() {
+    match (code[i], code.get(i + 1)) {
+      (b'\\', _) if in_string => i += 1,
+      (b'"', _) => in_string = !in_string,
+      (b'/', Some(b'/')) if !in_string => {
+        while i < code.len() && code[i] != b'\n' {
+          code[i] = b' ';
+          i += 1;
+        }
+        continue;
+      }
+      (b'/', Some(b'*')) if !in_string => {
+        let end = text[i + 2..].find("*/").map_or(code.len(), |end| i + 2 + end + 2);
+        for c in &mut code[i..end] {
+          if *c != b'\n' {
+            *c = b' ';
+          }
+        }
+        i = end;
+        continue;
+      }
+      _ => (),
+    }
+    i += 1;
+  }
+  code
+}
+
+/// 在 tsconfig.json 的頂層數組 `key` 末尾加入 `item`, 保留註釋和格式
+/// # 返回
+/// * 修改後的內容, 找不到數組時為 `None`
+fn push_to_array(text: &str, key: &str, item: &str) -> Option<String> {
+  let code = blank_comments(text);
+  let quoted = serde_json::to_string(key).ok()?;
+  let (mut depth, mut in_string, mut start, mut i) = (0, false, None, 0);
+  // 找到頂層的 `"key": [`
+  while i < code.len() {
+    let c = code[i];
+    if in_string {
+      match c {
+        b'\\' => i += 1,
+        b'"' => in_string = false,
+        _ => (),
+      }
+    } else {
+      match c {
+        b'"' if depth == 1 && code[i..].starts_with(quoted.as_bytes()) => {
+          let rest = &text[i + quoted.len()..];
+          let after = code[i + quoted.len()..].iter().position(|c| !c.is_ascii_whitespace())?;
+          if rest.as_bytes()[after] == b':' {
+            let value = after + 1 + code[i + quoted.len() + after + 1..].iter().position(|c| !c.is_ascii_whitespace())?;
+            if rest.as_bytes()[value] != b'[' {
+              return None;
+            }
+            start = Some(i + quoted.len() + value);
+            break;
+          }
+          in_string = true;
+        }
+        b'"' => in_string = true,
+        b'{' | b'[' => depth += 1,
+        b'}' | b']' => depth -= 1,
+        _ => (),
+      }
+    }
+    i += 1;
+  }
+  let start = start?;
+
+  // 數組中最後一個非空白字符
+  let (mut depth, mut in_string, mut escaped, mut last) = (0, false, false, start);
+  for (j, &c) in code.iter().enumerate().skip(start) {
+    if in_string {
+      in_string = escaped || c != b'"';
+      escaped = !escaped && c == b'\\';
+    } else {
+      match c {
+        b'"' => in_string = true,
+        b'[' | b'{' => depth += 1,
+        b']' | b'}' => {
+          depth -= 1;
+          if depth == 0 {
+            break;
+          }
+        }
+        _ => (),
+      }
+    }
+    if !c.is_ascii_whitespace() {
+      last = j;
+    }
+  }
+
+  let item = serde_json::to_string(item).ok()?;
+  let line_start = text[..last].rfind('\n').map_or(0, |n| n + 1);
+  let indent: String = text[line_start..].chars().take_while(|c| *c == ' ' || *c == '\t').collect();
+  let multiline = text[start..last].contains('\n');
+  let insert = match code[last] {
+    b'[' => item,
+    b',' if multiline => format!("\n{}{}", indent, item),
+    b',' => format!(" {}", item),
+    _ if multiline => format!(",\n{}{}", indent, item),
+    _ => format!(", {}", item),
+  };
+  Some(format!("{}{}{}", &text[..=last], insert, &text[last + 1..]))
+}
+
+/// 在 tsconfig.json 中引用聲明文件
+/// * 已經被 `include` 匹配時不修改; 只有 `files` 時加到 `files`
+/// * 直接在原文中插入, 保留註釋和格式; 無法插入時重寫整個文件並警告會丟失註釋
+/// # 返回
+/// * 是否修改了 tsconfig.json
+pub(crate) fn reference_types(tsconfig: &Path) -> Result<bool, Error> {
+  let text = fs::read_to_string(tsconfig).with_path(tsconfig)?;
+  let mut json: Value = serde_json::from_str(&strip_jsonc(&text)).map_err(|e| {
+    Error::Config(t!("types.invalid_tsconfig", path = tsconfig.display(), e = e).to_string())
+  })?;
+  let Some(obj) = json.as_object_mut() else {
+    return Ok(false);
+  };
+  let key = match (obj.get("include"), obj.get("files")) {
+    // 默認包含所有文件
+    (None, None) => return Ok(false),
+    (None, Some(_)) => "files",
+    _ => "include",
+  };
+  let list = obj
+    .entry(key)
+    .or_insert_with(|| Value::Array(Vec::new()));
+  let Some(list) = list.as_array_mut() else {
+    return Ok(false);
+  };
+  let referenced = list.iter().filter_map(Value::as_str).any(|item| {
+    if key == "files" { item.trim_start_matches("./") == TYPES_FILE } else { include_matches(item, TYPES_FILE) }
+  });
+  if referenced {
+    return Ok(false);
+  }
+  list.push(Value::String(TYPES_FILE.to_string()));
+  let text = match push_to_array(&text, key, TYPES_FILE) {
+    Some(text) => text,
+    None => {
+      warn!("{}", t!("types.tsconfig_rewritten", path = tsconfig.display()));
+      serde_json::to_string_pretty(&json)? + "\n"
+    }
+  };
+  fs::write(tsconfig, text).with_path(tsconfig)?;
+  Ok(true)
+}
+
+/// 為mod生成聲明文件, 有 tsconfig.json 時在其中引用
+/// # 返回
+/// * 聲明文件的路徑
+pub(crate) fn write_types(dir: &Path) -> Result<PathBuf, Error> {
+  let boot_json_path = dir.join("boot.json");
+  let text = fs::read_to_string(&boot_json_path).with_path(&boot_json_path)?;
+  let boot_json: Value = serde_json::from_str(&text).map_err(|e| Error::BootJson {
+    path: boot_json_path.clone(),
+    msg: e.to_string(),
+    pos: Some((e.line(), e.column())),
+  })?;
+
+  let path = dir.join(TYPES_FILE);
+  if let Some(parent) = path.parent() {
+    fs::create_dir_all(parent).with_path(parent)?;
+  }
+  fs::write(&path, render(&boot_json)).with_path(&path)?;
+  info!("{}", t!("types.written", path = path.display()));
+
+  let tsconfig = dir.join("tsconfig.json");
+  if !tsconfig.is_file() {
+    warn!("{}", t!("types.no_tsconfig", path = dir.display()));
+  } else if reference_types(&tsconfig)? {
+    info!("{}", t!("types.tsconfig_updated", path = tsconfig.display(), file = TYPES_FILE));
+  }
+  Ok(path)
+}
+
+/// `mod-dev types`
+/// * `dirs` - mod文件夾, 為空時處理 `mods_path` 中所有有 tsconfig.json 的mod
+pub(crate) fn run(cofg: &Cofg, dirs: Vec<PathBuf>) -> Result<(), Error> {
+  let dirs = if dirs.is_empty() {
+    let mut found = Vec::new();
+    for root in cofg.path.mods_path.roots() {
+      let Ok(entries) = fs::read_dir(&root) else {
+        continue;
+      };
+      let mut paths: Vec<PathBuf> = entries.flatten().map(|e| e.path()).collect();
+      paths.sort();
+      found.extend(
+        paths
+          .into_iter()
+          .filter(|p| !p.join(".ig").exists() && p.join("boot.json").is_file() && p.join("tsconfig.json").is_file())
+      );
+    }
+    if found.is_empty() {
+      warn!("{}", t!("types.no_mods"));
+    }
+    found
+  } else {
+    dirs
+  };
+  for dir in dirs {
+    write_types(&dir)?;
+  }
+  Ok(())
+}
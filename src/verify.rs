@@ -0,0 +1,51 @@
+//! 打包後重新打開 zip 檢查結果
+
+use std::fs::File;
+use std::path::Path;
+use rust_i18n::t;
+use crate::error::{ Error, WithPath };
+use crate::inspect::ModZip;
+
+/// 檢查打包好的mod zip
+/// * 條目名稱是合法的 UTF-8 且使用 `/` 分隔
+/// * 內嵌的 boot.json 可以解析
+/// * 列表中的每個文件都在 zip 中
+/// * 除 boot.json 外沒有不在列表中的文件
+/// # 返回
+/// * 所有問題, 為空表示通過
+pub(crate) fn verify_mod_zip(zip_path: &Path) -> Result<Vec<String>, Error> {
+  let mut problems = Vec::new();
+
+  let file = File::open(zip_path).with_path(zip_path)?;
+  let mut archive = zip::ZipArchive::new(file).with_path(zip_path)?;
+  for i in 0..archive.len() {
+    let file = archive.by_index_raw(i).with_path(zip_path)?;
+    match std::str::from_utf8(file.name_raw()) {
+      Ok(name) if name.contains('\\') => {
+        problems.push(t!("verify.backslash", path = name).to_string());
+      }
+      Ok(_) => (),
+      Err(_) => {
+        problems.push(
+          t!("verify.not_utf8", path = String::from_utf8_lossy(file.name_raw())).to_string()
+        );
+      }
+    }
+  }
+
+  let zip = ModZip::open(zip_path)?;
+  let boot_json = match zip.boot_json() {
+    Ok(boot_json) => boot_json,
+    Err(e) => {
+      problems.push(t!("verify.boot_json", e = e).to_string());
+      return Ok(problems);
+    }
+  };
+  for (list, file) in zip.missing(&boot_json) {
+    problems.push(t!("verify.missing", path = file, list = list).to_string());
+  }
+  for file in zip.unlisted(&boot_json) {
+    problems.push(t!("verify.unlisted", path = file).to_string());
+  }
+  Ok(problems)
+}